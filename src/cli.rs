@@ -1,13 +1,28 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::llm::config::{parse_max_tokens, parse_temperature, parse_top_p, GenerationConfig, GenerationSettings};
+use crate::llm::client::DEFAULT_MAX_CONTINUATIONS;
+use crate::llm::streaming::DEFAULT_SECTION_CONCURRENCY;
+use crate::llm::{Budget, CacheMode, FailoverOn, Fixtures, ModelPrice, ProviderOptions, ProviderSpec, RateLimits, RetryPolicy};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "tldrs", about = "Generate docs from codebase using LLMs")]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Suppress all progress output
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Emit progress as machine-readable events on stderr instead of status lines
+    #[arg(long, value_enum, global = true, conflicts_with = "quiet")]
+    pub events: Option<EventFormat>,
 }
 
+// Parsed once at startup, so the size difference between commands does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Generate README.md
@@ -15,18 +30,34 @@ pub enum Commands {
         /// Path to project dir
         #[arg(default_value = ".")]
         path: PathBuf,
-        
-        /// LLM provider to use
-        #[arg(short, long, default_value = "gemini")]
-        provider: LlmProvider,
-        
-        /// API key for the selected provider
-        #[arg(short, long)]
-        api_key: Option<String>,
-        
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+
+        #[command(flatten)]
+        generation: GenerationArgs,
+
+        /// Provider to fall back to when a call fails, as `provider` or `provider:model`, e.g. `openai:gpt-4o` (repeatable; tried in order, keys from the environment)
+        #[arg(long = "fallback", value_name = "PROVIDER[:MODEL]")]
+        fallbacks: Vec<ProviderSpec>,
+
+        /// Failures that move a call on to the next fallback provider
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = FailoverOn::DEFAULT.to_vec())]
+        failover_on: Vec<FailoverOn>,
+
+        #[command(flatten)]
+        cache: CacheArgs,
+
+        #[command(flatten)]
+        budget: BudgetArgs,
+
         /// Use streaming mode to write README sections as they are generated
         #[arg(short, long)]
         streaming: bool,
+
+        /// Number of sections generated at once in streaming mode; they are still written in order
+        #[arg(long, default_value_t = DEFAULT_SECTION_CONCURRENCY, value_parser = parse_concurrency)]
+        concurrency: usize,
         
         /// Custom prompt for README generation (overrides default system prompt)
         #[arg(long)]
@@ -39,13 +70,335 @@ pub enum Commands {
         /// Additional instructions to append to the prompt (works with default or custom prompts)
         #[arg(long)]
         instructions: Option<String>,
+
+        /// Summarize every file and directory with a cheap model first, for repositories larger than the context window
+        #[arg(long)]
+        summarize: bool,
+
+        /// Add a "Project Structure" section with the directory tree and one-line directory descriptions
+        #[arg(long)]
+        structure_section: bool,
+    },
+
+    /// List the models available from a provider
+    Models {
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+
+    /// Print the file analysis and packed context as JSON, without calling an LLM
+    Scan {
+        /// Path to project dir or source archive
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Print compact JSON on a single line
+        #[arg(long)]
+        compact: bool,
+    },
+}
+
+/// Which provider to call and how to reach it
+#[derive(Args, Debug, Clone)]
+pub struct ProviderArgs {
+    /// LLM provider to use
+    #[arg(short, long, default_value = "gemini")]
+    pub provider: LlmProvider,
+
+    /// API key for the selected provider
+    #[arg(short, long)]
+    pub api_key: Option<String>,
+
+    /// Model for README and section calls (all providers except azure)
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Endpoint for the provider, e.g. http://localhost:8000/v1 for openai-compatible or the resource endpoint for azure (ollama defaults to OLLAMA_HOST or http://localhost:11434)
+    #[arg(long)]
+    pub base_url: Option<String>,
+
+    /// Extra HTTP header sent with every request, as `Name: value` (openai, openai-compatible, azure; repeatable)
+    #[arg(short = 'H', long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Azure OpenAI deployment name (defaults to AZURE_OPENAI_DEPLOYMENT)
+    #[arg(long)]
+    pub deployment: Option<String>,
+
+    /// Azure OpenAI API version (defaults to OPENAI_API_VERSION or 2024-10-21)
+    #[arg(long)]
+    pub api_version: Option<String>,
+
+    /// Service-account JSON key for vertex (defaults to GOOGLE_APPLICATION_CREDENTIALS)
+    #[arg(long)]
+    pub credentials: Option<PathBuf>,
+
+    /// Google Cloud project for vertex (defaults to GOOGLE_CLOUD_PROJECT or the key's project)
+    #[arg(long)]
+    pub project: Option<String>,
+
+    /// Google Cloud region for vertex (defaults to GOOGLE_CLOUD_LOCATION or us-central1)
+    #[arg(long)]
+    pub location: Option<String>,
+
+    /// Times to retry a call that hit a rate limit, server error or dropped connection (0 disables retrying)
+    #[arg(long, default_value_t = 3)]
+    pub max_retries: u32,
+
+    /// Longest wait between retries, in seconds; a longer Retry-After from the server fails the call instead
+    #[arg(long, default_value_t = 30)]
+    pub retry_max_delay: u64,
+
+    /// Requests per minute to stay under for each model, across all calls in the run
+    #[arg(long, visible_alias = "rpm", env = "TLDRS_REQUESTS_PER_MINUTE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub requests_per_minute: Option<u32>,
+
+    /// Tokens per minute to stay under for each model; input is estimated from the prompt length
+    #[arg(long, visible_alias = "tpm", env = "TLDRS_TOKENS_PER_MINUTE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub tokens_per_minute: Option<u32>,
+
+    /// Save every HTTP exchange with the provider to fixture files in this directory
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer requests from fixtures saved with --record instead of calling the provider; no API key is needed
+    #[arg(long, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+
+    /// JSON file of replies for the mock provider to give in order, e.g. `[{"text": "# Demo"}, {"error": {"status": 429, "message": "slow down"}}]`
+    #[arg(long, value_name = "FILE")]
+    pub mock_replies: Option<PathBuf>,
+}
+
+impl ProviderArgs {
+    pub fn rate_limits(&self) -> RateLimits {
+        RateLimits {
+            requests_per_minute: self.requests_per_minute,
+            tokens_per_minute: self.tokens_per_minute,
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            max_delay: Duration::from_secs(self.retry_max_delay),
+            ..RetryPolicy::default()
+        }
+    }
+
+    pub fn fixtures(&self) -> Option<Fixtures> {
+        match (&self.record, &self.replay) {
+            (Some(dir), _) => Some(Fixtures::record(dir)),
+            (None, Some(dir)) => Some(Fixtures::replay(dir)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn options(&self) -> ProviderOptions {
+        ProviderOptions {
+            model: self.model.clone(),
+            base_url: self.base_url.clone(),
+            headers: self.headers.clone(),
+            deployment: self.deployment.clone(),
+            api_version: self.api_version.clone(),
+            credentials: self.credentials.clone(),
+            project: self.project.clone(),
+            location: self.location.clone(),
+            retry: self.retry_policy(),
+            rate_limits: self.rate_limits(),
+            fixtures: self.fixtures(),
+            mock_replies: self.mock_replies.clone(),
+            ..ProviderOptions::default()
+        }
     }
 }
 
-#[derive(Clone, ValueEnum)]
+/// How provider replies are cached between runs
+#[derive(Args, Debug, Clone)]
+pub struct CacheArgs {
+    /// Neither reuse nor store cached replies
+    #[arg(long, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Call the provider even when a reply is cached, replacing the cached one
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Hours a cached reply is reused for
+    #[arg(long, value_name = "HOURS", default_value_t = 168)]
+    pub cache_ttl: u64,
+
+    /// Size the reply cache is kept under, in megabytes; the oldest replies go first
+    #[arg(long, value_name = "MB", default_value_t = 100)]
+    pub cache_max_size: u64,
+}
+
+impl CacheArgs {
+    pub fn mode(&self) -> CacheMode {
+        match (self.no_cache, self.refresh) {
+            (true, _) => CacheMode::Off,
+            (false, true) => CacheMode::Refresh,
+            (false, false) => CacheMode::Use,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl.saturating_mul(60 * 60))
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.cache_max_size.saturating_mul(1024 * 1024)
+    }
+}
+
+/// Limits on what a run may spend, and the prices its cost is estimated from
+#[derive(Args, Debug, Clone)]
+pub struct BudgetArgs {
+    /// Input and output tokens the whole run may use; a call that could go over is not made
+    #[arg(long, value_name = "TOKENS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_tokens_total: Option<u64>,
+
+    /// Estimated cost in US dollars the whole run may reach; a call that could go over is not made
+    #[arg(long, value_name = "USD", value_parser = parse_cost)]
+    pub max_cost: Option<f64>,
+
+    /// Price of a model in US dollars per million input and output tokens, as `model=input,output` (repeatable)
+    #[arg(long = "price", value_name = "MODEL=INPUT,OUTPUT", value_parser = parse_price)]
+    pub prices: Vec<(String, ModelPrice)>,
+}
+
+impl BudgetArgs {
+    pub fn budget(&self) -> Budget {
+        Budget { max_tokens: self.max_tokens_total, max_cost: self.max_cost }
+    }
+}
+
+/// Sampling settings for README and section calls
+#[derive(Args, Debug, Clone)]
+pub struct GenerationArgs {
+    /// Sampling temperature, 0 to 2 (both call types)
+    #[arg(long, value_parser = parse_temperature)]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling probability, 0 to 1 (both call types)
+    #[arg(long, value_parser = parse_top_p)]
+    pub top_p: Option<f64>,
+
+    /// Maximum output tokens per call (both call types)
+    #[arg(long, value_parser = parse_max_tokens)]
+    pub max_tokens: Option<u32>,
+
+    /// Stop generating at this sequence (both call types; repeatable)
+    #[arg(long = "stop")]
+    pub stop_sequences: Vec<String>,
+
+    /// Seed for more reproducible output, where the provider supports it (both call types)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Times to ask the model to continue a reply cut off at the output token limit; 0 keeps cut-off replies
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_CONTINUATIONS)]
+    pub max_continuations: u32,
+
+    /// Settings for whole-README calls as `key=value` pairs, e.g. `model=gemini-2.5-pro,max_tokens=8000` (keys: model, temperature, top_p, max_tokens, stop, seed)
+    #[arg(long, env = "TLDRS_MAIN_CONFIG")]
+    pub main_config: Option<GenerationConfig>,
+
+    /// Settings for section calls (streaming mode, structure section) as `key=value` pairs, e.g. `temperature=0.3,max_tokens=1500`
+    #[arg(long, env = "TLDRS_SECTION_CONFIG")]
+    pub section_config: Option<GenerationConfig>,
+}
+
+impl GenerationArgs {
+    /// Per-call settings layered over the flags that apply to both call types
+    pub fn settings(&self) -> GenerationSettings {
+        let shared = GenerationConfig {
+            model: None,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_output_tokens: self.max_tokens,
+            stop_sequences: self.stop_sequences.clone(),
+            seed: self.seed,
+        };
+
+        GenerationSettings {
+            main: shared.merge(&self.main_config.clone().unwrap_or_default()),
+            section: shared.merge(&self.section_config.clone().unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
 pub enum LlmProvider {
     /// Google Gemini
     Gemini,
+    /// Gemini models on Google Cloud Vertex AI, authenticated with a service-account key
+    Vertex,
     /// OpenAI GPT
+    #[value(name = "openai", alias = "open-ai")]
     OpenAI,
-}
\ No newline at end of file
+    /// Any server that speaks the OpenAI chat completions API (vLLM, LM Studio, llama.cpp, gateways)
+    #[value(name = "openai-compatible")]
+    OpenAICompatible,
+    /// Azure OpenAI deployments
+    Azure,
+    /// Anthropic Claude
+    Anthropic,
+    /// Local models served by Ollama
+    Ollama,
+    /// Canned or scripted replies without calling a model, for trying tldrs out and testing offline
+    Mock,
+}
+
+impl LlmProvider {
+    /// The name used on the command line and in the provider registry, e.g. `openai-compatible`
+    pub fn name(&self) -> String {
+        self.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+    }
+}
+
+/// Machine-readable progress formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EventFormat {
+    /// One JSON object per line
+    Json,
+}
+
+/// Parse a `Name: value` header argument
+fn parse_header(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw.split_once(':')
+        .ok_or_else(|| format!("invalid header '{}', expected `Name: value`", raw))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("invalid header '{}', the name is empty", raw));
+    }
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Parse a cost limit in US dollars
+fn parse_cost(raw: &str) -> Result<f64, String> {
+    match raw.trim_start_matches('$').parse::<f64>() {
+        Ok(cost) if cost > 0.0 && cost.is_finite() => Ok(cost),
+        _ => Err(format!("cost must be a positive number of dollars, got `{}`", raw)),
+    }
+}
+
+/// Parse a `model=input,output` price in US dollars per million tokens
+fn parse_price(raw: &str) -> Result<(String, ModelPrice), String> {
+    let invalid = || format!("invalid price '{}', expected `model=input,output` in dollars per million tokens", raw);
+    let (model, prices) = raw.split_once('=').ok_or_else(invalid)?;
+    let (input, output) = prices.split_once(',').ok_or_else(invalid)?;
+    let parse = |price: &str| price.trim().parse::<f64>().ok().filter(|price| *price >= 0.0 && price.is_finite());
+    match (model.trim(), parse(input), parse(output)) {
+        (model, Some(input), Some(output)) if !model.is_empty() => Ok((model.to_string(), ModelPrice { input, output })),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse a concurrency limit of at least one
+fn parse_concurrency(raw: &str) -> Result<usize, String> {
+    match raw.parse::<usize>() {
+        Ok(concurrency) if concurrency > 0 => Ok(concurrency),
+        _ => Err(format!("concurrency must be a positive whole number, got `{}`", raw)),
+    }
+}
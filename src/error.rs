@@ -0,0 +1,181 @@
+use crate::llm::client::{ApiError, ContentBlocked};
+use crate::llm::usage::BudgetExceeded;
+use reqwest::StatusCode;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Why a run failed, grouped by what the user can do about it
+///
+/// Providers pass errors around as `Box<dyn std::error::Error>`; they are
+/// sorted into these categories with `Error::from` at the edges of the
+/// library, so callers can match on them and the CLI can exit with a
+/// distinct code for each (see [`Error::exit_code`]).
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The options are incomplete or contradict each other, or a file they name cannot be read
+    #[error("{message}{}", source.as_ref().map(|source| format!(": {}", source)).unwrap_or_default())]
+    Config { message: String, source: Option<std::io::Error> },
+
+    /// No key or credentials were given for a provider that needs them
+    #[error("No {credential} for {provider}. Set {env_var} or use {flag}")]
+    MissingApiKey { provider: String, credential: &'static str, env_var: &'static str, flag: &'static str },
+
+    /// The provider rejected the key, or the account may not use the model
+    #[error("{api} rejected the credentials ({status}). Check that the API key is valid and has access to the model: {message}")]
+    AuthRejected { api: String, status: StatusCode, message: String },
+
+    /// The provider's quota or rate limit was used up, even after retrying
+    #[error("{api} quota or rate limit exceeded. Wait and try again, lower --concurrency or set --rpm/--tpm: {message}")]
+    QuotaExceeded { api: String, message: String, retry_after: Option<Duration> },
+
+    /// The provider refused the prompt or withheld its reply
+    #[error("{api} blocked the request ({reason}). Exclude the flagged files, adjust the prompt or try another provider")]
+    Blocked { api: String, reason: String },
+
+    /// The provider could not be reached or kept failing with server errors
+    #[error("Could not get a response from {api}: {message}. Check your connection and --base-url, or try again later")]
+    Network { api: String, message: String },
+
+    /// The provider answered with an error not covered above, such as an unknown model
+    #[error("{api} error ({status}): {message}")]
+    Api { api: String, status: StatusCode, message: String },
+
+    /// The provider's reply was not in the expected format
+    #[error("{api} returned a response tldrs could not read: {message}")]
+    InvalidResponse { api: String, message: String },
+
+    /// A call was not made because it could take the run over its budget
+    #[error(transparent)]
+    Budget(#[from] BudgetExceeded),
+
+    /// Reading the project or writing the README failed, at `path` when it is known
+    #[error("{}{source}", path.as_ref().map(|path| format!("{}: ", path.display())).unwrap_or_default())]
+    Io { path: Option<PathBuf>, source: std::io::Error },
+
+    /// Some README sections could not be generated; `source` is the first failure
+    #[error("Failed to generate {}", sections.join("; "))]
+    SectionsFailed { sections: Vec<String>, source: Box<Error> },
+
+    /// Anything not covered above
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    pub fn config(message: impl Into<String>) -> Self {
+        Self::Config { message: message.into(), source: None }
+    }
+
+    /// A file named in the options could not be read
+    pub fn config_file(message: impl Into<String>, source: std::io::Error) -> Self {
+        Self::Config { message: message.into(), source: Some(source) }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        Self::Io { path: Some(path.into()), source }
+    }
+
+    pub fn invalid_response(api: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::InvalidResponse { api: api.into(), message: message.into() }
+    }
+
+    pub fn network(api: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::Network { api: api.into(), message: message.into() }
+    }
+
+    /// Process exit status for the CLI, one per category (listed in the README)
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Config { .. } => 2,
+            Self::MissingApiKey { .. } => 3,
+            Self::AuthRejected { .. } => 4,
+            Self::QuotaExceeded { .. } => 5,
+            Self::Blocked { .. } => 6,
+            Self::Network { .. } => 7,
+            Self::Api { .. } => 8,
+            Self::InvalidResponse { .. } => 9,
+            Self::Budget(_) => 10,
+            Self::Io { .. } => 11,
+            Self::SectionsFailed { source, .. } => source.exit_code(),
+        }
+    }
+}
+
+impl From<ApiError> for Error {
+    fn from(error: ApiError) -> Self {
+        let api = error.api().to_string();
+        let message = error.message().to_string();
+        match error.status() {
+            Some(status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)) => Self::AuthRejected { api, status, message },
+            // Gemini answers a bad key with a plain 400
+            Some(status @ StatusCode::BAD_REQUEST) if message.contains("API_KEY_INVALID") => Self::AuthRejected { api, status, message },
+            Some(StatusCode::TOO_MANY_REQUESTS) => Self::QuotaExceeded { api, message, retry_after: error.retry_after() },
+            Some(status) if error.is_retryable() => Self::Network { api, message: format!("{}: {}", status, message) },
+            Some(status) => Self::Api { api, status, message },
+            None => Self::Network { api, message },
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io { path: None, source }
+    }
+}
+
+impl From<ContentBlocked> for Error {
+    fn from(error: ContentBlocked) -> Self {
+        Self::Blocked { api: error.api().to_string(), reason: error.reason().to_string() }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        let api = error.url().and_then(|url| url.host_str()).unwrap_or("the provider").to_string();
+        if error.is_decode() {
+            Self::InvalidResponse { api, message: error.to_string() }
+        } else {
+            Self::Network { api, message: error.to_string() }
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::invalid_response("The provider", error.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        let error = match error.downcast::<Error>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ApiError>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<ContentBlocked>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<BudgetExceeded>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<reqwest::Error>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        match error.downcast::<serde_json::Error>() {
+            Ok(error) => (*error).into(),
+            Err(error) => Self::Other(error.to_string()),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Generate README files from a codebase using LLMs.
+//!
+//! The [`ReadmeBuilder`] drives a full run: scan a project directory or source
+//! archive, pack the most relevant files into a context, call the selected
+//! provider and optionally write the result to disk. The building blocks are
+//! public as well: [`rag`] for file discovery, [`llm`] for the providers and
+//! prompts, and [`util`] for filters, caches and output helpers.
+
+pub mod cli;
+pub mod error;
+pub mod llm;
+pub mod rag;
+pub mod util;
+
+pub use cli::LlmProvider;
+pub use error::Error;
+pub use llm::LlmGenerator;
+pub use rag::{collect_files, scan, FileContent, ScanResult, ScannedFile, SkippedFile};
+pub use util::analysis::{analyze_project, ScanAnalysis};
+pub use util::readme::{GenerationMode, ReadmeBuilder, ReadmeOutput};
+pub use util::report::{Event, JsonReporter, PlainReporter, QuietReporter, Reporter, SharedReporter, TtyReporter};
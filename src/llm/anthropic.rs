@@ -0,0 +1,157 @@
+use crate::error::Error;
+use crate::llm::chat::{conversation, prompt_chars, system_instruction, CallKind, ChatProvider, Message, ProviderFuture};
+use crate::llm::client::{ContentBlocked, LlmClient, Reply};
+use crate::llm::config::{CallParams, GenerationSettings};
+use crate::util::report::SharedReporter;
+use serde_json::json;
+use std::sync::Arc;
+use anyhow::Result;
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MAIN_MODEL: &str = "claude-sonnet-4-5";
+const SUMMARY_MODEL: &str = "claude-haiku-4-5";
+/// The Messages API rejects temperatures above this, unlike the 0 to 2 accepted elsewhere
+const MAX_TEMPERATURE: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct AnthropicGenerator {
+    client: Arc<LlmClient>,
+    base_url: String,
+    generation: GenerationSettings,
+}
+
+impl ChatProvider for AnthropicGenerator {
+    fn id(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn endpoint(&self) -> String {
+        self.base_url.trim_end_matches('/').to_string()
+    }
+
+    fn client(&self) -> &LlmClient {
+        &self.client
+    }
+
+    fn client_mut(&mut self) -> &mut LlmClient {
+        Arc::make_mut(&mut self.client)
+    }
+
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        let model = if kind == CallKind::Summary { SUMMARY_MODEL } else { MAIN_MODEL };
+        Ok(self.generation.resolve(kind, model))
+    }
+
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        Box::pin(self.request_message(params, messages))
+    }
+
+    /// Model ids available to the API key (`/v1/models`)
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(self.request_models())
+    }
+}
+
+impl AnthropicGenerator {
+    /// Create a generator for the Anthropic Messages API
+    ///
+    /// The endpoint can be redirected (e.g. to a proxy) with `ANTHROPIC_BASE_URL`.
+    pub fn new(api_key: String, reporter: SharedReporter) -> Self {
+        let base_url = std::env::var("ANTHROPIC_BASE_URL").ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        Self {
+            client: Arc::new(LlmClient::new(api_key, reporter)),
+            base_url,
+            generation: GenerationSettings::default(),
+        }
+    }
+
+    /// Send requests to another endpoint instead of `https://api.anthropic.com`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Use these models and sampling settings instead of the built-in ones
+    ///
+    /// The Messages API has no seed, so a configured seed is not sent, and it
+    /// only takes temperatures up to 1.0, so higher ones are sent as 1.0.
+    pub fn with_generation(mut self, generation: GenerationSettings) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    async fn request_models(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let request = self.client.client()
+            .get(format!("{}/v1/models", self.base_url.trim_end_matches('/')))
+            .query(&[("limit", "1000")])
+            .header("x-api-key", self.client.api_key())
+            .header("anthropic-version", ANTHROPIC_VERSION);
+        let response = self.client.send("Anthropic API", request).await?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        let models = response_json["data"].as_array()
+            .map(|models| {
+                models.iter()
+                    .filter_map(|model| model["id"].as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(models)
+    }
+
+    /// Send a request to the Messages API and return the text content
+    async fn request_message(&self, params: &CallParams, messages: &[Message]) -> Result<Reply, Box<dyn std::error::Error>> {
+        // The system prompt goes beside the conversation, not in it
+        let mut body = json!({
+            "model": params.model,
+            "max_tokens": params.max_output_tokens,
+            "temperature": params.temperature.min(MAX_TEMPERATURE),
+            "messages": conversation(messages).collect::<Vec<_>>()
+        });
+        if let Some(system) = system_instruction(messages) {
+            body["system"] = json!(system);
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if !params.stop_sequences.is_empty() {
+            body["stop_sequences"] = json!(params.stop_sequences);
+        }
+
+        let request = self.client.client()
+            .post(format!("{}/v1/messages", self.base_url.trim_end_matches('/')))
+            .header("x-api-key", self.client.api_key())
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(&body);
+        let response = self.client.send_chat("Anthropic API", request, self.id(), &params.model, prompt_chars(messages)).await?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        let usage = &response_json["usage"];
+        self.client.report_usage("anthropic", &params.model, usage["input_tokens"].as_u64(), usage["output_tokens"].as_u64());
+
+        // The reply is a list of content blocks; only text blocks are of interest
+        let text: String = response_json["content"].as_array()
+            .map(|blocks| {
+                blocks.iter()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if response_json["stop_reason"] == "refusal" {
+            return Err(ContentBlocked::new("Anthropic API", "refusal").into());
+        }
+        if text.trim().is_empty() {
+            return Err(Error::invalid_response("Anthropic API", "the reply has no text").into());
+        }
+
+        Ok(Reply { text, truncated: response_json["stop_reason"] == "max_tokens" })
+    }
+}
@@ -0,0 +1,184 @@
+use crate::llm::config::CallParams;
+use crate::util::manifest::content_hash;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bump whenever the entry layout or the key changes, so old entries are ignored
+const CACHE_VERSION: u32 = 2;
+
+/// How long a cached response is reused by default (a week)
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Default limit on the size of the response cache (100 MB)
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Whether provider responses are read from and written to the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Reuse cached responses and store new ones
+    #[default]
+    Use,
+    /// Call the provider every time, replacing what is cached
+    Refresh,
+    /// Neither read nor write the cache
+    Off,
+}
+
+/// Responses from earlier runs stored on disk, one file per call
+///
+/// Entries are keyed by a hash of the provider and its endpoint, the call's
+/// model and sampling settings, and the full prompt, so any change to the code context
+/// or the prompt misses the cache. Like the scan manifest this is purely a
+/// cache: unreadable entries are ignored and the directory can be deleted at
+/// any time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    mode: CacheMode,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    version: u32,
+    /// Unix timestamp of when the response was stored
+    created_at: u64,
+    provider: String,
+    model: String,
+    text: String,
+}
+
+impl ResponseCache {
+    /// A cache in `dir` with the default lifetime and size limit
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), mode: CacheMode::default(), ttl: DEFAULT_CACHE_TTL, max_bytes: DEFAULT_CACHE_MAX_BYTES }
+    }
+
+    pub fn with_mode(mut self, mode: CacheMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Stop reusing responses once they are older than `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Remove the oldest responses once the cache grows past `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn mode(&self) -> CacheMode {
+        self.mode
+    }
+
+    /// Key for a call: everything that decides what the provider answers
+    pub fn key(provider: &str, endpoint: &str, params: &CallParams, prompt: &str) -> String {
+        let call = json!({
+            "version": CACHE_VERSION,
+            "provider": provider,
+            "endpoint": endpoint,
+            "model": params.model,
+            "temperature": params.temperature,
+            "top_p": params.top_p,
+            "max_output_tokens": params.max_output_tokens,
+            "stop_sequences": params.stop_sequences,
+            "seed": params.seed,
+            "prompt_hash": content_hash(prompt),
+        });
+        content_hash(&call.to_string())
+    }
+
+    /// The stored response for `key`, if there is a fresh one and the cache is being read
+    pub fn get(&self, key: &str) -> Option<String> {
+        if self.mode != CacheMode::Use {
+            return None;
+        }
+
+        let path = self.entry_path(key);
+        let entry = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<CachedResponse>(&raw).ok())
+            .filter(|entry| entry.version == CACHE_VERSION)?;
+        if self.is_expired(entry.created_at) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        Some(entry.text)
+    }
+
+    /// Store a response, then trim the cache back under its size limit
+    pub fn put(&self, key: &str, provider: &str, model: &str, text: &str) -> std::io::Result<()> {
+        if self.mode == CacheMode::Off {
+            return Ok(());
+        }
+
+        let entry = CachedResponse {
+            version: CACHE_VERSION,
+            created_at: now(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            text: text.to_string(),
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        // Write then rename, so a concurrent reader never sees half an entry
+        let path = self.entry_path(key);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&partial, serde_json::to_string(&entry)?)?;
+        std::fs::rename(&partial, &path)?;
+
+        self.prune()
+    }
+
+    /// Remove expired entries, then the oldest ones until the cache fits in its size limit
+    fn prune(&self) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || entry.path().extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            let created_at = modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            if self.is_expired(created_at) {
+                std::fs::remove_file(entry.path())?;
+            } else {
+                entries.push((modified, metadata.len(), entry.path()));
+            }
+        }
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort();
+        for (_, size, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            std::fs::remove_file(path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    fn is_expired(&self, created_at: u64) -> bool {
+        now().saturating_sub(created_at) > self.ttl.as_secs()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
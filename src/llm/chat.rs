@@ -0,0 +1,253 @@
+use crate::llm::client::{LlmClient, Reply, RetryPolicy, CONTINUE_PROMPT};
+use crate::llm::cache::ResponseCache;
+use crate::llm::config::CallParams;
+use crate::llm::fixtures::Fixtures;
+use crate::llm::rate_limit::RateLimits;
+use crate::llm::streaming::{clean_markdown_response, LlmApiClient, TextSink, DEFAULT_CONTEXT_BUDGET};
+use crate::llm::usage::UsageTracker;
+use crate::util::report::SharedReporter;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// System prompt for README sections, followed by the user's guidelines
+const SECTION_SYSTEM_PROMPT: &str = "You are a technical writing assistant. Generate only raw markdown content without wrapping it in code blocks. Do not include opinions or subjective commentary. Follow these guidelines while completing the specific task:";
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize source code for other language models. Be terse and factual. Output plain text only.";
+
+/// Who a message in a conversation is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Instructions that frame the whole conversation
+    System,
+    User,
+    /// The model's own earlier replies
+    Assistant,
+}
+
+/// One message of a conversation sent to a [`ChatProvider`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: Role::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: Role::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: Role::Assistant, content: content.into() }
+    }
+}
+
+/// The kinds of call tldrs makes, each with its own model and sampling defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// Fast mode's single README call
+    Main,
+    /// Streaming sections and the project structure section
+    Section,
+    /// File and directory summaries, on a cheap, fast model
+    Summary,
+}
+
+/// A future returned by a [`ChatProvider`]
+pub type ProviderFuture<'a, T> = LocalBoxFuture<'a, Result<T, Box<dyn std::error::Error>>>;
+
+/// An LLM API that answers conversations of system, user and assistant messages
+///
+/// This is everything a provider has to implement: how to send one request
+/// and read its reply. Prompts, caching, budgets, rate limits and
+/// continuations are handled once for every provider, through the
+/// [`LlmApiClient`] implementation all providers share. The trait is
+/// dyn-compatible, so providers can be picked at run time from a
+/// [`ProviderRegistry`](crate::llm::registry::ProviderRegistry).
+pub trait ChatProvider: fmt::Debug {
+    /// Short name used in cache keys, usage reports and rate limits, e.g. `openai`
+    fn id(&self) -> &'static str;
+
+    fn client(&self) -> &LlmClient;
+
+    fn client_mut(&mut self) -> &mut LlmClient;
+
+    /// Model and sampling settings for a kind of call
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>>;
+
+    /// Send one request and return the reply
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply>;
+
+    /// Send one request, handing text to `on_text` as it arrives, and return whether the reply was cut off
+    ///
+    /// Providers without streaming support hand over the whole reply at once.
+    fn chat_streaming<'a>(
+        &'a self,
+        params: &'a CallParams,
+        messages: &'a [Message],
+        on_text: &'a mut TextSink<'_>,
+    ) -> ProviderFuture<'a, bool> {
+        Box::pin(async move {
+            let reply = self.chat(params, messages).await?;
+            on_text(&reply.text)?;
+            Ok(reply.truncated)
+        })
+    }
+
+    /// Check that the provider can serve requests before any work is sent to it
+    fn prepare(&mut self) -> ProviderFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Models the provider can serve
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>>;
+
+    /// Where calls are sent, such as the base URL or deployment
+    ///
+    /// Part of the response cache key, so servers that use the same model
+    /// names do not share replies.
+    fn endpoint(&self) -> String {
+        String::new()
+    }
+
+    /// Size of the packed code context this provider can take, in bytes
+    fn context_budget(&self) -> usize {
+        DEFAULT_CONTEXT_BUDGET
+    }
+
+    fn reporter(&self) -> &SharedReporter {
+        self.client().reporter()
+    }
+
+    /// Retry failed calls according to `retry` instead of the default policy
+    fn with_retry_policy(self, retry: RetryPolicy) -> Self where Self: Sized {
+        self.with_client(|client| client.with_retry_policy(retry))
+    }
+
+    /// Hold calls back to stay under these requests and tokens per minute
+    fn with_rate_limits(self, limits: RateLimits) -> Self where Self: Sized {
+        self.with_client(|client| client.with_rate_limits(limits))
+    }
+
+    /// Reuse and store replies in this response cache
+    fn with_response_cache(self, cache: Option<ResponseCache>) -> Self where Self: Sized {
+        self.with_client(|client| client.with_cache(cache))
+    }
+
+    /// Count token usage in this tracker and hold calls to its budget
+    fn with_usage_tracker(self, usage: Option<Arc<UsageTracker>>) -> Self where Self: Sized {
+        self.with_client(|client| client.with_usage_tracker(usage))
+    }
+
+    /// Record HTTP exchanges to these fixtures, or replay them instead of calling the API
+    fn with_fixtures(self, fixtures: Option<Fixtures>) -> Self where Self: Sized {
+        self.with_client(|client| client.with_fixtures(fixtures))
+    }
+
+    /// Ask for at most `max_continuations` more replies after one is cut off at the output token limit
+    fn with_max_continuations(self, max_continuations: u32) -> Self where Self: Sized {
+        self.with_client(|client| client.with_max_continuations(max_continuations))
+    }
+
+    /// Replace the client with a reconfigured copy
+    fn with_client(mut self, configure: impl FnOnce(LlmClient) -> LlmClient) -> Self where Self: Sized {
+        let client = self.client_mut();
+        *client = configure(client.clone());
+        self
+    }
+}
+
+/// Prompts for each kind of call, built the same way for every provider
+impl<P: ChatProvider + ?Sized> LlmApiClient for P {
+    async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let content = call(self, CallKind::Section, &section_messages(section_prompt, custom_prompt)).await?;
+        Ok(clean_markdown_response(&content))
+    }
+
+    async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let messages = [
+            Message::system(custom_prompt),
+            Message::user(format!(
+                "Analyze this codebase and generate a comprehensive README.md. Output raw markdown content only, no code block wrappers:\n\n{}",
+                context
+            )),
+        ];
+        let content = call(self, CallKind::Main, &messages).await?;
+        Ok(clean_markdown_response(&content))
+    }
+
+    async fn call_summary(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let messages = [Message::system(SUMMARY_SYSTEM_PROMPT), Message::user(prompt)];
+        let content = call(self, CallKind::Summary, &messages).await?;
+        Ok(content.trim().to_string())
+    }
+
+    async fn stream_with_custom_context(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let params = self.params(CallKind::Section)?;
+        let messages = section_messages(section_prompt, custom_prompt);
+        let prompt = serde_json::to_string(&messages)?;
+        self.client().call_streaming(self.id(), &self.endpoint(), &params, &prompt, on_text, async |partial, on_text| {
+            self.chat_streaming(&params, &continued(&messages, partial), on_text).await
+        }).await
+    }
+}
+
+/// Make a call through the provider's client, or reuse a cached reply
+async fn call<P: ChatProvider + ?Sized>(
+    provider: &P,
+    kind: CallKind,
+    messages: &[Message],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let params = provider.params(kind)?;
+    let prompt = serde_json::to_string(messages)?;
+    provider.client().call(provider.id(), &provider.endpoint(), &params, &prompt, async |partial| {
+        provider.chat(&params, &continued(messages, partial)).await
+    }).await
+}
+
+fn section_messages(section_prompt: &str, custom_prompt: &str) -> Vec<Message> {
+    vec![
+        Message::system(format!("{}\n\n{}", SECTION_SYSTEM_PROMPT, custom_prompt)),
+        Message::user(section_prompt),
+    ]
+}
+
+/// `messages`, followed by the cut-off reply and a request to continue it when there is one
+fn continued(messages: &[Message], partial: Option<&str>) -> Vec<Message> {
+    let mut messages = messages.to_vec();
+    if let Some(partial) = partial {
+        messages.push(Message::assistant(partial));
+        messages.push(Message::user(CONTINUE_PROMPT));
+    }
+    messages
+}
+
+/// Characters of prompt in `messages`, from which rate limits estimate input tokens
+pub fn prompt_chars(messages: &[Message]) -> usize {
+    messages.iter().map(|message| message.content.len()).sum()
+}
+
+/// The system messages joined into one instruction, for APIs that take it apart from the conversation
+pub fn system_instruction(messages: &[Message]) -> Option<String> {
+    let system: Vec<&str> = messages.iter()
+        .filter(|message| message.role == Role::System)
+        .map(|message| message.content.as_str())
+        .collect();
+    (!system.is_empty()).then(|| system.join("\n\n"))
+}
+
+/// The conversation without its system messages
+pub fn conversation(messages: &[Message]) -> impl Iterator<Item = &Message> {
+    messages.iter().filter(|message| message.role != Role::System)
+}
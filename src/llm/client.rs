@@ -1,20 +1,255 @@
-use reqwest::Client;
-use std::time::Duration;
+use crate::llm::cache::ResponseCache;
+use crate::llm::config::CallParams;
+use crate::llm::fixtures::{FixtureMode, Fixtures};
+use crate::llm::rate_limit::{self, estimate_tokens, RateLimits};
+use crate::llm::streaming::TextSink;
+use crate::llm::usage::{BudgetExceeded, Reservation, UsageTracker};
+use crate::util::report::{Event, SharedReporter};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+/// How failed provider calls are retried
+///
+/// Rate limits (429), server errors (5xx), timeouts and dropped connections
+/// are retried with exponential backoff and jitter. Other errors, such as a
+/// rejected key (401) or a bad request (400), fail straight away.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each retry after it
+    pub base_delay: Duration,
+    /// Longest wait between attempts; a longer `Retry-After` ends retrying
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fail on the first error
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Self::default() }
+    }
+
+    /// Backoff before retry number `retry` (counting from zero), between half and all of the exponential delay
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        // Spread retries from concurrent calls so they do not hit the server together
+        let jitter = RandomState::new().hash_one(Instant::now()) as f64 / u64::MAX as f64;
+        delay.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+/// A provider call that failed, with the HTTP status when the server answered
+#[derive(Debug)]
+pub struct ApiError {
+    api: String,
+    status: Option<StatusCode>,
+    message: String,
+    retry_after: Option<Duration>,
+    /// The request timed out or the connection dropped
+    transient: bool,
+}
+
+impl ApiError {
+    /// An error that is never retried, with the HTTP status if there was one
+    pub fn new(api: &str, status: Option<StatusCode>, message: impl Into<String>) -> Self {
+        Self { api: api.to_string(), status, message: message.into(), retry_after: None, transient: false }
+    }
+
+    /// An error for a call that never got an HTTP response
+    pub fn transport(api: &str, error: reqwest::Error) -> Self {
+        Self {
+            api: api.to_string(),
+            status: None,
+            // Refused connections and DNS failures are usually configuration mistakes, not worth waiting out
+            transient: error.is_timeout() || (error.is_request() && !error.is_connect()),
+            message: error.to_string(),
+            retry_after: None,
+        }
+    }
+
+    /// The provider's API, e.g. `OpenAI API`
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    /// The HTTP status, if the server answered
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
+
+    /// The response body, or what went wrong with the connection
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// How long the server asked to wait before trying again
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Whether trying the same call again might succeed
+    pub fn is_retryable(&self) -> bool {
+        match self.status {
+            Some(status) => status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+                || status.is_server_error(),
+            None => self.transient,
+        }
+    }
+
+    async fn from_response(api: &str, response: Response) -> Self {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let message = response.text().await.unwrap_or_default();
+        Self { api: api.to_string(), status: Some(status), message, retry_after, transient: false }
+    }
+
+    /// Short description for retry notices
+    fn reason(&self) -> String {
+        match self.status {
+            Some(status) => status.to_string(),
+            None => "request failed".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => write!(f, "{} error ({}): {}", self.api, status, self.message),
+            None => write!(f, "{} request failed: {}", self.api, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// The provider answered, but refused the prompt or withheld its reply (safety filters, refusals)
 #[derive(Debug)]
+pub struct ContentBlocked {
+    api: String,
+    reason: String,
+}
+
+impl ContentBlocked {
+    pub fn new(api: &str, reason: impl Into<String>) -> Self {
+        Self { api: api.to_string(), reason: reason.into() }
+    }
+
+    pub fn api(&self) -> &str {
+        &self.api
+    }
+
+    /// The reason the provider gave for blocking, e.g. `SAFETY` or `content_filter`
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for ContentBlocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} refused to generate a response ({})", self.api, self.reason)
+    }
+}
+
+impl std::error::Error for ContentBlocked {}
+
+/// Continuation requests made by default when a reply stops at the output token limit
+pub const DEFAULT_MAX_CONTINUATIONS: u32 = 2;
+
+/// Sent after a cut-off reply to ask the model to pick up where it stopped
+pub const CONTINUE_PROMPT: &str = "Your reply was cut off by the output length limit. \
+Continue exactly where it stopped, mid-sentence or mid-code-block if need be. \
+Do not repeat anything already written and do not add any preamble.";
+
+/// The text of one provider call, and whether it stopped at the output token limit
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reply {
+    pub text: String,
+    pub truncated: bool,
+}
+
+/// Wait requested by `retry-after-ms` or `Retry-After` (in seconds; HTTP dates are not supported)
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<f64>().ok();
+    let seconds = header("retry-after-ms").map(|ms| ms / 1000.0).or_else(|| header("retry-after"))?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[derive(Clone)]
 pub struct LlmClient {
     client: Client,
     api_key: String,
+    reporter: SharedReporter,
+    retry: RetryPolicy,
+    limits: RateLimits,
+    cache: Option<ResponseCache>,
+    usage: Option<Arc<UsageTracker>>,
+    fixtures: Option<Fixtures>,
+    max_continuations: u32,
 }
 
 impl LlmClient {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: String, reporter: SharedReporter) -> Self {
+        Self::with_timeout(api_key, reporter, Duration::from_secs(120))
+    }
+
+    pub fn with_timeout(api_key: String, reporter: SharedReporter, timeout: Duration) -> Self {
         let client = Client::builder()
-            .timeout(Duration::from_secs(120))
+            .timeout(timeout)
             .build()
             .expect("Failed to create HTTP client");
         
-        Self { client, api_key }
+        Self { client, api_key, reporter, retry: RetryPolicy::default(), limits: RateLimits::default(), cache: None, usage: None, fixtures: None, max_continuations: DEFAULT_MAX_CONTINUATIONS }
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Option<ResponseCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_usage_tracker(mut self, usage: Option<Arc<UsageTracker>>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Record HTTP exchanges to these fixtures, or replay them instead of sending requests
+    pub fn with_fixtures(mut self, fixtures: Option<Fixtures>) -> Self {
+        self.fixtures = fixtures;
+        self
+    }
+
+    /// Ask for at most `max_continuations` more replies after one is cut off; 0 keeps cut-off replies as they are
+    pub fn with_max_continuations(mut self, max_continuations: u32) -> Self {
+        self.max_continuations = max_continuations;
+        self
     }
     
     pub fn client(&self) -> &Client {
@@ -24,4 +259,257 @@ impl LlmClient {
     pub fn api_key(&self) -> &str {
         &self.api_key
     }
+
+    pub fn reporter(&self) -> &SharedReporter {
+        &self.reporter
+    }
+
+    /// Whether requests are answered from fixtures instead of being sent
+    pub fn replaying(&self) -> bool {
+        self.fixtures.as_ref().is_some_and(|fixtures| fixtures.mode() == FixtureMode::Replay)
+    }
+
+    /// Wait until a call to `model` with a prompt of `prompt_chars` characters fits under the rate limits
+    ///
+    /// Input tokens are estimated from the prompt length; output tokens are
+    /// counted once the provider reports them through [`Self::report_usage`].
+    pub async fn acquire(&self, provider: &str, model: &str, prompt_chars: usize) {
+        rate_limit::acquire(provider, model, &self.limits, estimate_tokens(prompt_chars), |wait| {
+            self.reporter.report(&Event::RateLimited {
+                provider: provider.to_string(),
+                model: model.to_string(),
+                delay_ms: wait.as_millis() as u64,
+            });
+        })
+        .await;
+    }
+
+    /// Send a request, retrying transient failures, and return the successful response
+    ///
+    /// Anything other than a 2xx status becomes an [`ApiError`] carrying the
+    /// status and response body. `api` names the provider in errors and
+    /// retry notices.
+    pub async fn send(&self, api: &str, request: RequestBuilder) -> Result<Response, ApiError> {
+        self.send_limited(api, request, None).await
+    }
+
+    /// Like [`Self::send`] for a call to `model`, waiting for the rate limits before every attempt
+    ///
+    /// Retries count against the limits like first attempts, since the
+    /// provider counts them too.
+    pub async fn send_chat(&self, api: &str, request: RequestBuilder, provider: &str, model: &str, prompt_chars: usize) -> Result<Response, ApiError> {
+        self.send_limited(api, request, Some((provider, model, prompt_chars))).await
+    }
+
+    async fn send_limited(&self, api: &str, request: RequestBuilder, limited: Option<(&str, &str, usize)>) -> Result<Response, ApiError> {
+        let mut retry = 0;
+        loop {
+            if let Some((provider, model, prompt_chars)) = limited {
+                self.acquire(provider, model, prompt_chars).await;
+            }
+            // Bodies are always buffered JSON or forms, so every request can be cloned
+            let Some(attempt) = request.try_clone() else {
+                return Self::check(api, self.exchange(api, request).await).await;
+            };
+            let error = match Self::check(api, self.exchange(api, attempt).await).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+
+            let Some(delay) = self.retry_delay(&error, retry) else {
+                return Err(error);
+            };
+            retry += 1;
+            self.reporter.report(&Event::RetryScheduled {
+                api: api.to_string(),
+                reason: error.reason(),
+                retry,
+                max_retries: self.retry.max_retries,
+                delay_ms: delay.as_millis() as u64,
+            });
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Send one attempt, through the fixtures when recording or replaying
+    async fn exchange(&self, api: &str, request: RequestBuilder) -> Result<Response, ApiError> {
+        match &self.fixtures {
+            Some(fixtures) => fixtures.exchange(api, request, &self.reporter).await,
+            None => request.send().await.map_err(|e| ApiError::transport(api, e)),
+        }
+    }
+
+    async fn check(api: &str, result: Result<Response, ApiError>) -> Result<Response, ApiError> {
+        let response = result?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(ApiError::from_response(api, response).await)
+        }
+    }
+
+    /// How long to wait before retrying after `error`, or `None` to give up
+    fn retry_delay(&self, error: &ApiError, retry: u32) -> Option<Duration> {
+        if !error.is_retryable() || retry >= self.retry.max_retries {
+            return None;
+        }
+        // Replayed failures answer instantly, so there is nothing to wait for
+        if self.replaying() {
+            return Some(Duration::ZERO);
+        }
+        match error.retry_after() {
+            // A long wait, e.g. for a daily quota, is better left to the user
+            Some(wait) if wait > self.retry.max_delay => None,
+            Some(wait) => Some(wait),
+            None => Some(self.retry.backoff(retry)),
+        }
+    }
+
+    /// Make a call through the response cache, holding it to the run's budget
+    ///
+    /// A cached reply is returned without calling the provider. Otherwise the
+    /// call's share of the budget is set aside and `call` is made; while its
+    /// reply is cut off at the output token limit, `call` is made again with
+    /// the text so far so the model can continue it, up to the continuation
+    /// limit. The stitched reply is cached. `prompt` must hold everything sent
+    /// besides the settings in `params` and the `endpoint` it goes to.
+    pub async fn call(
+        &self,
+        provider: &str,
+        endpoint: &str,
+        params: &CallParams,
+        prompt: &str,
+        mut call: impl AsyncFnMut(Option<&str>) -> Result<Reply, Box<dyn std::error::Error>>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let key = self.cache.as_ref().map(|_| ResponseCache::key(provider, endpoint, params, prompt));
+        if let Some(text) = self.cached(provider, &params.model, key.as_deref()) {
+            return Ok(text);
+        }
+
+        let mut text = String::new();
+        let mut round = 0;
+        loop {
+            let _reservation = self.reserve(provider, params, prompt.len() + text.len())?;
+            let reply = call((round > 0).then_some(text.as_str())).await?;
+            text.push_str(&reply.text);
+            if !reply.truncated || !self.continue_reply(provider, params, round) {
+                break;
+            }
+            round += 1;
+        }
+        self.store(key.as_deref(), provider, &params.model, &text);
+        Ok(text)
+    }
+
+    /// Like [`Self::call`] for a streamed call; `call` returns whether the reply was cut off
+    ///
+    /// Continuations are streamed to `on_text` straight after the text they
+    /// continue. A cached reply is handed to `on_text` in one piece.
+    pub async fn call_streaming(
+        &self,
+        provider: &str,
+        endpoint: &str,
+        params: &CallParams,
+        prompt: &str,
+        on_text: &mut TextSink<'_>,
+        mut call: impl AsyncFnMut(Option<&str>, &mut TextSink<'_>) -> Result<bool, Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = self.cache.as_ref().map(|_| ResponseCache::key(provider, endpoint, params, prompt));
+        if let Some(text) = self.cached(provider, &params.model, key.as_deref()) {
+            return on_text(&text);
+        }
+
+        let mut reply = String::new();
+        let mut round = 0;
+        loop {
+            let _reservation = self.reserve(provider, params, prompt.len() + reply.len())?;
+            let partial = (round > 0).then(|| reply.clone());
+            let truncated = call(partial.as_deref(), &mut |text| {
+                reply.push_str(text);
+                on_text(text)
+            }).await?;
+            if !truncated || !self.continue_reply(provider, params, round) {
+                break;
+            }
+            round += 1;
+        }
+        self.store(key.as_deref(), provider, &params.model, &reply);
+        Ok(())
+    }
+
+    /// Whether to ask for another continuation after `round` earlier ones, warning when the limit is reached
+    fn continue_reply(&self, provider: &str, params: &CallParams, round: u32) -> bool {
+        if round >= self.max_continuations {
+            let message = match round {
+                0 => format!("{} response was cut off at the {} token limit", params.model, params.max_output_tokens),
+                1 => format!("{} response was still cut off at the {} token limit after a continuation", params.model, params.max_output_tokens),
+                _ => format!(
+                    "{} response was still cut off at the {} token limit after {} continuations",
+                    params.model, params.max_output_tokens, round
+                ),
+            };
+            self.reporter.report(&Event::Warning { message });
+            return false;
+        }
+        self.reporter.report(&Event::ReplyContinued {
+            provider: provider.to_string(),
+            model: params.model.clone(),
+            round: round + 1,
+            max_rounds: self.max_continuations,
+        });
+        true
+    }
+
+    /// Set aside the call's share of the budget, if the run has one
+    fn reserve(&self, provider: &str, params: &CallParams, prompt_chars: usize) -> Result<Option<Reservation<'_>>, BudgetExceeded> {
+        self.usage.as_deref()
+            .map(|usage| usage.reserve(provider, &params.model, prompt_chars, params.max_output_tokens))
+            .transpose()
+    }
+
+    /// The cached reply for `key`, if there is one
+    fn cached(&self, provider: &str, model: &str, key: Option<&str>) -> Option<String> {
+        let text = self.cache.as_ref()?.get(key?)?;
+        self.reporter.report(&Event::CacheHit { provider: provider.to_string(), model: model.to_string() });
+        Some(text)
+    }
+
+    /// Cache a reply; the call already succeeded, so a failure to store it is only a warning
+    fn store(&self, key: Option<&str>, provider: &str, model: &str, text: &str) {
+        let (Some(cache), Some(key)) = (&self.cache, key) else {
+            return;
+        };
+        if let Err(err) = cache.put(key, provider, model, text) {
+            self.reporter.report(&Event::Warning {
+                message: format!("Could not cache the response in {}: {}", cache.dir().display(), err),
+            });
+        }
+    }
+
+    /// Report token usage for a call, if the provider returned any
+    pub fn report_usage(&self, provider: &str, model: &str, input_tokens: Option<u64>, output_tokens: Option<u64>) {
+        if input_tokens.is_none() && output_tokens.is_none() {
+            return;
+        }
+        if self.limits.tokens_per_minute.is_some() {
+            rate_limit::record_tokens(provider, model, output_tokens.unwrap_or(0));
+        }
+        if let Some(usage) = &self.usage {
+            usage.record(provider, model, input_tokens.unwrap_or(0), output_tokens.unwrap_or(0));
+        }
+
+        self.reporter.report(&Event::TokensUsed {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens: input_tokens.unwrap_or(0),
+            output_tokens: output_tokens.unwrap_or(0),
+        });
+    }
+}
+
+impl fmt::Debug for LlmClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the API key
+        f.debug_struct("LlmClient").field("client", &self.client).finish_non_exhaustive()
+    }
 }
@@ -0,0 +1,138 @@
+use crate::llm::chat::CallKind;
+use std::str::FromStr;
+
+/// Sampling settings for one kind of call
+///
+/// Unset fields keep the provider's defaults for that call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationConfig {
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_output_tokens: Option<u32>,
+    pub stop_sequences: Vec<String>,
+    pub seed: Option<u64>,
+}
+
+/// Settings for whole-README calls and per-section calls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationSettings {
+    /// Fast mode's single README call
+    pub main: GenerationConfig,
+    /// Streaming sections and the project structure section
+    pub section: GenerationConfig,
+}
+
+/// Settings for a single request, with every default filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallParams {
+    pub model: String,
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub max_output_tokens: u32,
+    pub stop_sequences: Vec<String>,
+    pub seed: Option<u64>,
+}
+
+impl GenerationConfig {
+    /// Combine two configs, preferring values set in `other`
+    pub fn merge(&self, other: &GenerationConfig) -> GenerationConfig {
+        GenerationConfig {
+            model: other.model.clone().or_else(|| self.model.clone()),
+            temperature: other.temperature.or(self.temperature),
+            top_p: other.top_p.or(self.top_p),
+            max_output_tokens: other.max_output_tokens.or(self.max_output_tokens),
+            stop_sequences: if other.stop_sequences.is_empty() {
+                self.stop_sequences.clone()
+            } else {
+                other.stop_sequences.clone()
+            },
+            seed: other.seed.or(self.seed),
+        }
+    }
+
+    /// Fill unset values with a call type's defaults
+    pub fn resolve(&self, model: &str, max_output_tokens: u32, temperature: f64) -> CallParams {
+        CallParams {
+            model: self.model.clone().unwrap_or_else(|| model.to_string()),
+            temperature: self.temperature.unwrap_or(temperature),
+            top_p: self.top_p,
+            max_output_tokens: self.max_output_tokens.unwrap_or(max_output_tokens),
+            stop_sequences: self.stop_sequences.clone(),
+            seed: self.seed,
+        }
+    }
+}
+
+impl GenerationSettings {
+    /// Settings for a kind of call on `model`, unless another model is configured for it
+    ///
+    /// Summaries always use the provider's defaults.
+    pub fn resolve(&self, kind: CallKind, model: &str) -> CallParams {
+        match kind {
+            CallKind::Main => self.main.resolve(model, 4000, 0.7),
+            CallKind::Section => self.section.resolve(model, 1000, 0.7),
+            CallKind::Summary => GenerationConfig::default().resolve(model, 300, 0.2),
+        }
+    }
+}
+
+/// Parses `key=value` pairs separated by commas, e.g.
+/// `model=gpt-4o-mini,temperature=0.2,max_tokens=1500`
+///
+/// Keys are `model`, `temperature`, `top_p`, `max_tokens`, `stop` (repeatable)
+/// and `seed`.
+impl FromStr for GenerationConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = GenerationConfig::default();
+
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, got `{}`", pair))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "model" => config.model = Some(value.to_string()),
+                "temperature" => config.temperature = Some(parse_temperature(value)?),
+                "top_p" => config.top_p = Some(parse_top_p(value)?),
+                "max_tokens" | "max_output_tokens" => config.max_output_tokens = Some(parse_max_tokens(value)?),
+                "stop" => config.stop_sequences.push(value.to_string()),
+                "seed" => config.seed = Some(value.parse().map_err(|_| format!("invalid seed `{}`", value))?),
+                other => {
+                    return Err(format!(
+                        "unknown setting `{}` (expected model, temperature, top_p, max_tokens, stop or seed)",
+                        other
+                    ));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Sampling temperature between 0 and 2
+pub fn parse_temperature(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(temperature) if (0.0..=2.0).contains(&temperature) => Ok(temperature),
+        _ => Err(format!("temperature must be a number between 0 and 2, got `{}`", value)),
+    }
+}
+
+/// Nucleus sampling probability between 0 and 1
+pub fn parse_top_p(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(top_p) if (0.0..=1.0).contains(&top_p) => Ok(top_p),
+        _ => Err(format!("top_p must be a number between 0 and 1, got `{}`", value)),
+    }
+}
+
+/// Output token limit greater than zero
+pub fn parse_max_tokens(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(max_tokens) if max_tokens > 0 => Ok(max_tokens),
+        _ => Err(format!("max tokens must be a positive whole number, got `{}`", value)),
+    }
+}
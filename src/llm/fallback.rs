@@ -0,0 +1,293 @@
+use crate::cli::LlmProvider;
+use crate::error::Error;
+use crate::llm::client::{ApiError, ContentBlocked};
+use crate::llm::provider::LlmGenerator;
+use crate::llm::streaming::{LlmApiClient, StreamingSectionGenerator, TextSink};
+use crate::util::report::{Event, SharedReporter};
+use crate::util::tree::ProjectTree;
+use clap::ValueEnum;
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+/// Failures that move a call on to the next provider in a [`FallbackChain`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FailoverOn {
+    /// Rate limits, server errors and timeouts that outlasted retrying, and unreachable servers
+    Unavailable,
+    /// Prompts or replies refused by the provider's safety filters
+    Blocked,
+    /// Any other rejected request, such as a bad key or an unknown model
+    Rejected,
+}
+
+impl FailoverOn {
+    /// Failing over on unavailable providers and blocked content, but not on mistakes in the request
+    pub const DEFAULT: &[FailoverOn] = &[FailoverOn::Unavailable, FailoverOn::Blocked];
+
+    /// Which kind of failure an error is, if it is one a chain can fail over on
+    pub fn classify(error: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if error.is::<ContentBlocked>() {
+            return Some(Self::Blocked);
+        }
+        // Some failures are already sorted into categories by the provider
+        if let Some(error) = error.downcast_ref::<Error>() {
+            return match error {
+                Error::Network { .. } | Error::QuotaExceeded { .. } => Some(Self::Unavailable),
+                Error::Blocked { .. } => Some(Self::Blocked),
+                Error::AuthRejected { .. } | Error::Api { .. } => Some(Self::Rejected),
+                _ => None,
+            };
+        }
+        let error = error.downcast_ref::<ApiError>()?;
+        if error.is_retryable() || error.status().is_none() {
+            Some(Self::Unavailable)
+        } else {
+            Some(Self::Rejected)
+        }
+    }
+}
+
+/// A provider and, optionally, the model to use with it, written `provider[:model]`
+///
+/// The model may itself contain colons, as in `ollama:llama3.2:latest`.
+/// `provider` is a [`ProviderRegistry`](crate::llm::ProviderRegistry) name,
+/// so it is only checked once the provider is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderSpec {
+    pub provider: String,
+    pub model: Option<String>,
+}
+
+impl ProviderSpec {
+    pub fn new(provider: impl Into<String>, model: Option<String>) -> Self {
+        Self { provider: provider.into(), model }
+    }
+}
+
+impl FromStr for ProviderSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, model) = match s.split_once(':') {
+            Some((name, model)) => (name, Some(model.trim().to_string()).filter(|model| !model.is_empty())),
+            None => (s, None),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("missing provider name in `{}`", s));
+        }
+        // Built-in providers are also known by their aliases
+        let provider = LlmProvider::from_str(name, true).map_or_else(|_| name.to_lowercase(), |provider| provider.name());
+        Ok(Self { provider, model })
+    }
+}
+
+impl fmt::Display for ProviderSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.model {
+            Some(model) => write!(f, "{}:{}", self.provider, model),
+            None => write!(f, "{}", self.provider),
+        }
+    }
+}
+
+/// Providers tried in order, each call moving on to the next when one fails
+///
+/// Only the failures listed in [`FallbackChain::failover_on`] move a call
+/// on; anything else fails it straight away. A streamed section only fails
+/// over if none of its text has been written yet.
+#[derive(Debug)]
+pub struct FallbackChain {
+    members: Vec<ChainMember>,
+    failover_on: Vec<FailoverOn>,
+}
+
+#[derive(Debug)]
+struct ChainMember {
+    /// How the provider is named in notices and README comments
+    label: String,
+    generator: LlmGenerator,
+}
+
+impl FallbackChain {
+    /// Start a chain with the provider tried first
+    pub fn new(label: impl Into<String>, primary: LlmGenerator) -> Self {
+        Self { members: Vec::new(), failover_on: FailoverOn::DEFAULT.to_vec() }.then(label, primary)
+    }
+
+    /// Add a provider to try after the ones already in the chain
+    ///
+    /// A chain added to a chain contributes its providers, not itself.
+    pub fn then(mut self, label: impl Into<String>, generator: LlmGenerator) -> Self {
+        match generator {
+            LlmGenerator::Fallback(chain) => self.members.extend(chain.members),
+            generator => self.members.push(ChainMember { label: label.into(), generator }),
+        }
+        self
+    }
+
+    /// Fail over on these kinds of failure instead of [`FailoverOn::DEFAULT`]
+    pub fn failover_on(mut self, failover_on: &[FailoverOn]) -> Self {
+        self.failover_on = failover_on.to_vec();
+        self
+    }
+
+    /// Names of the providers in the order they are tried
+    pub fn labels(&self) -> Vec<&str> {
+        self.members.iter().map(|member| member.label.as_str()).collect()
+    }
+
+    pub fn reporter(&self) -> &SharedReporter {
+        self.members[0].generator.reporter()
+    }
+
+    /// Prepare every provider, leaving out the ones that cannot serve requests
+    ///
+    /// Fails only when no provider is left.
+    pub async fn prepare(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let reporter = self.reporter().clone();
+        let mut ready = Vec::new();
+        let mut left_out = Vec::new();
+        let mut first_error = None;
+        for mut member in std::mem::take(&mut self.members) {
+            match member.generator.prepare().await {
+                Ok(()) => ready.push(member),
+                Err(err) => {
+                    reporter.report(&Event::Warning {
+                        message: format!("Leaving {} out of the fallback chain: {}", member.label, err),
+                    });
+                    first_error.get_or_insert(err);
+                    left_out.push(member);
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) if ready.is_empty() => {
+                self.members = left_out;
+                Err(err)
+            }
+            _ => {
+                self.members = ready;
+                Ok(())
+            }
+        }
+    }
+
+    /// Models the first provider can serve
+    pub async fn list_models(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.members[0].generator.list_models().await
+    }
+
+    /// The smallest context any provider in the chain can take, so each can take over
+    pub fn context_budget(&self) -> usize {
+        self.members.iter().map(|member| member.generator.context_budget()).min().unwrap_or_default()
+    }
+
+    /// Generate the whole README in one call, noting which provider wrote it
+    pub async fn generate_readme_fast(
+        &self,
+        context: &str,
+        custom_prompt: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (content, label) = self.first_success(async |generator| generator.call_main(context, custom_prompt).await).await?;
+        Ok(format!("<!-- README generated by {} -->\n\n{}", label, content))
+    }
+
+    /// Generate the "Project Structure" section, noting which provider described the directories
+    pub async fn generate_structure_section(
+        &self,
+        tree: &ProjectTree,
+        context: &str,
+        custom_prompt: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let (section, label) = self.first_success(async |generator| {
+            StreamingSectionGenerator::generate_structure_section(generator, tree, context, custom_prompt).await
+        }).await?;
+        Ok(format!("<!-- structure generated by {} -->\n{}", label, section))
+    }
+
+    /// Make a call on each provider in turn until one succeeds, returning its reply and label
+    async fn first_success<T>(
+        &self,
+        call: impl AsyncFnMut(&LlmGenerator) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<(T, &str), Box<dyn std::error::Error>> {
+        self.first_success_while(call, || true).await
+    }
+
+    /// Like [`Self::first_success`], but only failing over while `can_fail_over` holds
+    async fn first_success_while<T>(
+        &self,
+        mut call: impl AsyncFnMut(&LlmGenerator) -> Result<T, Box<dyn std::error::Error>>,
+        can_fail_over: impl Fn() -> bool,
+    ) -> Result<(T, &str), Box<dyn std::error::Error>> {
+        let mut members = self.members.iter().peekable();
+        while let Some(member) = members.next() {
+            let err = match call(&member.generator).await {
+                Ok(reply) => return Ok((reply, &member.label)),
+                Err(err) => err,
+            };
+            let fails_over = FailoverOn::classify(err.as_ref()).is_some_and(|class| self.failover_on.contains(&class));
+            match members.peek() {
+                Some(next) if fails_over && can_fail_over() => {
+                    self.reporter().report(&Event::ProviderFailedOver {
+                        from: member.label.clone(),
+                        to: next.label.clone(),
+                        error: err.to_string(),
+                    });
+                }
+                _ => return Err(err),
+            }
+        }
+        Err(Error::config("The fallback chain has no providers").into())
+    }
+}
+
+impl LlmApiClient for FallbackChain {
+    async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (content, _) = self.first_success(async |generator| {
+            generator.call_with_custom_context(section_prompt, custom_prompt).await
+        }).await?;
+        Ok(content)
+    }
+
+    async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (content, _) = self.first_success(async |generator| generator.call_main(context, custom_prompt).await).await?;
+        Ok(content)
+    }
+
+    async fn call_summary(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (content, _) = self.first_success(async |generator| generator.call_summary(prompt).await).await?;
+        Ok(content)
+    }
+
+    async fn stream_with_custom_context(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stream_attributed(section_prompt, custom_prompt, on_text).await.map(|_| ())
+    }
+
+    async fn stream_attributed(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        // Text already written cannot be taken back, so only a section that has not started fails over
+        let started = Cell::new(false);
+        let ((), label) = self.first_success_while(
+            async |generator| {
+                generator.stream_with_custom_context(section_prompt, custom_prompt, &mut |text| {
+                    started.set(started.get() || !text.is_empty());
+                    on_text(text)
+                }).await
+            },
+            || !started.get(),
+        ).await?;
+        Ok(Some(label.to_string()))
+    }
+}
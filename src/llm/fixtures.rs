@@ -0,0 +1,142 @@
+use crate::llm::client::ApiError;
+use crate::util::manifest::content_hash;
+use crate::util::report::{Event, SharedReporter};
+use reqwest::{Request, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bump whenever the fixture layout or the key changes, so old fixtures are not replayed
+const FIXTURE_VERSION: u32 = 1;
+
+/// Query parameters that carry credentials; they are left out of fixtures and their keys
+const SECRET_PARAMS: &[&str] = &["key", "api_key", "api-key"];
+
+/// Response headers that are saved and replayed; the rest are dropped
+const KEPT_HEADERS: &[&str] = &["content-type", "retry-after", "retry-after-ms"];
+
+/// Whether HTTP exchanges are saved to fixtures or answered from them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Send every request and save what the provider answered
+    Record,
+    /// Answer every request from a saved exchange, without sending it
+    Replay,
+}
+
+/// HTTP exchanges with providers saved on disk, one file per request
+///
+/// Fixtures are keyed by a hash of the request's method, URL and body, so a
+/// replayed run has to send exactly the requests that were recorded: the
+/// same project, prompt, provider and settings. Error responses are saved
+/// too, which makes failures replayable. Request headers and credentials in
+/// the query string are never saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixtures {
+    dir: PathBuf,
+    mode: FixtureMode,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Exchange {
+    version: u32,
+    method: String,
+    url: String,
+    /// The request body, kept so fixtures can be read and reviewed
+    request: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Fixtures {
+    /// Save every exchange to `dir`
+    pub fn record(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), mode: FixtureMode::Record }
+    }
+
+    /// Answer requests from the exchanges saved in `dir`
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), mode: FixtureMode::Replay }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    /// Send a request and save the response, or answer it from its fixture when replaying
+    ///
+    /// A replayed request without a fixture fails like a request that never
+    /// got a response. Failing to save a fixture is only a warning, as the
+    /// call itself succeeded.
+    pub async fn exchange(&self, api: &str, request: RequestBuilder, reporter: &SharedReporter) -> Result<Response, ApiError> {
+        let (client, request) = request.build_split();
+        let request = request.map_err(|e| ApiError::transport(api, e))?;
+        let url = redacted_url(&request);
+        let body = request.body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .unwrap_or_default();
+        let path = self.dir.join(format!("{}.json", content_hash(&format!("{} {}\n{}", request.method(), url, body))));
+
+        let exchange = match self.mode {
+            FixtureMode::Replay => std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|raw| serde_json::from_str::<Exchange>(&raw).ok())
+                .filter(|exchange| exchange.version == FIXTURE_VERSION)
+                .ok_or_else(|| ApiError::new(api, None, format!(
+                    "no recorded response for {} {} in {} (record one with --record)",
+                    request.method(), url, self.dir.display()
+                )))?,
+            FixtureMode::Record => {
+                let method = request.method().to_string();
+                let response = client.execute(request).await.map_err(|e| ApiError::transport(api, e))?;
+                let status = response.status().as_u16();
+                let headers = response.headers().iter()
+                    .filter(|(name, _)| KEPT_HEADERS.contains(&name.as_str()))
+                    .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                    .collect();
+                // Streamed replies are saved whole and replayed in one piece
+                let text = response.text().await.map_err(|e| ApiError::transport(api, e))?;
+                let exchange = Exchange { version: FIXTURE_VERSION, method, url, request: body, status, headers, body: text };
+                if let Err(err) = self.save(&path, &exchange) {
+                    reporter.report(&Event::Warning {
+                        message: format!("Could not save the response to {}: {}", path.display(), err),
+                    });
+                }
+                exchange
+            }
+        };
+
+        let mut response = http::Response::builder().status(exchange.status);
+        for (name, value) in &exchange.headers {
+            response = response.header(name, value);
+        }
+        response.body(exchange.body)
+            .map(Response::from)
+            .map_err(|err| ApiError::new(api, None, format!("unreadable fixture {}: {}", path.display(), err)))
+    }
+
+    fn save(&self, path: &Path, exchange: &Exchange) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, serde_json::to_string_pretty(exchange)?)
+    }
+}
+
+/// The request URL without credentials in its query string
+fn redacted_url(request: &Request) -> String {
+    let mut url = request.url().clone();
+    let pairs: Vec<(String, String)> = url.query_pairs()
+        .filter(|(name, _)| !SECRET_PARAMS.contains(&name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
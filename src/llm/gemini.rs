@@ -1,186 +1,334 @@
-use crate::llm::client::LlmClient;
-use crate::llm::streaming::{LlmApiClient, StreamingSectionGenerator};
+use crate::error::Error;
+use crate::llm::chat::{conversation, prompt_chars, system_instruction, CallKind, ChatProvider, Message, ProviderFuture, Role};
+use crate::llm::client::{ContentBlocked, LlmClient, Reply};
+use crate::llm::config::{CallParams, GenerationSettings};
+use crate::llm::sse::read_events;
+use crate::llm::streaming::TextSink;
+use crate::llm::vertex::VertexEndpoint;
+use crate::util::report::SharedReporter;
 use serde_json::json;
 use std::sync::Arc;
-use tokio::time::Duration;
-use indicatif::{ProgressBar, ProgressStyle};
 use anyhow::Result;
 
+const AI_STUDIO_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const MAIN_MODEL: &str = "gemini-2.0-flash";
+const SECTION_MODEL: &str = "gemini-2.5-pro";
+const SUMMARY_MODEL: &str = "gemini-2.0-flash-lite";
+/// Finish reasons for a reply withheld by Gemini's filters rather than finished
+const BLOCKED_FINISH_REASONS: &[&str] = &["SAFETY", "RECITATION", "BLOCKLIST", "PROHIBITED_CONTENT", "SPII"];
+
+/// Generator for Gemini models, through AI Studio or Vertex AI
 #[derive(Debug, Clone)]
 pub struct SpeedOptimizedGenerator {
     client: Arc<LlmClient>,
+    /// Vertex AI project and credentials; AI Studio with an API key when unset
+    vertex: Option<VertexEndpoint>,
+    generation: GenerationSettings,
 }
 
-impl LlmApiClient for SpeedOptimizedGenerator {
-    async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let combined_prompt = format!(
-            "Context: {}\n\nTask: {}",
-            custom_prompt,
-            section_prompt
-        );
-
-        let response = self.client.client()
-            .post("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-pro:generateContent")
-            .query(&[("key", self.client.api_key())])
-            .header("Content-Type", "application/json")
-            .json(&json!({
-                "contents": [{
-                    "parts": [{
-                        "text": combined_prompt
-                    }]
-                }],
-                "generationConfig": {
-                    "temperature": 0.7,
-                    "maxOutputTokens": 1000
-                }
-            }))
-            .send()
-            .await?;
+impl ChatProvider for SpeedOptimizedGenerator {
+    fn id(&self) -> &'static str {
+        if self.vertex.is_some() { "vertex" } else { "gemini" }
+    }
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("Gemini API error: {}", error_text).into());
+    fn endpoint(&self) -> String {
+        match &self.vertex {
+            Some(vertex) => vertex.location_url(),
+            None => AI_STUDIO_URL.to_string(),
         }
+    }
 
-        let response_json: serde_json::Value = response.json().await?;
-        
-        if let Some(content) = response_json["candidates"][0]["content"]["parts"][0]["text"].as_str() {
-            Ok(clean_markdown_response(content))
-        } else {
-            Err("Invalid response format from Gemini API".into())
-        }
+    fn client(&self) -> &LlmClient {
+        &self.client
     }
 
-    async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "{}\n\nAnalyze this codebase and generate a comprehensive README.md:\n\n{}",
-            custom_prompt,
-            context
-        );
-
-        let response = self.client.client()
-            .post("https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent")
-            .query(&[("key", self.client.api_key())])
-            .header("Content-Type", "application/json")
-            .json(&json!({
-                "contents": [{
-                    "parts": [{
-                        "text": prompt
-                    }]
-                }],
-                "generationConfig": {
-                    "temperature": 0.7,
-                    "maxOutputTokens": 4000
-                }
-            }))
-            .send()
-            .await?;
+    fn client_mut(&mut self) -> &mut LlmClient {
+        Arc::make_mut(&mut self.client)
+    }
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("Gemini API error: {}", error_text).into());
-        }
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        let model = match kind {
+            CallKind::Main => MAIN_MODEL,
+            CallKind::Section => SECTION_MODEL,
+            CallKind::Summary => SUMMARY_MODEL,
+        };
+        Ok(self.generation.resolve(kind, model))
+    }
 
-        let response_json: serde_json::Value = response.json().await?;
-        
-        if let Some(content) = response_json["candidates"][0]["content"]["parts"][0]["text"].as_str() {
-            Ok(clean_markdown_response(content))
-        } else {
-            Err("Invalid response format from Gemini API".into())
-        }
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        Box::pin(self.request_content(params, messages))
+    }
+
+    fn chat_streaming<'a>(
+        &'a self,
+        params: &'a CallParams,
+        messages: &'a [Message],
+        on_text: &'a mut TextSink<'_>,
+    ) -> ProviderFuture<'a, bool> {
+        Box::pin(self.request_stream(params, messages, on_text))
+    }
+
+    /// Load Vertex AI credentials and fetch the first access token
+    ///
+    /// Nothing needs preparing for AI Studio, and no token is fetched when
+    /// replaying fixtures.
+    fn prepare(&mut self) -> ProviderFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(vertex) = &mut self.vertex {
+                vertex.load()?;
+            }
+            if let Some(vertex) = &self.vertex {
+                self.bearer_token(vertex).await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Gemini models that support `generateContent`
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(async move {
+            // Vertex AI needs its credentials loaded first
+            self.prepare().await?;
+            self.request_models().await
+        })
     }
 }
 
 impl SpeedOptimizedGenerator {
-    pub fn new(api_key: String) -> Self {
+    /// Create a generator for the Gemini API in Google AI Studio
+    pub fn new(api_key: String, reporter: SharedReporter) -> Self {
         Self {
-            client: Arc::new(LlmClient::new(api_key)),
+            client: Arc::new(LlmClient::new(api_key, reporter)),
+            vertex: None,
+            generation: GenerationSettings::default(),
         }
     }
 
-    pub async fn generate_readme_fast(
+    /// Create a generator for Gemini models on Vertex AI
+    ///
+    /// Requests are authenticated with access tokens minted from the endpoint's
+    /// service-account key, which is read by [`ChatProvider::prepare`].
+    pub fn vertex(endpoint: VertexEndpoint, reporter: SharedReporter) -> Self {
+        Self {
+            client: Arc::new(LlmClient::new(String::new(), reporter)),
+            vertex: Some(endpoint),
+            generation: GenerationSettings::default(),
+        }
+    }
+
+    /// Use these models and sampling settings instead of the built-in ones
+    pub fn with_generation(mut self, generation: GenerationSettings) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    async fn request_models(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let request = match &self.vertex {
+            Some(vertex) => self.client.client()
+                .get(vertex.publisher_models_url())
+                .query(&[("pageSize", "1000")])
+                .bearer_auth(self.bearer_token(vertex).await?),
+            None => self.client.client()
+                .get(AI_STUDIO_URL)
+                .query(&[("key", self.client.api_key()), ("pageSize", "1000")]),
+        };
+
+        let response = self.client.send(self.api_name(), request).await?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        let models = match &self.vertex {
+            // Model Garden lists every Google model, not just Gemini
+            Some(_) => response_json["publisherModels"].as_array()
+                .map(|models| {
+                    models.iter()
+                        .filter_map(|model| model["name"].as_str()?.rsplit('/').next())
+                        .filter(|name| name.starts_with("gemini"))
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            None => response_json["models"].as_array()
+                .map(|models| {
+                    models.iter()
+                        .filter(|model| {
+                            model["supportedGenerationMethods"].as_array()
+                                .is_some_and(|methods| methods.iter().any(|method| method == "generateContent"))
+                        })
+                        .filter_map(|model| model["name"].as_str())
+                        .map(|name| name.trim_start_matches("models/").to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        Ok(models)
+    }
+
+    /// An access token for Vertex AI, or a placeholder when replaying fixtures
+    ///
+    /// Replayed requests are never sent, and the token exchange itself cannot
+    /// be replayed: its signed assertion changes with every call.
+    async fn bearer_token(&self, vertex: &VertexEndpoint) -> Result<String, Box<dyn std::error::Error>> {
+        if self.client.replaying() {
+            return Ok("replayed".to_string());
+        }
+        vertex.access_token(self.client.client()).await
+    }
+
+    fn api_name(&self) -> &'static str {
+        if self.vertex.is_some() { "Vertex AI API" } else { "Gemini API" }
+    }
+
+    /// Build a request for a model method, authenticated for AI Studio or Vertex AI
+    async fn content_request(
         &self,
-        chunks: &[String],
-        custom_prompt: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
-                .template("{spinner:.cyan} {msg}")
-                .unwrap()
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        pb.set_message("🚀 Generating optimized README...");
-
-        let filtered_chunks = StreamingSectionGenerator::filter_important_chunks(chunks);
-        let context = StreamingSectionGenerator::build_smart_context(&filtered_chunks);
-        
-        pb.set_message("📝 Calling Gemini API...");
-        let readme_content = self.call_main(&context, custom_prompt).await?;
-        
-        pb.finish_with_message("✅ README generated successfully!");
-        Ok(readme_content)
-    }
-
-    pub async fn generate_readme_streaming(
+        params: &CallParams,
+        messages: &[Message],
+        method: &str,
+    ) -> Result<reqwest::RequestBuilder, Box<dyn std::error::Error>> {
+        let request = match &self.vertex {
+            Some(vertex) => self.client.client()
+                .post(vertex.model_url(&params.model, method))
+                .bearer_auth(self.bearer_token(vertex).await?),
+            None => self.client.client()
+                .post(format!("{}/{}:{}", AI_STUDIO_URL, params.model, method))
+                .query(&[("key", self.client.api_key())]),
+        };
+
+        let mut body = json!({
+            "contents": contents(messages),
+            "generationConfig": generation_config(params)
+        });
+        if let Some(system) = system_instruction(messages) {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        Ok(request.header("Content-Type", "application/json").json(&body))
+    }
+
+    fn report_usage(&self, model: &str, response_json: &serde_json::Value) {
+        let usage = &response_json["usageMetadata"];
+        self.client.report_usage(self.id(), model, usage["promptTokenCount"].as_u64(), usage["candidatesTokenCount"].as_u64());
+    }
+
+    /// Whether a response (or stream chunk) says the prompt or the reply was blocked
+    fn blocked(&self, response_json: &serde_json::Value) -> Option<ContentBlocked> {
+        let feedback = &response_json["promptFeedback"];
+        if let Some(reason) = feedback["blockReason"].as_str() {
+            let reason = match feedback["blockReasonMessage"].as_str() {
+                Some(message) => format!("{}: {}", reason, message),
+                None => reason.to_string(),
+            };
+            return Some(ContentBlocked::new(self.api_name(), format!("prompt blocked: {}{}", reason, flagged(feedback))));
+        }
+        let candidate = &response_json["candidates"][0];
+        let reason = candidate["finishReason"].as_str()?;
+        BLOCKED_FINISH_REASONS.contains(&reason)
+            .then(|| ContentBlocked::new(self.api_name(), format!("reply blocked: {}{}", reason, flagged(candidate))))
+    }
+
+    /// Send a `generateContent` request and return the first candidate's text
+    async fn request_content(&self, params: &CallParams, messages: &[Message]) -> Result<Reply, Box<dyn std::error::Error>> {
+        let request = self.content_request(params, messages, "generateContent").await?;
+        let response = self.client.send_chat(self.api_name(), request, self.id(), &params.model, prompt_chars(messages)).await?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        self.report_usage(&params.model, &response_json);
+
+        if let Some(blocked) = self.blocked(&response_json) {
+            return Err(blocked.into());
+        }
+        let candidate = &response_json["candidates"][0];
+        if let Some(content) = candidate["content"]["parts"][0]["text"].as_str() {
+            Ok(Reply { text: content.to_string(), truncated: candidate["finishReason"] == "MAX_TOKENS" })
+        } else {
+            Err(Error::invalid_response(self.api_name(), "the reply has no text").into())
+        }
+    }
+
+    /// Send a `streamGenerateContent` request, handing text to `on_text` as it arrives
+    ///
+    /// Returns whether the reply was cut off at the output token limit.
+    async fn request_stream(
         &self,
-        chunks: &[String],
-        output_path: &std::path::Path,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        use std::io::Write;
-        
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
-                .template("{spinner:.cyan} {msg}")
-                .unwrap()
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        pb.set_message("🚀 Generating README with streaming writes...");
-
-        let mut file = std::fs::File::create(output_path)?;
-        
-        // Write minimal initial header
-        let header = "<!-- README being generated... -->\n\n";
-        file.write_all(header.as_bytes())?;
-        file.flush()?;
-        pb.set_message("📄 README file created, generating sections...");
-
-        let filtered_chunks = StreamingSectionGenerator::filter_important_chunks(chunks);
-        let generator = StreamingSectionGenerator::new();
-        generator.generate_sections_incrementally(self, &filtered_chunks, &mut file, &pb, custom_prompt).await?;
-        
-        // Write final section combination
-        pb.set_message("🔗 Finalizing README structure...");
-        generator.write_final_sections(&mut file).await?;
-        
-        pb.finish_with_message("✅ README.md generated with streaming writes!");
-        println!("📝 README.md written to: {}", output_path.display());
-        
-        Ok(())
+        params: &CallParams,
+        messages: &[Message],
+        on_text: &mut TextSink<'_>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let request = self.content_request(params, messages, "streamGenerateContent").await?
+            .query(&[("alt", "sse")]);
+        let response = self.client.send_chat(self.api_name(), request, self.id(), &params.model, prompt_chars(messages)).await?;
+
+        // Every chunk carries the usage so far, so only the last one counts
+        let mut last_chunk = None;
+        let mut finish_reason = None;
+        read_events(self.api_name(), response, |event| {
+            let chunk: serde_json::Value = serde_json::from_str(&event.data)?;
+            if let Some(error) = chunk.get("error") {
+                return Err(Error::network(self.api_name(), format!("error mid-stream: {}", error)).into());
+            }
+            if let Some(blocked) = self.blocked(&chunk) {
+                return Err(blocked.into());
+            }
+
+            let candidate = &chunk["candidates"][0];
+            if let Some(parts) = candidate["content"]["parts"].as_array() {
+                for text in parts.iter().filter_map(|part| part["text"].as_str()) {
+                    on_text(text)?;
+                }
+            }
+            if let Some(reason) = candidate["finishReason"].as_str() {
+                finish_reason = Some(reason.to_string());
+            }
+            last_chunk = Some(chunk);
+            Ok(true)
+        }).await?;
+
+        if let Some(chunk) = &last_chunk {
+            self.report_usage(&params.model, chunk);
+        }
+        match finish_reason {
+            Some(reason) => Ok(reason == "MAX_TOKENS"),
+            None => Err(Error::network(self.api_name(), "the stream ended before the reply was complete").into()),
+        }
     }
 }
 
-/// Clean markdown code blocks from AI responses
-fn clean_markdown_response(content: &str) -> String {
-    let mut cleaned = content.trim();
-    
-    // Remove opening markdown code block
-    if cleaned.starts_with("```markdown") {
-        cleaned = &cleaned[11..];
-    } else if cleaned.starts_with("```md") {
-        cleaned = &cleaned[5..];
-    } else if cleaned.starts_with("```") {
-        cleaned = &cleaned[3..];
-    }
-    
-    // Remove closing markdown code block
-    if cleaned.ends_with("```") {
-        cleaned = &cleaned[..cleaned.len()-3];
-    }
-    
-    cleaned.trim().to_string()
+/// The safety categories a prompt or candidate was blocked for, e.g. ` (HARM_CATEGORY_HARASSMENT)`
+fn flagged(feedback: &serde_json::Value) -> String {
+    let categories: Vec<&str> = feedback["safetyRatings"].as_array()
+        .map(|ratings| {
+            ratings.iter()
+                .filter(|rating| rating["blocked"] == true)
+                .filter_map(|rating| rating["category"].as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    if categories.is_empty() { String::new() } else { format!(" ({})", categories.join(", ")) }
+}
+
+/// The conversation in Gemini's turns, in which the assistant is the `model`
+fn contents(messages: &[Message]) -> serde_json::Value {
+    conversation(messages)
+        .map(|message| {
+            let role = if message.role == Role::Assistant { "model" } else { "user" };
+            json!({ "role": role, "parts": [{ "text": message.content }] })
+        })
+        .collect()
+}
+
+fn generation_config(params: &CallParams) -> serde_json::Value {
+    let mut config = json!({
+        "temperature": params.temperature,
+        "maxOutputTokens": params.max_output_tokens
+    });
+    if let Some(top_p) = params.top_p {
+        config["topP"] = json!(top_p);
+    }
+    if !params.stop_sequences.is_empty() {
+        config["stopSequences"] = json!(params.stop_sequences);
+    }
+    if let Some(seed) = params.seed {
+        config["seed"] = json!(seed);
+    }
+    config
 }
@@ -0,0 +1,151 @@
+use crate::error::Error;
+use crate::llm::chat::{prompt_chars, CallKind, ChatProvider, Message, ProviderFuture};
+use crate::llm::client::{ApiError, ContentBlocked, LlmClient, Reply};
+use crate::llm::config::{CallParams, GenerationSettings};
+use crate::llm::rate_limit::estimate_tokens;
+use crate::util::report::SharedReporter;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const API: &str = "Mock API";
+const MODEL: &str = "mock";
+
+/// One scripted reply of the [`MockProvider`]
+///
+/// In a script file each reply is a one-key object, e.g. `{"text": "# Demo"}`,
+/// `{"truncated": "# De"}`, `{"error": {"status": 429, "message": "slow down"}}`
+/// or `{"blocked": "SAFETY"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockReply {
+    /// A complete reply
+    Text(String),
+    /// A reply cut off at the output token limit
+    Truncated(String),
+    /// The API answering with an HTTP error status
+    Error { status: u16, message: String },
+    /// The provider refusing the prompt, for the given reason
+    Blocked(String),
+}
+
+/// A provider that answers without calling a model, for trying tldrs out and testing it offline
+///
+/// Calls are answered from a script of replies, in the order they are made;
+/// once the script runs out every call gets a short canned reply. Token
+/// usage is estimated from the text, so budgets apply as they would to a
+/// real provider. Clones share the script.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    client: Arc<LlmClient>,
+    generation: GenerationSettings,
+    replies: Arc<Mutex<VecDeque<MockReply>>>,
+    /// Script file read in `prepare`
+    script: Option<PathBuf>,
+}
+
+impl ChatProvider for MockProvider {
+    fn id(&self) -> &'static str {
+        "mock"
+    }
+
+    fn client(&self) -> &LlmClient {
+        &self.client
+    }
+
+    fn client_mut(&mut self) -> &mut LlmClient {
+        Arc::make_mut(&mut self.client)
+    }
+
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        Ok(self.generation.resolve(kind, MODEL))
+    }
+
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        let reply = self.replies.lock().unwrap().pop_front();
+        Box::pin(async move {
+            // Nothing is sent, but the call still counts against the rate limits
+            self.client.acquire(self.id(), &params.model, prompt_chars(messages)).await;
+            self.answer(params, messages, reply)
+        })
+    }
+
+    /// Read the script file, if one was given
+    fn prepare(&mut self) -> ProviderFuture<'_, ()> {
+        Box::pin(async move { self.load_script() })
+    }
+
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(async { Ok(vec![MODEL.to_string()]) })
+    }
+}
+
+impl MockProvider {
+    pub fn new(reporter: SharedReporter) -> Self {
+        Self {
+            client: Arc::new(LlmClient::new(String::new(), reporter)),
+            generation: GenerationSettings::default(),
+            replies: Arc::new(Mutex::new(VecDeque::new())),
+            script: None,
+        }
+    }
+
+    /// Give these replies, in order, before falling back to the canned one
+    pub fn with_replies(self, replies: impl IntoIterator<Item = MockReply>) -> Self {
+        self.replies.lock().unwrap().extend(replies);
+        self
+    }
+
+    /// Read further replies from a JSON array of [`MockReply`]s when the provider is prepared
+    pub fn with_script(mut self, path: impl Into<PathBuf>) -> Self {
+        self.script = Some(path.into());
+        self
+    }
+
+    /// Use these models and sampling settings; the model only shows up in usage reports and cache keys
+    pub fn with_generation(mut self, generation: GenerationSettings) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    fn load_script(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = self.script.take() else {
+            return Ok(());
+        };
+        let replies: Vec<MockReply> = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|raw| serde_json::from_str(&raw).map_err(|err| err.to_string()))
+            .map_err(|err| Error::config(format!("Could not read the mock replies in {}: {}", path.display(), err)))?;
+        self.replies.lock().unwrap().extend(replies);
+        Ok(())
+    }
+
+    fn answer(&self, params: &CallParams, messages: &[Message], reply: Option<MockReply>) -> Result<Reply, Box<dyn std::error::Error>> {
+        let reply = match reply {
+            Some(MockReply::Text(text)) => Reply { text, truncated: false },
+            Some(MockReply::Truncated(text)) => Reply { text, truncated: true },
+            Some(MockReply::Error { status, message }) => {
+                let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                return Err(ApiError::new(API, Some(status), message).into());
+            }
+            Some(MockReply::Blocked(reason)) => return Err(ContentBlocked::new(API, reason).into()),
+            None => Reply { text: canned(messages), truncated: false },
+        };
+
+        let prompt_chars = messages.iter().map(|message| message.content.len()).sum();
+        self.client.report_usage("mock", &params.model, Some(estimate_tokens(prompt_chars)), Some(estimate_tokens(reply.text.len())));
+        Ok(reply)
+    }
+}
+
+/// The reply once the script has run out, describing what the call was sent
+fn canned(messages: &[Message]) -> String {
+    let chars: usize = messages.iter().map(|message| message.content.len()).sum();
+    format!(
+        "Mock reply to {} messages of {} characters in all; no model was called.\n",
+        messages.len(),
+        chars
+    )
+}
@@ -1,10 +1,37 @@
+pub mod anthropic;
+pub mod cache;
+pub mod chat;
 pub mod client;
+pub mod config;
+pub mod fallback;
+pub mod fixtures;
 pub mod prompt;
 pub mod streaming;
 pub mod gemini;
+pub mod mock;
+pub mod ollama;
 pub mod openai;
 pub mod provider;
+pub mod rate_limit;
+pub mod registry;
+pub mod sse;
+pub mod summarize;
+pub mod usage;
+pub mod vertex;
 
+pub use anthropic::AnthropicGenerator;
+pub use cache::{CacheMode, ResponseCache};
+pub use chat::{CallKind, ChatProvider, Message, Role};
+pub use client::{ApiError, ContentBlocked, RetryPolicy};
+pub use config::{GenerationConfig, GenerationSettings};
+pub use fallback::{FailoverOn, FallbackChain, ProviderSpec};
+pub use fixtures::{FixtureMode, Fixtures};
 pub use gemini::SpeedOptimizedGenerator;
+pub use mock::{MockProvider, MockReply};
+pub use ollama::OllamaGenerator;
 pub use openai::OpenAiGenerator;
-pub use provider::LlmGenerator;
+pub use provider::{LlmGenerator, ProviderOptions};
+pub use rate_limit::RateLimits;
+pub use registry::{ProviderConfig, ProviderEntry, ProviderRegistry};
+pub use usage::{Budget, BudgetExceeded, ModelPrice, PriceTable, UsageSummary, UsageTracker};
+pub use vertex::{ServiceAccountAuth, VertexEndpoint};
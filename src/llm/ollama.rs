@@ -0,0 +1,226 @@
+use crate::error::Error;
+use crate::llm::chat::{prompt_chars, CallKind, ChatProvider, Message, ProviderFuture};
+use crate::llm::client::{ApiError, LlmClient, Reply};
+use crate::llm::config::{CallParams, GenerationSettings};
+use crate::util::report::SharedReporter;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+
+const DEFAULT_HOST: &str = "http://localhost:11434";
+
+/// Context window requested from Ollama, in tokens
+///
+/// Ollama otherwise runs models with a small default window and silently drops
+/// the start of longer prompts.
+pub const OLLAMA_CONTEXT_WINDOW: u32 = 8192;
+
+/// Character budget for the packed code context, leaving room in the window for
+/// the prompt and the generated README
+pub const OLLAMA_CONTEXT_BUDGET: usize = 8000;
+
+/// Local models can be slow, especially without a GPU
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+pub struct OllamaGenerator {
+    client: Arc<LlmClient>,
+    base_url: String,
+    model: Option<String>,
+    generation: GenerationSettings,
+}
+
+impl ChatProvider for OllamaGenerator {
+    fn id(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn endpoint(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn client(&self) -> &LlmClient {
+        &self.client
+    }
+
+    fn client_mut(&mut self) -> &mut LlmClient {
+        Arc::make_mut(&mut self.client)
+    }
+
+    /// Every call uses the selected model
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        Ok(self.generation.resolve(kind, self.selected_model()?))
+    }
+
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        Box::pin(self.request_chat(params, messages))
+    }
+
+    fn prepare(&mut self) -> ProviderFuture<'_, ()> {
+        Box::pin(self.resolve_model())
+    }
+
+    /// Names of the models installed on the server (`/api/tags`)
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(self.request_models())
+    }
+
+    fn context_budget(&self) -> usize {
+        OLLAMA_CONTEXT_BUDGET
+    }
+}
+
+impl OllamaGenerator {
+    /// Create a generator for a local Ollama server
+    ///
+    /// Without an explicit base URL the `OLLAMA_HOST` environment variable is
+    /// used, falling back to `http://localhost:11434`. No API key is needed.
+    pub fn new(model: Option<String>, base_url: Option<String>, reporter: SharedReporter) -> Self {
+        let base_url = base_url
+            .or_else(|| std::env::var("OLLAMA_HOST").ok().filter(|host| !host.is_empty()))
+            .map(|host| normalize_host(&host))
+            .unwrap_or_else(|| DEFAULT_HOST.to_string());
+
+        Self {
+            client: Arc::new(LlmClient::with_timeout(String::new(), reporter, REQUEST_TIMEOUT)),
+            base_url,
+            model,
+            generation: GenerationSettings::default(),
+        }
+    }
+
+    /// Use these models and sampling settings instead of the built-in ones
+    pub fn with_generation(mut self, generation: GenerationSettings) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Name the server's address when it cannot be reached at all
+    fn unreachable(&self, error: ApiError) -> Box<dyn std::error::Error> {
+        match error.status() {
+            None => Error::network(format!("Ollama at {}", self.base_url), error.message()).into(),
+            Some(_) => error.into(),
+        }
+    }
+
+    async fn request_models(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let request = self.client.client()
+            .get(format!("{}/api/tags", self.base_url));
+        let response = self.client.send("Ollama API", request).await.map_err(|error| self.unreachable(error))?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        let models = response_json["models"].as_array()
+            .map(|models| {
+                models.iter()
+                    .filter_map(|model| model["name"].as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(models)
+    }
+
+    /// Make sure the selected model is installed, picking the only installed model if none was chosen
+    async fn resolve_model(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let installed = self.request_models().await?;
+
+        match &self.model {
+            Some(model) => {
+                // `llama3.2` refers to the same model as `llama3.2:latest`
+                let found = installed.iter().any(|name| name == model || *name == format!("{}:latest", model));
+                if !found {
+                    return Err(Error::config(format!(
+                        "Model '{}' is not installed in Ollama. Run `ollama pull {}` first (installed: {})",
+                        model, model, describe_models(&installed)
+                    )).into());
+                }
+            }
+            None if installed.len() == 1 => {
+                self.model = installed.into_iter().next();
+            }
+            None => {
+                return Err(Error::config(format!(
+                    "No Ollama model selected. Use --model to pick one (installed: {})",
+                    describe_models(&installed)
+                )).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn selected_model(&self) -> Result<&str, Box<dyn std::error::Error>> {
+        Ok(self.model.as_deref().ok_or_else(|| Error::config("No Ollama model selected. Use --model to pick one"))?)
+    }
+
+    /// Send a non-streaming request to `/api/chat` and return the reply
+    async fn request_chat(&self, params: &CallParams, messages: &[Message]) -> Result<Reply, Box<dyn std::error::Error>> {
+        let model = params.model.as_str();
+        let mut options = json!({
+            "temperature": params.temperature,
+            "num_predict": params.max_output_tokens,
+            "num_ctx": OLLAMA_CONTEXT_WINDOW
+        });
+        if let Some(top_p) = params.top_p {
+            options["top_p"] = json!(top_p);
+        }
+        if !params.stop_sequences.is_empty() {
+            options["stop"] = json!(params.stop_sequences);
+        }
+        if let Some(seed) = params.seed {
+            options["seed"] = json!(seed);
+        }
+
+        let request = self.client.client()
+            .post(format!("{}/api/chat", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(&json!({
+                "model": model,
+                "stream": false,
+                "messages": messages,
+                "options": options
+            }));
+        let response = self.client.send_chat("Ollama API", request, self.id(), model, prompt_chars(messages)).await
+            .map_err(|error| self.unreachable(error))?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        self.client.report_usage(
+            "ollama",
+            model,
+            response_json["prompt_eval_count"].as_u64(),
+            response_json["eval_count"].as_u64(),
+        );
+
+        match response_json["message"]["content"].as_str() {
+            Some(content) if !content.trim().is_empty() => Ok(Reply {
+                text: content.to_string(),
+                truncated: response_json["done_reason"] == "length",
+            }),
+            _ => Err(Error::invalid_response("Ollama API", "the reply has no message content").into()),
+        }
+    }
+}
+
+/// Accept `OLLAMA_HOST`-style values such as `127.0.0.1:11434` as well as full URLs
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    } else {
+        format!("http://{}", host)
+    }
+}
+
+fn describe_models(models: &[String]) -> String {
+    if models.is_empty() {
+        "none".to_string()
+    } else {
+        models.join(", ")
+    }
+}
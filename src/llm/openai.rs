@@ -1,193 +1,384 @@
-use crate::llm::client::LlmClient;
-use crate::llm::streaming::{LlmApiClient, StreamingSectionGenerator};
+use crate::error::Error;
+use crate::llm::chat::{prompt_chars, CallKind, ChatProvider, Message, ProviderFuture};
+use crate::llm::client::{ContentBlocked, LlmClient, Reply};
+use crate::llm::config::{CallParams, GenerationSettings};
+use crate::llm::sse::read_events;
+use crate::llm::streaming::TextSink;
+use crate::util::report::SharedReporter;
 use serde_json::json;
 use std::sync::Arc;
-use tokio::time::Duration;
-use indicatif::{ProgressBar, ProgressStyle};
 use anyhow::Result;
 
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const MAIN_MODEL: &str = "gpt-4o";
+const SUMMARY_MODEL: &str = "gpt-4o-mini";
+
+/// Latest generally available Azure OpenAI data plane API version
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+/// Where an Azure OpenAI model is deployed
+#[derive(Debug, Clone)]
+struct AzureDeployment {
+    deployment: String,
+    api_version: String,
+}
+
+/// Generator for the OpenAI chat completions API and servers that mimic it
+/// (vLLM, LM Studio, llama.cpp server, gateways, Azure OpenAI)
 #[derive(Debug, Clone)]
 pub struct OpenAiGenerator {
     client: Arc<LlmClient>,
+    /// Base URL that `/chat/completions` is appended to, or the Azure resource endpoint
+    base_url: String,
+    azure: Option<AzureDeployment>,
+    model: String,
+    summary_model: String,
+    /// Extra headers sent with every request
+    headers: Vec<(String, String)>,
+    /// Whether the model still has to be looked up on the server
+    discover_model: bool,
+    /// Whether to ask for usage at the end of streams, which some compatible servers reject
+    stream_usage: bool,
+    generation: GenerationSettings,
 }
 
-impl LlmApiClient for OpenAiGenerator {
-    async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let combined_prompt = format!(
-            "Context: {}\n\nTask: {}",
-            custom_prompt,
-            section_prompt
-        );
-
-        let response = self.client.client()
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.client.api_key()))
-            .header("Content-Type", "application/json")
-            .json(&json!({
-                "model": "gpt-4o",
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": "You are a technical writing assistant. Generate only raw markdown content without wrapping it in code blocks. Do not include opinions or subjective commentary. Follow the provided context and guidelines while completing the specific task."
-                    },
-                    {
-                        "role": "user",
-                        "content": combined_prompt
-                    }
-                ],
-                "max_tokens": 1000,
-                "temperature": 0.7
-            }))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("OpenAI API error: {}", error_text).into());
+impl ChatProvider for OpenAiGenerator {
+    fn id(&self) -> &'static str {
+        if self.azure.is_some() { "azure" } else { "openai" }
+    }
+
+    /// The chat completions URL, which names the Azure deployment
+    fn endpoint(&self) -> String {
+        self.chat_completions_url()
+    }
+
+    fn client(&self) -> &LlmClient {
+        &self.client
+    }
+
+    fn client_mut(&mut self) -> &mut LlmClient {
+        Arc::make_mut(&mut self.client)
+    }
+
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        let model = if kind == CallKind::Summary { &self.summary_model } else { &self.model };
+        Ok(self.generation.resolve(kind, model))
+    }
+
+    fn chat<'a>(&'a self, params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        Box::pin(self.request_completion(params, messages))
+    }
+
+    fn chat_streaming<'a>(
+        &'a self,
+        params: &'a CallParams,
+        messages: &'a [Message],
+        on_text: &'a mut TextSink<'_>,
+    ) -> ProviderFuture<'a, bool> {
+        Box::pin(self.request_stream(params, messages, on_text))
+    }
+
+    fn prepare(&mut self) -> ProviderFuture<'_, ()> {
+        Box::pin(self.resolve_model())
+    }
+
+    /// Model ids served by the endpoint (`/models`)
+    ///
+    /// An Azure generator only knows its own deployment.
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(self.request_models())
+    }
+}
+
+impl OpenAiGenerator {
+    /// Create a generator for the OpenAI API
+    ///
+    /// The endpoint can be redirected (e.g. to a proxy) with `OPENAI_BASE_URL`.
+    pub fn new(api_key: String, reporter: SharedReporter) -> Self {
+        let base_url = std::env::var("OPENAI_BASE_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+
+        Self {
+            client: Arc::new(LlmClient::new(api_key, reporter)),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            azure: None,
+            model: MAIN_MODEL.to_string(),
+            summary_model: SUMMARY_MODEL.to_string(),
+            headers: Vec::new(),
+            discover_model: false,
+            stream_usage: true,
+            generation: GenerationSettings::default(),
         }
+    }
 
-        let response_json: serde_json::Value = response.json().await?;
-        
-        if let Some(content) = response_json["choices"][0]["message"]["content"].as_str() {
-            Ok(clean_markdown_response(content))
-        } else {
-            Err("Invalid response format from OpenAI API".into())
+    /// Create a generator for any server that speaks the OpenAI chat completions API
+    ///
+    /// `base_url` is the URL that `/chat/completions` is appended to, usually
+    /// ending in `/v1`. An empty API key sends no `Authorization` header. Without
+    /// a model, the first model listed by the server's `/models` endpoint is used.
+    /// Streams do not ask for usage, as some servers reject the extra field.
+    pub fn compatible(base_url: impl Into<String>, model: Option<String>, api_key: String, reporter: SharedReporter) -> Self {
+        let base_url: String = base_url.into();
+        let discover_model = model.is_none();
+        let model = model.unwrap_or_default();
+
+        Self {
+            client: Arc::new(LlmClient::new(api_key, reporter)),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            azure: None,
+            summary_model: model.clone(),
+            model,
+            headers: Vec::new(),
+            discover_model,
+            stream_usage: false,
+            generation: GenerationSettings::default(),
         }
     }
 
-    async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let prompt = format!(
-            "Analyze this codebase and generate a comprehensive README.md. Output raw markdown content only, no code block wrappers:\n\n{}",
-            context
-        );
+    /// Create a generator for an Azure OpenAI deployment
+    ///
+    /// Unset values fall back to `AZURE_OPENAI_ENDPOINT` (the resource endpoint,
+    /// e.g. `https://my-resource.openai.azure.com`), `AZURE_OPENAI_DEPLOYMENT` and
+    /// `OPENAI_API_VERSION`. The deployment decides the model, so it is used for
+    /// every call.
+    pub fn azure(
+        endpoint: Option<String>,
+        deployment: Option<String>,
+        api_version: Option<String>,
+        api_key: String,
+        reporter: SharedReporter,
+    ) -> Self {
+        let from_env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let endpoint = endpoint.or_else(|| from_env("AZURE_OPENAI_ENDPOINT")).unwrap_or_default();
+        let deployment = deployment.or_else(|| from_env("AZURE_OPENAI_DEPLOYMENT")).unwrap_or_default();
+        let api_version = api_version
+            .or_else(|| from_env("OPENAI_API_VERSION"))
+            .unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string());
 
-        let response = self.client.client()
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.client.api_key()))
-            .header("Content-Type", "application/json")
-            .json(&json!({
-                "model": "gpt-4o",
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": custom_prompt
-                    },
-                    {
-                        "role": "user",
-                        "content": prompt
-                    }
-                ],
-                "max_tokens": 4000,
-                "temperature": 0.7
-            }))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("OpenAI API error: {}", error_text).into());
+        Self {
+            client: Arc::new(LlmClient::new(api_key, reporter)),
+            base_url: endpoint.trim_end_matches('/').to_string(),
+            azure: Some(AzureDeployment { deployment: deployment.clone(), api_version }),
+            model: deployment.clone(),
+            summary_model: deployment,
+            headers: Vec::new(),
+            discover_model: false,
+            stream_usage: true,
+            generation: GenerationSettings::default(),
+        }
+    }
+
+    /// Send requests to another endpoint instead of `https://api.openai.com/v1`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Use this model for README and section calls
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        // Compatible servers usually serve a single model, so use it for summaries too
+        if self.summary_model.is_empty() {
+            self.summary_model = self.model.clone();
+        }
+        self.discover_model = false;
+        self
+    }
+
+    /// Use these models and sampling settings instead of the built-in ones
+    pub fn with_generation(mut self, generation: GenerationSettings) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Send an extra header with every request (e.g. for a gateway)
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn request_models(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if let Some(azure) = &self.azure {
+            return Ok(vec![azure.deployment.clone()]);
         }
 
+        let request = self.request(self.client.client().get(format!("{}/models", self.base_url)));
+        let response = self.client.send(self.api_name(), request).await?;
+
         let response_json: serde_json::Value = response.json().await?;
-        
-        if let Some(content) = response_json["choices"][0]["message"]["content"].as_str() {
-            Ok(clean_markdown_response(content))
-        } else {
-            Err("Invalid response format from OpenAI API".into())
+        let models = response_json["data"].as_array()
+            .map(|models| {
+                models.iter()
+                    .filter_map(|model| model["id"].as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(models)
+    }
+
+    /// Look up the served model if none was configured
+    async fn resolve_model(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(azure) = &self.azure {
+            if self.base_url.is_empty() {
+                return Err(Error::config("No Azure OpenAI endpoint set. Use --base-url or set AZURE_OPENAI_ENDPOINT").into());
+            }
+            if azure.deployment.is_empty() {
+                return Err(Error::config("No Azure OpenAI deployment set. Use --deployment or set AZURE_OPENAI_DEPLOYMENT").into());
+            }
+            return Ok(());
         }
+        if self.base_url.is_empty() {
+            return Err(Error::config("No endpoint set for the OpenAI-compatible provider. Use --base-url to set one").into());
+        }
+        if !self.discover_model {
+            return Ok(());
+        }
+
+        let models = self.request_models().await
+            .map_err(|e| format!("No model set and the server at {} could not list its models: {}", self.base_url, e))?;
+        let model = models.into_iter().next()
+            .ok_or_else(|| Error::config(format!("No model set and the server at {} lists no models. Use --model to pick one", self.base_url)))?;
+
+        self.summary_model = model.clone();
+        self.model = model;
+        self.discover_model = false;
+        Ok(())
     }
-}
 
-impl OpenAiGenerator {
-    pub fn new(api_key: String) -> Self {
-        Self {
-            client: Arc::new(LlmClient::new(api_key)),
+    fn api_name(&self) -> &'static str {
+        if self.azure.is_some() { "Azure OpenAI API" } else { "OpenAI API" }
+    }
+
+    fn chat_completions_url(&self) -> String {
+        match &self.azure {
+            Some(azure) => format!("{}/openai/deployments/{}/chat/completions", self.base_url, azure.deployment),
+            None => format!("{}/chat/completions", self.base_url),
         }
     }
 
-    pub async fn generate_readme_fast(
-        &self,
-        chunks: &[String],
-        custom_prompt: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
-                .template("{spinner:.cyan} {msg}")
-                .unwrap()
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        pb.set_message("🤖 Generating README with OpenAI...");
-
-        let filtered_chunks = StreamingSectionGenerator::filter_important_chunks(chunks);
-        let context = StreamingSectionGenerator::build_smart_context(&filtered_chunks);
-        
-        pb.set_message("📝 Calling OpenAI API...");
-        let readme_content = self.call_main(&context, custom_prompt).await?;
-        
-        pb.finish_with_message("✅ README generated successfully!");
-        Ok(readme_content)
-    }
-
-    pub async fn generate_readme_streaming(
+    /// Add authentication and extra headers to a request
+    ///
+    /// Azure takes the key in an `api-key` header and needs an `api-version`
+    /// query parameter; everything else uses bearer auth when a key is set.
+    fn request(&self, mut builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(azure) = &self.azure {
+            builder = builder
+                .query(&[("api-version", azure.api_version.as_str())])
+                .header("api-key", self.client.api_key());
+        } else if !self.client.api_key().is_empty() {
+            builder = builder.header("Authorization", format!("Bearer {}", self.client.api_key()));
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder
+    }
+
+    /// Send a chat completions request and return the first choice's content
+    async fn request_completion(&self, params: &CallParams, messages: &[Message]) -> Result<Reply, Box<dyn std::error::Error>> {
+        let request = self.request(self.client.client().post(self.chat_completions_url()))
+            .header("Content-Type", "application/json")
+            .json(&completion_body(params, messages));
+        let response = self.client.send_chat(self.api_name(), request, self.id(), &params.model, prompt_chars(messages)).await?;
+
+        let response_json: serde_json::Value = response.json().await?;
+        let usage = &response_json["usage"];
+        self.client.report_usage(self.id(), &params.model, usage["prompt_tokens"].as_u64(), usage["completion_tokens"].as_u64());
+
+        if response_json["choices"][0]["finish_reason"] == "content_filter" {
+            return Err(ContentBlocked::new(self.api_name(), "content_filter").into());
+        }
+        let choice = &response_json["choices"][0];
+        if let Some(content) = choice["message"]["content"].as_str() {
+            Ok(Reply { text: content.to_string(), truncated: choice["finish_reason"] == "length" })
+        } else {
+            Err(Error::invalid_response(self.api_name(), "the reply has no message content").into())
+        }
+    }
+
+    /// Send a streaming chat completions request, handing content to `on_text` as it arrives
+    ///
+    /// Returns whether the reply was cut off at the output token limit.
+    async fn request_stream(
         &self,
-        chunks: &[String],
-        output_path: &std::path::Path,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        use std::io::Write;
-        
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
-                .template("{spinner:.cyan} {msg}")
-                .unwrap()
-        );
-        pb.enable_steady_tick(Duration::from_millis(100));
-        pb.set_message("🤖 Generating README with OpenAI streaming writes...");
-
-        let mut file = std::fs::File::create(output_path)?;
-        
-        // Write minimal initial header
-        let header = "<!-- README being generated with OpenAI... -->\n\n";
-        file.write_all(header.as_bytes())?;
-        file.flush()?;
-        pb.set_message("📄 README file created, generating sections...");
-
-        let filtered_chunks = StreamingSectionGenerator::filter_important_chunks(chunks);
-        let generator = StreamingSectionGenerator::new();
-        generator.generate_sections_incrementally(self, &filtered_chunks, &mut file, &pb, custom_prompt).await?;
-        
-        // Write final section combination
-        pb.set_message("🔗 Finalizing README structure...");
-        generator.write_final_sections(&mut file).await?;
-        
-        pb.finish_with_message("✅ README.md generated with OpenAI streaming writes!");
-        println!("📝 README.md written to: {}", output_path.display());
-        
-        Ok(())
+        params: &CallParams,
+        messages: &[Message],
+        on_text: &mut TextSink<'_>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut body = completion_body(params, messages);
+        body["stream"] = json!(true);
+        // Usage arrives in a final chunk with no choices
+        if self.stream_usage {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        let request = self.request(self.client.client().post(self.chat_completions_url()))
+            .header("Content-Type", "application/json")
+            .json(&body);
+        let response = self.client.send_chat(self.api_name(), request, self.id(), &params.model, prompt_chars(messages)).await?;
+
+        let mut usage = None;
+        let mut finish_reason = None;
+        let mut done = false;
+        read_events(self.api_name(), response, |event| {
+            if event.data.trim() == "[DONE]" {
+                done = true;
+                return Ok(false);
+            }
+
+            let chunk: serde_json::Value = serde_json::from_str(&event.data)?;
+            if let Some(error) = chunk.get("error") {
+                return Err(Error::network(self.api_name(), format!("error mid-stream: {}", error)).into());
+            }
+            if let Some(content) = chunk["choices"][0]["delta"]["content"].as_str() {
+                on_text(content)?;
+            }
+            if chunk["choices"][0]["finish_reason"] == "content_filter" {
+                return Err(ContentBlocked::new(self.api_name(), "content_filter").into());
+            }
+            if let Some(reason) = chunk["choices"][0]["finish_reason"].as_str() {
+                finish_reason = Some(reason.to_string());
+            }
+            if chunk["usage"].is_object() {
+                usage = Some(chunk["usage"].clone());
+            }
+            Ok(true)
+        }).await?;
+
+        if let Some(usage) = usage {
+            self.client.report_usage(self.id(), &params.model, usage["prompt_tokens"].as_u64(), usage["completion_tokens"].as_u64());
+        }
+        // Some servers never send `[DONE]`; a finish reason means the reply is complete all the same
+        if !done && finish_reason.is_none() {
+            return Err(Error::network(self.api_name(), "the stream ended before the reply was complete").into());
+        }
+        Ok(finish_reason.as_deref() == Some("length"))
     }
 }
 
-/// Clean markdown code blocks from AI responses
-fn clean_markdown_response(content: &str) -> String {
-    let mut cleaned = content.trim();
-    
-    // Remove opening markdown code block
-    if cleaned.starts_with("```markdown") {
-        cleaned = &cleaned[11..];
-    } else if cleaned.starts_with("```md") {
-        cleaned = &cleaned[5..];
-    } else if cleaned.starts_with("```") {
-        cleaned = &cleaned[3..];
-    }
-    
-    // Remove closing markdown code block
-    if cleaned.ends_with("```") {
-        cleaned = &cleaned[..cleaned.len()-3];
-    }
-    
-    cleaned.trim().to_string()
+fn completion_body(params: &CallParams, messages: &[Message]) -> serde_json::Value {
+    let mut body = json!({
+        "model": params.model,
+        "messages": messages,
+        "max_tokens": params.max_output_tokens,
+        "temperature": params.temperature
+    });
+    if let Some(top_p) = params.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if !params.stop_sequences.is_empty() {
+        body["stop"] = json!(params.stop_sequences);
+    }
+    if let Some(seed) = params.seed {
+        body["seed"] = json!(seed);
+    }
+    body
 }
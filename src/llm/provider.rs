@@ -1,41 +1,223 @@
 use crate::cli::LlmProvider;
-use crate::llm::{SpeedOptimizedGenerator, OpenAiGenerator};
-use std::path::Path;
+use crate::error::Error;
+use crate::llm::GenerationSettings;
+use crate::llm::cache::ResponseCache;
+use crate::llm::chat::ChatProvider;
+use crate::llm::client::RetryPolicy;
+use crate::llm::fallback::FallbackChain;
+use crate::llm::fixtures::Fixtures;
+use crate::llm::rate_limit::RateLimits;
+use crate::llm::registry::ProviderRegistry;
+use crate::llm::streaming::{LlmApiClient, StreamingSectionGenerator, TextSink};
+use crate::llm::summarize::HierarchicalSummarizer;
+use crate::llm::usage::UsageTracker;
+use crate::util::manifest::ScanManifest;
+use crate::util::report::SharedReporter;
+use crate::util::tree::ProjectTree;
+use crate::rag::ScannedFile;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+/// Provider settings beyond the API key
+#[derive(Debug, Clone, Default)]
+pub struct ProviderOptions {
+    /// Model for README and section calls (required for Ollama unless exactly one model is installed)
+    pub model: Option<String>,
+    /// Models and sampling settings per call type, taking precedence over `model`
+    pub generation: GenerationSettings,
+    /// Endpoint to use instead of the provider's default
+    pub base_url: Option<String>,
+    /// Extra headers sent with every request (OpenAI-compatible providers)
+    pub headers: Vec<(String, String)>,
+    /// Azure OpenAI deployment name
+    pub deployment: Option<String>,
+    /// Azure OpenAI `api-version` query parameter
+    pub api_version: Option<String>,
+    /// Service-account JSON key for Vertex AI
+    pub credentials: Option<PathBuf>,
+    /// Google Cloud project for Vertex AI
+    pub project: Option<String>,
+    /// Google Cloud region for Vertex AI
+    pub location: Option<String>,
+    /// How failed calls are retried
+    pub retry: RetryPolicy,
+    /// Requests and tokens per minute to stay under, shared by every call in the process
+    pub rate_limits: RateLimits,
+    /// Where replies are cached between runs; nothing is cached when unset
+    pub cache: Option<ResponseCache>,
+    /// Counts the run's token usage and holds calls to its budget
+    pub usage: Option<Arc<UsageTracker>>,
+    /// Continuation requests after a reply is cut off at the output token limit; the default when unset
+    pub max_continuations: Option<u32>,
+    /// Where HTTP exchanges are recorded to or replayed from; requests are sent as usual when unset
+    pub fixtures: Option<Fixtures>,
+    /// JSON file of scripted replies for the mock provider
+    pub mock_replies: Option<PathBuf>,
+}
+
+/// The provider a run uses: a single one, or several tried in order
+#[derive(Debug)]
 pub enum LlmGenerator {
-    Gemini(SpeedOptimizedGenerator),
-    OpenAI(OpenAiGenerator),
+    Single(Box<dyn ChatProvider>),
+    /// Several providers tried in order
+    Fallback(FallbackChain),
+}
+
+impl LlmApiClient for LlmGenerator {
+    async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.call_with_custom_context(section_prompt, custom_prompt).await,
+            Self::Fallback(chain) => Box::pin(chain.call_with_custom_context(section_prompt, custom_prompt)).await,
+        }
+    }
+
+    async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.call_main(context, custom_prompt).await,
+            Self::Fallback(chain) => Box::pin(chain.call_main(context, custom_prompt)).await,
+        }
+    }
+
+    async fn call_summary(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.call_summary(prompt).await,
+            Self::Fallback(chain) => Box::pin(chain.call_summary(prompt)).await,
+        }
+    }
+
+    async fn stream_with_custom_context(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.stream_with_custom_context(section_prompt, custom_prompt, on_text).await,
+            Self::Fallback(chain) => Box::pin(chain.stream_with_custom_context(section_prompt, custom_prompt, on_text)).await,
+        }
+    }
+
+    async fn stream_attributed(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match self {
+            Self::Fallback(chain) => Box::pin(chain.stream_attributed(section_prompt, custom_prompt, on_text)).await,
+            _ => self.stream_with_custom_context(section_prompt, custom_prompt, on_text).await.map(|()| None),
+        }
+    }
 }
 
 impl LlmGenerator {
-    pub fn new(provider: LlmProvider, api_key: String) -> Self {
-        match provider {
-            LlmProvider::Gemini => Self::Gemini(SpeedOptimizedGenerator::new(api_key)),
-            LlmProvider::OpenAI => Self::OpenAI(OpenAiGenerator::new(api_key)),
+    /// Build a built-in provider by name from the [default registry](ProviderRegistry::default)
+    ///
+    /// Fails with [`Error::Config`] for names that are not registered.
+    pub fn new(provider: &str, api_key: String, options: ProviderOptions, reporter: SharedReporter) -> Result<Self, Error> {
+        ProviderRegistry::default().build(provider, api_key, options, reporter)
+    }
+
+    /// Use a provider as it is, without the options applied by a registry
+    pub fn single(provider: impl ChatProvider + 'static) -> Self {
+        Self::Single(Box::new(provider))
+    }
+
+    pub fn reporter(&self) -> &SharedReporter {
+        match self {
+            Self::Single(provider) => provider.reporter(),
+            Self::Fallback(chain) => chain.reporter(),
+        }
+    }
+
+    /// Check that the provider can serve requests before any work is sent to it
+    pub async fn prepare(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.prepare().await,
+            Self::Fallback(chain) => Box::pin(chain.prepare()).await,
+        }
+    }
+
+    /// Models the provider can serve
+    pub async fn list_models(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        match self {
+            Self::Single(provider) => provider.list_models().await,
+            Self::Fallback(chain) => Box::pin(chain.list_models()).await,
+        }
+    }
+
+    /// Size of the packed code context this provider can take, in bytes
+    pub fn context_budget(&self) -> usize {
+        match self {
+            Self::Single(provider) => provider.context_budget(),
+            Self::Fallback(chain) => chain.context_budget(),
         }
     }
 
     pub async fn generate_readme_fast(
         &self,
-        chunks: &[String],
+        context: &str,
         custom_prompt: &str,
     ) -> Result<String, Box<dyn std::error::Error>> {
         match self {
-            Self::Gemini(generator) => generator.generate_readme_fast(chunks, custom_prompt).await,
-            Self::OpenAI(generator) => generator.generate_readme_fast(chunks, custom_prompt).await,
+            Self::Single(provider) => provider.call_main(context, custom_prompt).await,
+            Self::Fallback(chain) => chain.generate_readme_fast(context, custom_prompt).await,
         }
     }
 
     pub async fn generate_readme_streaming(
         &self,
-        chunks: &[String],
-        output_path: &Path,
+        context: &str,
+        file: &mut dyn Write,
         custom_prompt: &str,
+        extra_section: Option<&str>,
+        concurrency: usize,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        // Write minimal initial header
+        let header = "<!-- README being generated... -->\n\n";
+        file.write_all(header.as_bytes())?;
+        file.flush()?;
+
+        let generator = StreamingSectionGenerator::new(self.reporter().clone())
+            .with_concurrency(concurrency);
+        generator.generate_sections_incrementally(self, context, file, custom_prompt).await?;
+        if let Some(section) = extra_section {
+            generator.write_extra_section(file, section)?;
+        }
+
+        // Write final section combination
+        generator.write_final_sections(file).await?;
+
+        Ok(())
+    }
+
+    /// Generate a "Project Structure" section with per-directory descriptions
+    pub async fn generate_structure_section(
+        &self,
+        tree: &ProjectTree,
+        context: &str,
+        custom_prompt: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
         match self {
-            Self::Gemini(generator) => generator.generate_readme_streaming(chunks, output_path, custom_prompt).await,
-            Self::OpenAI(generator) => generator.generate_readme_streaming(chunks, output_path, custom_prompt).await,
+            Self::Single(provider) => StreamingSectionGenerator::generate_structure_section(provider.as_ref(), tree, context, custom_prompt).await,
+            Self::Fallback(chain) => Box::pin(chain.generate_structure_section(tree, context, custom_prompt)).await,
         }
     }
+
+    /// Build a context from hierarchical file and directory summaries
+    pub async fn build_summarized_context(
+        &self,
+        files: &[ScannedFile],
+        manifest: &mut ScanManifest,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        HierarchicalSummarizer::build_context(self, files, manifest, self.reporter().as_ref()).await
+    }
+}
+
+/// The API key to use: the given one, or the provider's environment variable
+///
+/// Providers without keys, or whose key is optional, get an empty key.
+pub fn resolve_api_key(provider: &LlmProvider, api_key: Option<String>) -> Result<String, Error> {
+    ProviderRegistry::default().resolve_api_key(&provider.name(), api_key)
 }
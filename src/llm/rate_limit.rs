@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Requests and tokens allowed per minute for one provider and model
+///
+/// Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_minute.is_none() && self.tokens_per_minute.is_none()
+    }
+}
+
+/// Rough token count for `chars` characters of prompt, at four characters per token
+pub fn estimate_tokens(chars: usize) -> u64 {
+    chars.div_ceil(4) as u64
+}
+
+/// A bucket that refills continuously up to one minute's allowance
+#[derive(Debug)]
+struct Bucket {
+    per_minute: f64,
+    /// May go negative when a call turns out to use more than was taken up front
+    available: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32, now: Instant) -> Self {
+        Self { per_minute: per_minute as f64, available: per_minute as f64, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_minute / 60.0).min(self.per_minute);
+        self.updated = now;
+    }
+
+    /// How long until `amount` is available; a call larger than the whole allowance only waits for a full bucket
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.per_minute) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / self.per_minute)
+        }
+    }
+}
+
+/// Request and token buckets for one provider and model
+#[derive(Debug, Default)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+}
+
+impl Buckets {
+    /// Follow the most recently configured limits, keeping what has been used so far
+    fn configure(&mut self, limits: &RateLimits, now: Instant) {
+        fn update(bucket: &mut Option<Bucket>, limit: Option<u32>, now: Instant) {
+            match (bucket.as_mut(), limit) {
+                (Some(bucket), Some(per_minute)) => {
+                    bucket.refill(now);
+                    bucket.per_minute = per_minute as f64;
+                    bucket.available = bucket.available.min(bucket.per_minute);
+                }
+                (None, Some(per_minute)) => *bucket = Some(Bucket::new(per_minute, now)),
+                (_, None) => *bucket = None,
+            }
+        }
+        update(&mut self.requests, limits.requests_per_minute, now);
+        update(&mut self.tokens, limits.tokens_per_minute, now);
+    }
+
+    /// Take one request and `tokens` tokens, or say how long to wait before trying again
+    fn try_take(&mut self, tokens: u64, now: Instant) -> Option<Duration> {
+        for bucket in [&mut self.requests, &mut self.tokens].into_iter().flatten() {
+            bucket.refill(now);
+        }
+
+        let wait = [(&self.requests, 1.0), (&self.tokens, tokens as f64)]
+            .into_iter()
+            .filter_map(|(bucket, amount)| Some(bucket.as_ref()?.wait_for(amount)))
+            .max()
+            .unwrap_or_default();
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+
+        if let Some(requests) = &mut self.requests {
+            requests.available -= 1.0;
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.available -= tokens as f64;
+        }
+        None
+    }
+}
+
+/// Buckets keyed by provider and model
+type Registry = Mutex<HashMap<(String, String), Arc<Mutex<Buckets>>>>;
+
+/// Buckets for every provider and model used in this process, so separate runs share one quota
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn buckets(provider: &str, model: &str) -> Arc<Mutex<Buckets>> {
+    let mut registry = registry().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    registry.entry((provider.to_string(), model.to_string())).or_default().clone()
+}
+
+/// Wait until a call of about `tokens` input tokens fits under `limits`, then count it
+///
+/// `on_wait` is told about each wait before it starts.
+pub async fn acquire(provider: &str, model: &str, limits: &RateLimits, tokens: u64, mut on_wait: impl FnMut(Duration)) {
+    if limits.is_unlimited() {
+        return;
+    }
+
+    let buckets = buckets(provider, model);
+    loop {
+        let wait = {
+            let mut buckets = buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            buckets.configure(limits, now);
+            buckets.try_take(tokens, now)
+        };
+        let Some(wait) = wait else {
+            return;
+        };
+        on_wait(wait);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Count tokens that were not known when the call was let through, such as its output
+pub fn record_tokens(provider: &str, model: &str, tokens: u64) {
+    let buckets = buckets(provider, model);
+    let mut buckets = buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(bucket) = &mut buckets.tokens {
+        bucket.refill(Instant::now());
+        bucket.available -= tokens as f64;
+    }
+}
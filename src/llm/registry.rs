@@ -0,0 +1,234 @@
+use crate::error::Error;
+use crate::llm::chat::ChatProvider;
+use crate::llm::client::DEFAULT_MAX_CONTINUATIONS;
+use crate::llm::provider::{LlmGenerator, ProviderOptions};
+use crate::llm::{AnthropicGenerator, GenerationConfig, GenerationSettings, MockProvider, OllamaGenerator, OpenAiGenerator, SpeedOptimizedGenerator, VertexEndpoint};
+use crate::util::report::{Event, SharedReporter};
+use std::collections::BTreeMap;
+
+/// What a provider is built from
+pub struct ProviderConfig {
+    /// The resolved API key; empty for providers that take none
+    pub api_key: String,
+    /// The options, with `--model` already folded into the per-call settings
+    pub options: ProviderOptions,
+    pub reporter: SharedReporter,
+}
+
+/// Builds a provider; retries, rate limits, caching and budgets are set up on its client afterwards
+pub type ProviderConstructor = fn(ProviderConfig) -> Box<dyn ChatProvider>;
+
+/// A provider that can be picked by name
+#[derive(Debug, Clone, Copy)]
+pub struct ProviderEntry {
+    /// How the provider is named in progress output
+    pub label: &'static str,
+    /// Environment variable the API key is read from, for providers that take one
+    pub api_key_env: Option<&'static str>,
+    /// Whether a run fails without an API key
+    pub key_required: bool,
+    pub build: ProviderConstructor,
+}
+
+/// Provider names mapped to the constructors that build them
+///
+/// The default registry holds every built-in provider under its command-line
+/// name. Library users can register their own [`ChatProvider`]s beside them.
+#[derive(Debug, Clone)]
+pub struct ProviderRegistry {
+    entries: BTreeMap<String, ProviderEntry>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("gemini", ProviderEntry { label: "Gemini", api_key_env: Some("GEMINI_API_KEY"), key_required: true, build: gemini });
+        // Vertex AI authenticates with a service account instead
+        registry.register("vertex", ProviderEntry { label: "Vertex AI", api_key_env: None, key_required: false, build: vertex });
+        registry.register("openai", ProviderEntry { label: "OpenAI", api_key_env: Some("OPENAI_API_KEY"), key_required: true, build: openai });
+        // Self-hosted servers often run without authentication, so their key is optional
+        registry.register("openai-compatible", ProviderEntry {
+            label: "OpenAI-compatible endpoint",
+            api_key_env: Some("OPENAI_COMPATIBLE_API_KEY"),
+            key_required: false,
+            build: openai_compatible,
+        });
+        registry.register("azure", ProviderEntry { label: "Azure OpenAI", api_key_env: Some("AZURE_OPENAI_API_KEY"), key_required: true, build: azure });
+        registry.register("anthropic", ProviderEntry { label: "Claude", api_key_env: Some("ANTHROPIC_API_KEY"), key_required: true, build: anthropic });
+        // Local models need no key
+        registry.register("ollama", ProviderEntry { label: "Ollama", api_key_env: None, key_required: false, build: ollama });
+        registry.register("mock", ProviderEntry { label: "Mock", api_key_env: None, key_required: false, build: mock });
+        registry
+    }
+}
+
+impl ProviderRegistry {
+    /// A registry without any providers
+    pub fn empty() -> Self {
+        Self { entries: BTreeMap::new() }
+    }
+
+    /// Add a provider, replacing any registered under the same name
+    pub fn register(&mut self, name: impl Into<String>, entry: ProviderEntry) {
+        self.entries.insert(name.into().to_lowercase(), entry);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ProviderEntry> {
+        self.entries.get(&name.to_lowercase())
+    }
+
+    /// Names of the registered providers, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The API key to use: the given one, or the provider's environment variable
+    ///
+    /// Providers without keys, or whose key is optional, get an empty key.
+    pub fn resolve_api_key(&self, name: &str, api_key: Option<String>) -> Result<String, Error> {
+        let entry = self.entry(name)?;
+        match (api_key, entry.api_key_env) {
+            (Some(key), _) => Ok(key),
+            (None, Some(env_var)) => match std::env::var(env_var) {
+                Ok(key) => Ok(key),
+                Err(_) if !entry.key_required => Ok(String::new()),
+                Err(_) => Err(Error::MissingApiKey {
+                    provider: name.to_string(),
+                    credential: "API key",
+                    env_var,
+                    flag: "--api-key",
+                }),
+            },
+            (None, None) => Ok(String::new()),
+        }
+    }
+
+    /// Build the provider registered as `name`
+    pub fn build(&self, name: &str, api_key: String, options: ProviderOptions, reporter: SharedReporter) -> Result<LlmGenerator, Error> {
+        Ok(construct(self.entry(name)?, api_key, options, reporter))
+    }
+
+    fn entry(&self, name: &str) -> Result<&ProviderEntry, Error> {
+        self.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.names().collect();
+            Error::config(format!("Unknown provider `{}` (expected one of {})", name, names.join(", ")))
+        })
+    }
+}
+
+/// Build a provider and set up its client from the options
+fn construct(entry: &ProviderEntry, api_key: String, mut options: ProviderOptions, reporter: SharedReporter) -> LlmGenerator {
+    // `--model` is the base that per-call settings refine
+    let base = GenerationConfig { model: options.model.clone(), ..GenerationConfig::default() };
+    options.generation = GenerationSettings {
+        main: base.merge(&options.generation.main),
+        section: base.merge(&options.generation.section),
+    };
+
+    let (retry, rate_limits, cache, usage) = (options.retry.clone(), options.rate_limits, options.cache.clone(), options.usage.clone());
+    let fixtures = options.fixtures.clone();
+    let max_continuations = options.max_continuations.unwrap_or(DEFAULT_MAX_CONTINUATIONS);
+    let mut provider = (entry.build)(ProviderConfig { api_key, options, reporter });
+    let client = provider.client_mut();
+    *client = client.clone()
+        .with_retry_policy(retry)
+        .with_rate_limits(rate_limits)
+        .with_cache(cache)
+        .with_usage_tracker(usage)
+        .with_fixtures(fixtures)
+        .with_max_continuations(max_continuations);
+    LlmGenerator::Single(provider)
+}
+
+/// Warn that extra headers are only sent to OpenAI-compatible APIs
+fn ignore_headers(config: &ProviderConfig, provider: &str) {
+    if !config.options.headers.is_empty() {
+        config.reporter.report(&Event::Warning {
+            message: format!("Extra headers are not supported for {} and will not be sent", provider),
+        });
+    }
+}
+
+fn gemini(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    ignore_headers(&config, "Gemini");
+    Box::new(SpeedOptimizedGenerator::new(config.api_key, config.reporter).with_generation(config.options.generation))
+}
+
+fn vertex(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    ignore_headers(&config, "Vertex AI");
+    let options = config.options;
+    let mut endpoint = VertexEndpoint::new(options.credentials, options.project, options.location);
+    if let Some(base_url) = options.base_url {
+        endpoint = endpoint.with_base_url(base_url);
+    }
+    Box::new(SpeedOptimizedGenerator::vertex(endpoint, config.reporter).with_generation(options.generation))
+}
+
+fn openai(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    let options = config.options;
+    let mut generator = OpenAiGenerator::new(config.api_key, config.reporter);
+    if let Some(base_url) = options.base_url {
+        generator = generator.with_base_url(base_url);
+    }
+    if let Some(model) = options.model {
+        generator = generator.with_model(model);
+    }
+    Box::new(with_headers(generator.with_generation(options.generation), options.headers))
+}
+
+fn openai_compatible(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    let options = config.options;
+    let generator = OpenAiGenerator::compatible(options.base_url.unwrap_or_default(), options.model, config.api_key, config.reporter);
+    Box::new(with_headers(generator.with_generation(options.generation), options.headers))
+}
+
+fn azure(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    let options = config.options;
+    let generation = options.generation;
+    if generation.main.model.is_some() || generation.section.model.is_some() {
+        config.reporter.report(&Event::Warning {
+            message: "Azure OpenAI uses the deployment's model, so the configured model is ignored".to_string(),
+        });
+    }
+    let generator = OpenAiGenerator::azure(options.base_url, options.deployment, options.api_version, config.api_key, config.reporter)
+        // The deployment decides the model
+        .with_generation(GenerationSettings {
+            main: GenerationConfig { model: None, ..generation.main },
+            section: GenerationConfig { model: None, ..generation.section },
+        });
+    Box::new(with_headers(generator, options.headers))
+}
+
+fn anthropic(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    ignore_headers(&config, "Anthropic");
+    let options = config.options;
+    if options.generation.main.seed.is_some() || options.generation.section.seed.is_some() {
+        config.reporter.report(&Event::Warning {
+            message: "Anthropic does not support seeds, so the configured seed will not be sent".to_string(),
+        });
+    }
+    let generator = AnthropicGenerator::new(config.api_key, config.reporter).with_generation(options.generation);
+    match options.base_url {
+        Some(base_url) => Box::new(generator.with_base_url(base_url)),
+        None => Box::new(generator),
+    }
+}
+
+fn ollama(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    ignore_headers(&config, "Ollama");
+    let options = config.options;
+    Box::new(OllamaGenerator::new(options.model, options.base_url, config.reporter).with_generation(options.generation))
+}
+
+fn mock(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    let options = config.options;
+    let provider = MockProvider::new(config.reporter).with_generation(options.generation);
+    match options.mock_replies {
+        Some(script) => Box::new(provider.with_script(script)),
+        None => Box::new(provider),
+    }
+}
+
+fn with_headers(generator: OpenAiGenerator, headers: Vec<(String, String)>) -> OpenAiGenerator {
+    headers.into_iter().fold(generator, |generator, (name, value)| generator.with_header(name, value))
+}
@@ -0,0 +1,108 @@
+use crate::error::Error;
+
+/// One server-sent event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field, if the server named the event
+    pub event: Option<String>,
+    /// All `data:` lines of the event, joined with newlines
+    pub data: String,
+}
+
+/// Incremental parser for `text/event-stream` bodies
+///
+/// Bytes are buffered until a line is complete, so a multi-byte character
+/// split across network chunks is only decoded once all of it has arrived.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a chunk of the body and return the events it completed
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<SseEvent>, std::str::Utf8Error> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = std::str::from_utf8(&line[..line.len() - 1])?;
+            if let Some(event) = self.process_line(line.strip_suffix('\r').unwrap_or(line)) {
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// The last event, if the body ended without the blank line that normally closes it
+    pub fn finish(&mut self) -> Result<Option<SseEvent>, std::str::Utf8Error> {
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            let line = std::str::from_utf8(&line)?;
+            if let Some(event) = self.process_line(line.strip_suffix('\r').unwrap_or(line)) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(self.dispatch())
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Lines starting with a colon are comments, often used as keep-alives
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            // `id` and `retry` only matter for reconnecting, which is not done here
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent { event, data: std::mem::take(&mut self.data).join("\n") })
+    }
+}
+
+/// Read a `text/event-stream` response, handing each event to `on_event` as it arrives
+///
+/// Reading stops early when `on_event` returns `false`. A connection that
+/// drops mid-stream is reported as a network error from `api`.
+pub async fn read_events(
+    api: &str,
+    mut response: reqwest::Response,
+    mut on_event: impl FnMut(SseEvent) -> Result<bool, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut parser = SseParser::new();
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| Error::network(api, format!("stream interrupted: {}", e)))? {
+        for event in parser.feed(&chunk)? {
+            if !on_event(event)? {
+                return Ok(());
+            }
+        }
+    }
+
+    if let Some(event) = parser.finish()? {
+        on_event(event)?;
+    }
+    Ok(())
+}
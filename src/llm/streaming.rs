@@ -1,23 +1,75 @@
-use std::fs::File;
+use std::cell::RefCell;
 use std::io::Write;
-use indicatif::ProgressBar;
 use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use crate::error::Error;
+use crate::util::report::{Event, Reporter, SharedReporter};
+use crate::util::tree::ProjectTree;
+
+/// Default size of the packed code context, in bytes
+pub const DEFAULT_CONTEXT_BUDGET: usize = 15000;
+
+/// Default number of section calls in flight at once
+pub const DEFAULT_SECTION_CONCURRENCY: usize = 3;
+
+/// Receives streamed text as it arrives; an error stops the stream
+pub type TextSink<'a> = dyn FnMut(&str) -> Result<(), Box<dyn std::error::Error>> + 'a;
 
 /// Common interface for LLM API calls with custom context
+// The futures are only ever awaited in place, never spawned, so no `Send` bound is needed
+#[allow(async_fn_in_trait)]
 pub trait LlmApiClient {
     /// Make an API call with custom prompt context for streaming sections
     async fn call_with_custom_context(&self, section_prompt: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>>;
     
     /// Make a main API call for full README generation
     async fn call_main(&self, context: &str, custom_prompt: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Make a short call on a cheap, fast model (used for file and directory summaries)
+    async fn call_summary(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Make a section call, handing the reply to `on_text` piece by piece as it arrives
+    ///
+    /// Providers without streaming support hand over the whole reply at once.
+    async fn stream_with_custom_context(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let content = self.call_with_custom_context(section_prompt, custom_prompt).await?;
+        on_text(&content)
+    }
+
+    /// Make a streamed section call, naming the provider that answered it
+    ///
+    /// Only clients that pick between several providers name one; the rest return `None`.
+    async fn stream_attributed(
+        &self,
+        section_prompt: &str,
+        custom_prompt: &str,
+        on_text: &mut TextSink<'_>,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.stream_with_custom_context(section_prompt, custom_prompt, on_text).await?;
+        Ok(None)
+    }
 }
 
 /// Common streaming section generator
-pub struct StreamingSectionGenerator;
+pub struct StreamingSectionGenerator {
+    reporter: SharedReporter,
+    concurrency: usize,
+}
 
 impl StreamingSectionGenerator {
-    pub fn new() -> Self {
-        Self
+    pub fn new(reporter: SharedReporter) -> Self {
+        Self { reporter, concurrency: DEFAULT_SECTION_CONCURRENCY }
+    }
+
+    /// Generate up to `concurrency` sections at once (at least one)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
     }
 
     /// Clean markdown code block wrapping from LLM responses
@@ -40,194 +92,94 @@ impl StreamingSectionGenerator {
         }
     }
 
-    /// Generate all sections incrementally using the provided API client
-    pub async fn generate_sections_incrementally<T: LlmApiClient>(
-        &self,
-        api_client: &T,
-        chunks: &[String],
-        file: &mut File,
-        pb: &ProgressBar,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let context = Self::build_smart_context(chunks);
-        
-        // Generate each section
-        self.write_title_section(api_client, &context, file, pb, custom_prompt).await?;
-        self.write_description_section(api_client, &context, file, pb, custom_prompt).await?;
-        self.write_features_section(api_client, &context, file, pb, custom_prompt).await?;
-        self.write_installation_section(api_client, &context, file, pb, custom_prompt).await?;
-        self.write_usage_section(api_client, &context, file, pb, custom_prompt).await?;
-        
-        Ok(())
-    }
-
-    async fn write_title_section<T: LlmApiClient>(
+    /// Generate all sections using the provided API client, writing them in order
+    ///
+    /// Sections are requested concurrently. A failed section does not stop the
+    /// others; it is marked in the file and reported in the returned error once
+    /// every section has been written.
+    pub async fn generate_sections_incrementally<T: LlmApiClient + ?Sized>(
         &self,
         api_client: &T,
         context: &str,
-        file: &mut File,
-        pb: &ProgressBar,
+        file: &mut dyn Write,
         custom_prompt: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        pb.set_message("📝 Generating title...");
-        println!("🔄 Generating project title...");
-        
-        let title_prompt = format!(
-            "Generate ONLY a project title in markdown format (# Title). Be factual and concise, no opinions:\n\n{}",
-            &context[..context.len().min(3000)]
-        );
-        
-        let title = api_client.call_with_custom_context(&title_prompt, custom_prompt).await?;
-        let cleaned_title = Self::clean_markdown_wrapping(&title);
-        
-        for line in cleaned_title.lines() {
-            file.write_all(line.as_bytes())?;
-            file.write_all("\n".as_bytes())?;
-            file.flush()?;
-        }
-        
-        file.write_all("\n".as_bytes())?;
-        file.flush()?;
-        println!("✅ Title written");
-        
-        Ok(())
-    }
+        let sections = [
+            ("title", format!(
+                "Generate ONLY a project title in markdown format (# Title). Be factual and concise, no opinions:\n\n{}",
+                context_prefix(context, 3000)
+            )),
+            ("description", format!(
+                "Generate ONLY a brief project description (2-3 sentences). Be factual and objective, no opinions:\n\n{}",
+                context_prefix(context, 3000)
+            )),
+            ("features", format!(
+                "Generate a ## Features section with bullet points of key capabilities. Be factual, no opinions:\n\n{}",
+                context_prefix(context, 4000)
+            )),
+            ("installation", format!(
+                "Generate a ## Installation section with clear setup steps. Be factual and direct:\n\n{}",
+                context_prefix(context, 4000)
+            )),
+            ("usage", format!(
+                "Generate a ## Usage section with practical examples and command-line usage. Be factual and clear:\n\n{}",
+                context_prefix(context, 4000)
+            )),
+        ];
 
-    async fn write_description_section<T: LlmApiClient>(
-        &self,
-        api_client: &T,
-        context: &str,
-        file: &mut File,
-        pb: &ProgressBar,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        pb.set_message("📝 Writing description...");
-        println!("🔄 Generating description...");
-        
-        let desc_prompt = format!(
-            "Generate ONLY a brief project description (2-3 sentences). Be factual and objective, no opinions:\n\n{}",
-            &context[..context.len().min(3000)]
-        );
-        
-        let description = api_client.call_with_custom_context(&desc_prompt, custom_prompt).await?;
-        let cleaned_description = Self::clean_markdown_wrapping(&description);
-        
-        for line in cleaned_description.lines() {
-            file.write_all(line.as_bytes())?;
-            file.write_all("\n".as_bytes())?;
-            file.flush()?;
-        }
-        
-        file.write_all("\n".as_bytes())?;
-        file.flush()?;
-        println!("✅ Description written");
-        
-        Ok(())
-    }
+        let assembly = RefCell::new(SectionAssembly::new(file, self.reporter.as_ref(), &sections));
+        // Kept whole so the run's error can be classified by what went wrong first
+        let first_error = RefCell::new(None);
+        let written: Vec<std::io::Result<()>> = stream::iter(sections.iter().enumerate())
+            .map(|(index, (section, prompt))| {
+                let (assembly, first_error) = (&assembly, &first_error);
+                async move {
+                    self.reporter.report(&Event::SectionStarted { section: section.to_string() });
 
-    async fn write_features_section<T: LlmApiClient>(
-        &self,
-        api_client: &T,
-        context: &str,
-        file: &mut File,
-        pb: &ProgressBar,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        pb.set_message("✨ Adding features...");
-        println!("🔄 Generating features...");
-        
-        let features_prompt = format!(
-            "Generate a ## Features section with bullet points of key capabilities. Be factual, no opinions:\n\n{}",
-            &context[..context.len().min(4000)]
-        );
-        
-        let features = api_client.call_with_custom_context(&features_prompt, custom_prompt).await?;
-        let cleaned_features = Self::clean_markdown_wrapping(&features);
-        
-        for line in cleaned_features.lines() {
-            file.write_all(line.as_bytes())?;
-            file.write_all("\n".as_bytes())?;
-            file.flush()?;
-        }
-        
-        file.write_all("\n".as_bytes())?;
-        file.flush()?;
-        println!("✅ Features written");
-        
-        Ok(())
-    }
+                    let mut filter = FenceFilter::default();
+                    let streamed = api_client
+                        .stream_attributed(prompt, custom_prompt, &mut |text| {
+                            Ok(assembly.borrow_mut().push(index, &filter.push(text))?)
+                        })
+                        .await;
+                    let tail = assembly.borrow_mut().push(index, &filter.finish());
+                    let outcome = streamed.and_then(|source| Ok(tail.map(|()| source)?)).map_err(|err| {
+                        let message = err.to_string();
+                        first_error.borrow_mut().get_or_insert(Error::from(err));
+                        message
+                    });
 
-    async fn write_installation_section<T: LlmApiClient>(
-        &self,
-        api_client: &T,
-        context: &str,
-        file: &mut File,
-        pb: &ProgressBar,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        pb.set_message("🔧 Adding installation...");
-        println!("🔄 Generating installation guide...");
-        
-        let install_prompt = format!(
-            "Generate a ## Installation section with clear setup steps. Be factual and direct:\n\n{}",
-            &context[..context.len().min(4000)]
-        );
-        
-        let installation = api_client.call_with_custom_context(&install_prompt, custom_prompt).await?;
-        let cleaned_installation = Self::clean_markdown_wrapping(&installation);
-        
-        for line in cleaned_installation.lines() {
-            file.write_all(line.as_bytes())?;
-            file.write_all("\n".as_bytes())?;
-            file.flush()?;
-        }
-        
-        file.write_all("\n".as_bytes())?;
-        file.flush()?;
-        println!("✅ Installation written");
-        
-        Ok(())
-    }
+                    if let Err(error) = &outcome {
+                        self.reporter.report(&Event::SectionFailed { section: section.to_string(), error: error.clone() });
+                    }
+                    assembly.borrow_mut().complete(index, outcome)
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+        written.into_iter().collect::<std::io::Result<()>>()?;
 
-    async fn write_usage_section<T: LlmApiClient>(
-        &self,
-        api_client: &T,
-        context: &str,
-        file: &mut File,
-        pb: &ProgressBar,
-        custom_prompt: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        pb.set_message("📖 Adding usage...");
-        println!("🔄 Generating usage examples...");
-        
-        let usage_prompt = format!(
-            "Generate a ## Usage section with practical examples and command-line usage. Be factual and clear:\n\n{}",
-            &context[..context.len().min(4000)]
-        );
-        
-        let usage = api_client.call_with_custom_context(&usage_prompt, custom_prompt).await?;
-        let cleaned_usage = Self::clean_markdown_wrapping(&usage);
-        
-        for line in cleaned_usage.lines() {
-            file.write_all(line.as_bytes())?;
-            file.write_all("\n".as_bytes())?;
-            file.flush()?;
+        let failed = assembly.into_inner().failed();
+        if let Some(source) = first_error.into_inner() {
+            return Err(Error::SectionsFailed { sections: failed, source: Box::new(source) }.into());
         }
-        
-        file.write_all("\n".as_bytes())?;
-        file.flush()?;
-        println!("✅ Usage written");
-        
+
         Ok(())
     }
 
     /// Build smart context from code chunks
     pub fn build_smart_context(chunks: &[String]) -> String {
+        Self::build_smart_context_within(chunks, DEFAULT_CONTEXT_BUDGET)
+    }
+
+    /// Build smart context from code chunks, limited to `max_len` bytes
+    pub fn build_smart_context_within(chunks: &[String], max_len: usize) -> String {
         let combined = chunks.join("\n\n");
         
         // Limit context size to prevent token overflow
-        if combined.len() > 15000 {
-            format!("{}...[truncated]", &combined[..15000])
+        if combined.len() > max_len {
+            format!("{}...[truncated]", context_prefix(&combined, max_len))
         } else {
             combined
         }
@@ -239,17 +191,20 @@ impl StreamingSectionGenerator {
         
         for chunk in chunks {
             let chunk_lower = chunk.to_lowercase();
+            // Skip rules only look at the `path:` header line, so code that merely
+            // mentions `.json` or calls `df.describe()` is not thrown away
+            let header = chunk_lower.lines().next().unwrap_or("");
             
             // Skip unimportant files
-            if chunk_lower.contains("target/") 
-                || chunk_lower.contains("node_modules/")
-                || chunk_lower.contains(".git/")
-                || chunk_lower.contains("build/")
-                || chunk_lower.contains("dist/")
-                || chunk_lower.contains(".json")
-                || chunk_lower.contains(".lock")
-                || chunk_lower.contains(".d")
-                || chunk_lower.contains("deps/") {
+            if header.contains("target/") 
+                || header.contains("node_modules/")
+                || header.contains(".git/")
+                || header.contains("build/")
+                || header.contains("dist/")
+                || header.contains(".json")
+                || header.contains(".lock")
+                || header.ends_with(".d:")
+                || header.contains("deps/") {
                 continue;
             }
             
@@ -272,10 +227,70 @@ impl StreamingSectionGenerator {
         filtered.into_iter().take(50).collect()
     }
 
+    /// Generate a "Project Structure" section from the rendered tree
+    ///
+    /// The tree itself is deterministic; only the one-line directory
+    /// descriptions come from the model.
+    pub async fn generate_structure_section<T: LlmApiClient + ?Sized>(
+        api_client: &T,
+        tree: &ProjectTree,
+        context: &str,
+        custom_prompt: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let rendered = tree.render();
+        let directories: Vec<String> = tree.directories().into_iter().take(40).collect();
+
+        let mut section = format!("## Project Structure\n\n```text\n{}```\n", rendered);
+        if directories.is_empty() {
+            return Ok(section);
+        }
+
+        let structure_prompt = format!(
+            "For each directory listed below, write a one-line factual description of what it contains. Output exactly one line per directory in the form `path: description`, using the paths exactly as listed, and nothing else.\n\nDirectories:\n{}\n\nProject tree:\n{}\n\n{}",
+            directories.join("\n"),
+            rendered,
+            context_prefix(context, 4000)
+        );
+        let response = api_client.call_with_custom_context(&structure_prompt, custom_prompt).await?;
+        let cleaned = Self::clean_markdown_wrapping(&response);
+
+        let mut descriptions = Vec::new();
+        for line in cleaned.lines() {
+            let line = line.trim().trim_start_matches(['-', '*', ' ']).replace('`', "");
+            if let Some((path, description)) = line.split_once(':') {
+                let path = path.trim();
+                let path = if path.ends_with('/') { path.to_string() } else { format!("{}/", path) };
+                if directories.contains(&path) && !description.trim().is_empty() {
+                    descriptions.push(format!("- `{}` — {}", path, description.trim()));
+                }
+            }
+        }
+
+        if !descriptions.is_empty() {
+            section.push('\n');
+            section.push_str(&descriptions.join("\n"));
+            section.push('\n');
+        }
+
+        Ok(section)
+    }
+
+    /// Write a pre-generated section verbatim
+    pub fn write_extra_section(
+        &self,
+        file: &mut dyn Write,
+        section: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        file.write_all(section.as_bytes())?;
+        file.write_all("\n".as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
     /// Write final section combination
     pub async fn write_final_sections(
         &self,
-        file: &mut File,
+        file: &mut dyn Write,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let footer = "\n## 🤝 Contributing\n\nContributions are welcome! Please feel free to submit a Pull Request.\n\n## 📄 License\n\nThis project is licensed under the MIT License - see the LICENSE file for details.\n";
         
@@ -285,3 +300,236 @@ impl StreamingSectionGenerator {
         Ok(())
     }
 }
+
+/// Writes sections to the file in their declared order while they are generated concurrently
+///
+/// The earliest unfinished section goes straight to the file as it streams.
+/// Text for later sections is held until every section before it is complete.
+struct SectionAssembly<'a> {
+    file: &'a mut dyn Write,
+    reporter: &'a dyn Reporter,
+    sections: Vec<AssembledSection>,
+    /// Index of the section currently being written to the file
+    next: usize,
+}
+
+struct AssembledSection {
+    name: String,
+    /// Text received while an earlier section was still being written
+    held: String,
+    /// Some text has reached the file
+    written: bool,
+    /// Set once the section's stream has ended, with the provider that wrote it or the error if it failed
+    outcome: Option<Result<Option<String>, String>>,
+}
+
+impl<'a> SectionAssembly<'a> {
+    fn new(file: &'a mut dyn Write, reporter: &'a dyn Reporter, sections: &[(&str, String)]) -> Self {
+        let sections = sections.iter()
+            .map(|(name, _)| AssembledSection { name: name.to_string(), held: String::new(), written: false, outcome: None })
+            .collect();
+        Self { file, reporter, sections, next: 0 }
+    }
+
+    /// Add streamed text to a section
+    fn push(&mut self, index: usize, text: &str) -> std::io::Result<()> {
+        if index == self.next {
+            self.write(index, text)
+        } else {
+            self.sections[index].held.push_str(text);
+            Ok(())
+        }
+    }
+
+    /// Mark a section's stream as ended and write out every section that is now ready
+    ///
+    /// A failed section keeps whatever text arrived, followed by a comment
+    /// saying why it stopped. A section from a named provider is followed by
+    /// a comment naming it.
+    fn complete(&mut self, index: usize, outcome: Result<Option<String>, String>) -> std::io::Result<()> {
+        self.sections[index].outcome = Some(outcome);
+
+        while let Some(outcome) = self.sections.get(self.next).and_then(|section| section.outcome.clone()) {
+            let section = &self.sections[self.next];
+            match outcome {
+                Ok(source) => {
+                    // The fence filter holds back trailing whitespace, so the section ends right after its text
+                    let ending = if section.written { "\n\n" } else { "\n" };
+                    self.file.write_all(ending.as_bytes())?;
+                    if let Some(source) = source {
+                        let note = format!("<!-- {} generated by {} -->\n\n", section.name, source);
+                        self.file.write_all(note.as_bytes())?;
+                    }
+                    self.reporter.report(&Event::SectionFinished { section: section.name.clone() });
+                }
+                Err(error) => {
+                    let separator = if section.written { "\n\n" } else { "" };
+                    let note = format!("{}<!-- {} section failed: {} -->\n\n", separator, section.name, error);
+                    self.file.write_all(note.as_bytes())?;
+                }
+            }
+            self.file.flush()?;
+
+            self.next += 1;
+            if let Some(section) = self.sections.get_mut(self.next) {
+                let held = std::mem::take(&mut section.held);
+                self.write(self.next, &held)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, index: usize, text: &str) -> std::io::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.file.write_all(text.as_bytes())?;
+        self.file.flush()?;
+        let section = &mut self.sections[index];
+        section.written = true;
+        self.reporter.report(&Event::TextDelta { section: section.name.clone(), text: text.to_string() });
+        Ok(())
+    }
+
+    /// Failed sections with their errors, in declared order
+    fn failed(&self) -> Vec<String> {
+        self.sections.iter()
+            .filter_map(|section| match &section.outcome {
+                Some(Err(error)) => Some(format!("{} section: {}", section.name, error)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Trims a streamed markdown reply and strips a code fence wrapped around it
+///
+/// Models sometimes answer with the whole section inside ```` ```markdown ````.
+/// A first line starting with a backtick is held back until it is known
+/// whether it opens such a fence. Whitespace, and after an opening fence a
+/// bare closing fence, is held back until something follows it, so nothing
+/// is left over to strip when the reply ends. Everything else passes straight
+/// through.
+#[derive(Debug, Default)]
+struct FenceFilter {
+    /// Some non-whitespace text has arrived
+    started: bool,
+    /// The first line, while it might still be an opening fence
+    first_line: Option<String>,
+    /// The reply opened with a fence, so a trailing closing fence is dropped
+    fenced: bool,
+    /// Nothing has been passed on since the opening fence
+    at_line_start: bool,
+    /// Whitespace, possibly around a closing fence candidate, not passed on yet
+    held: String,
+}
+
+impl FenceFilter {
+    fn push(&mut self, text: &str) -> String {
+        let mut out = String::new();
+        for c in text.chars() {
+            self.push_char(c, &mut out);
+        }
+        out
+    }
+
+    fn push_char(&mut self, c: char, out: &mut String) {
+        if !self.started {
+            if c.is_whitespace() {
+                return;
+            }
+            self.started = true;
+            if c == '`' {
+                self.first_line = Some(String::new());
+            }
+        }
+
+        if let Some(first_line) = &mut self.first_line {
+            if c != '\n' {
+                first_line.push(c);
+                return;
+            }
+            let first_line = self.first_line.take().unwrap_or_default();
+            if matches!(first_line.trim(), "```" | "```markdown" | "```md") {
+                self.fenced = true;
+                self.at_line_start = true;
+            } else {
+                out.push_str(&first_line);
+                self.held.push('\n');
+            }
+            return;
+        }
+
+        if c.is_whitespace() || (c == '`' && self.may_close_with(c)) {
+            self.held.push(c);
+            return;
+        }
+
+        out.push_str(&std::mem::take(&mut self.held));
+        out.push(c);
+        self.at_line_start = false;
+    }
+
+    /// Whether the held text plus `c` could still be a closing fence on its own line
+    fn may_close_with(&self, c: char) -> bool {
+        if !self.fenced {
+            return false;
+        }
+        let (before, line) = match self.held.rfind('\n') {
+            Some(end) => (&self.held[..end], &self.held[end + 1..]),
+            None if self.at_line_start => ("", self.held.as_str()),
+            None => return false,
+        };
+        let line = format!("{}{}", line.trim_start(), c);
+        !before.contains('`') && line.len() <= 3 && line.chars().all(|c| c == '`')
+    }
+
+    /// Whatever was still held back once the reply is complete
+    fn finish(&mut self) -> String {
+        if let Some(first_line) = self.first_line.take() {
+            return first_line.trim_end().to_string();
+        }
+
+        let held = std::mem::take(&mut self.held);
+        if held.trim().is_empty() || (self.fenced && held.trim() == "```") {
+            String::new()
+        } else {
+            held.trim_end().to_string()
+        }
+    }
+}
+
+/// Longest prefix of `context` that fits in `max_len` bytes without splitting a character
+pub fn context_prefix(context: &str, max_len: usize) -> &str {
+    if context.len() <= max_len {
+        return context;
+    }
+
+    let mut end = max_len;
+    while !context.is_char_boundary(end) {
+        end -= 1;
+    }
+    &context[..end]
+}
+
+/// Clean markdown code blocks from AI responses
+pub fn clean_markdown_response(content: &str) -> String {
+    let mut cleaned = content.trim();
+    
+    // Remove opening markdown code block
+    if cleaned.starts_with("```markdown") {
+        cleaned = &cleaned[11..];
+    } else if cleaned.starts_with("```md") {
+        cleaned = &cleaned[5..];
+    } else if cleaned.starts_with("```") {
+        cleaned = &cleaned[3..];
+    }
+    
+    // Remove closing markdown code block
+    if cleaned.ends_with("```") {
+        cleaned = &cleaned[..cleaned.len()-3];
+    }
+    
+    cleaned.trim().to_string()
+}
\ No newline at end of file
@@ -0,0 +1,285 @@
+use crate::llm::streaming::{context_prefix, LlmApiClient};
+use crate::rag::ScannedFile;
+use futures::stream::{self, StreamExt};
+use crate::util::manifest::{content_hash, ScanManifest};
+use crate::util::report::{Event, Reporter};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Number of summary calls in flight at once
+const SUMMARY_CONCURRENCY: usize = 4;
+
+/// Largest slice of a single file sent to the summary model
+const MAX_FILE_CHARS: usize = 12000;
+
+/// Budget for the rendered summary tree in the final context
+const TREE_BUDGET: usize = 12000;
+
+/// Budget for raw top-priority files appended after the summary tree
+const RAW_FILES_BUDGET: usize = 8000;
+
+type CallResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// A directory in the summary tree
+#[derive(Debug, Default)]
+struct DirNode {
+    files: Vec<(String, String)>,
+    children: Vec<PathBuf>,
+    summary: Option<String>,
+}
+
+/// Map-reduce summarizer for repositories larger than the context window
+///
+/// Every file is summarized by a cheap model, summaries are rolled up per
+/// directory from the leaves upwards, and the final context is the rendered
+/// tree of summaries followed by the highest-priority raw files.
+pub struct HierarchicalSummarizer;
+
+impl HierarchicalSummarizer {
+    pub async fn build_context<T: LlmApiClient + ?Sized>(
+        api_client: &T,
+        files: &[ScannedFile],
+        manifest: &mut ScanManifest,
+        reporter: &dyn Reporter,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        reporter.report(&Event::SummarizeStarted { files: files.len() });
+
+        // Save after each phase so finished summaries survive a failed run
+        let file_summaries = Self::summarize_files(api_client, files, manifest, reporter).await;
+        manifest.save()?;
+        let file_summaries = file_summaries?;
+
+        let mut tree = Self::build_tree(files, &file_summaries);
+        reporter.report(&Event::DirectoriesSummarizing { directories: tree.len() });
+        let rollup = Self::summarize_directories(api_client, &mut tree, manifest).await;
+        manifest.save()?;
+        rollup?;
+
+        reporter.report(&Event::SummarizeFinished { files: files.len() });
+
+        let mut context = String::from("## Repository Summary\n\n");
+        context.push_str(&Self::render_tree(&tree));
+        context.push_str("\n\n## Key Files\n\n");
+        context.push_str(&Self::pack_raw_files(files));
+        Ok(context)
+    }
+
+    /// Summarize every file, reusing cached summaries for unchanged content
+    async fn summarize_files<T: LlmApiClient + ?Sized>(
+        api_client: &T,
+        files: &[ScannedFile],
+        manifest: &mut ScanManifest,
+        reporter: &dyn Reporter,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut summaries: Vec<Option<String>> = files.iter()
+            .map(|file| manifest.file_summary(&file.relative_path, &file.hash).cloned())
+            .collect();
+
+        for (file, _) in files.iter().zip(&summaries).filter(|(_, summary)| summary.is_some()) {
+            reporter.report(&Event::FileSummarized { path: file.relative_path.display().to_string(), cached: true });
+        }
+
+        let pending: Vec<usize> = (0..files.len()).filter(|&index| summaries[index].is_none()).collect();
+        let results: Vec<(usize, CallResult<String>)> = stream::iter(pending)
+            .map(|index| async move {
+                let file = &files[index];
+                let prompt = format!(
+                    "Summarize the purpose and key contents of this file in 2-3 sentences for someone writing the project README. Mention important public types, functions, commands or configuration. Output plain text only.\n\nFile: {}\n\n{}",
+                    file.relative_path.display(),
+                    context_prefix(&file.content, MAX_FILE_CHARS)
+                );
+                (index, api_client.call_summary(&prompt).await)
+            })
+            .buffer_unordered(SUMMARY_CONCURRENCY)
+            .inspect(|(index, _)| {
+                let path = files[*index].relative_path.display().to_string();
+                reporter.report(&Event::FileSummarized { path, cached: false });
+            })
+            .collect()
+            .await;
+
+        let mut first_error = None;
+        for (index, result) in results {
+            match result {
+                Ok(summary) => {
+                    manifest.set_file_summary(&files[index].relative_path, summary.clone());
+                    summaries[index] = Some(summary);
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+
+        Ok(summaries.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    /// Group file summaries into a directory tree keyed by relative directory path
+    fn build_tree(files: &[ScannedFile], summaries: &[String]) -> BTreeMap<PathBuf, DirNode> {
+        let mut tree: BTreeMap<PathBuf, DirNode> = BTreeMap::new();
+        tree.entry(PathBuf::new()).or_default();
+
+        for (file, summary) in files.iter().zip(summaries) {
+            let dir = file.relative_path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let name = file.relative_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            tree.entry(dir.clone()).or_default().files.push((name, summary.clone()));
+
+            // Register every ancestor so the roll-up reaches the root
+            let mut child = dir;
+            while let Some(parent) = child.parent().map(Path::to_path_buf) {
+                let node = tree.entry(parent.clone()).or_default();
+                if !node.children.contains(&child) {
+                    node.children.push(child.clone());
+                }
+                child = parent;
+            }
+        }
+
+        for node in tree.values_mut() {
+            node.files.sort();
+            node.children.sort();
+        }
+        tree
+    }
+
+    /// Summarize directories bottom-up, one depth level at a time
+    async fn summarize_directories<T: LlmApiClient + ?Sized>(
+        api_client: &T,
+        tree: &mut BTreeMap<PathBuf, DirNode>,
+        manifest: &mut ScanManifest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let max_depth = tree.keys().map(|dir| dir.components().count()).max().unwrap_or(0);
+        let mut used_hashes = HashSet::new();
+
+        for depth in (0..=max_depth).rev() {
+            let mut prompts = Vec::new();
+
+            for (dir, node) in tree.iter().filter(|(dir, _)| dir.components().count() == depth) {
+                let mut items: Vec<String> = node.files.iter()
+                    .map(|(name, summary)| format!("- {}: {}", name, summary))
+                    .collect();
+                items.extend(node.children.iter().filter_map(|child| {
+                    let summary = tree.get(child)?.summary.as_ref()?;
+                    let name = child.file_name()?.to_string_lossy();
+                    Some(format!("- {}/: {}", name, summary))
+                }));
+
+                // A directory with a single item is described by that item
+                if items.len() <= 1 {
+                    let summary = node.files.first().map(|(_, summary)| summary.clone())
+                        .or_else(|| node.children.first().and_then(|child| tree.get(child)?.summary.clone()));
+                    prompts.push((dir.clone(), None, summary));
+                    continue;
+                }
+
+                let dir_label = if dir.as_os_str().is_empty() {
+                    "the project root".to_string()
+                } else {
+                    format!("`{}`", dir.display())
+                };
+                let prompt = format!(
+                    "Summarize what {} contains in 1-2 sentences, based on these summaries of its files and subdirectories. Output plain text only.\n\n{}",
+                    dir_label,
+                    items.join("\n")
+                );
+                let hash = content_hash(&prompt);
+                used_hashes.insert(hash.clone());
+                match manifest.directory_summary(&hash) {
+                    Some(summary) => prompts.push((dir.clone(), None, Some(summary.clone()))),
+                    None => prompts.push((dir.clone(), Some((hash, prompt)), None)),
+                }
+            }
+
+            let results: Vec<(PathBuf, Option<String>, CallResult<Option<String>>)> = stream::iter(prompts)
+                .map(|(dir, request, summary)| async move {
+                    match request {
+                        Some((hash, prompt)) => {
+                            let result = api_client.call_summary(&prompt).await.map(Some);
+                            (dir, Some(hash), result)
+                        }
+                        None => (dir, None, Ok(summary)),
+                    }
+                })
+                .buffer_unordered(SUMMARY_CONCURRENCY)
+                .collect()
+                .await;
+
+            for (dir, hash, result) in results {
+                let summary = result.map_err(|e| format!("Failed to summarize directory {}: {}", dir.display(), e))?;
+                if let (Some(hash), Some(summary)) = (hash, &summary) {
+                    manifest.set_directory_summary(hash, summary.clone());
+                }
+                if let Some(node) = tree.get_mut(&dir) {
+                    node.summary = summary;
+                }
+            }
+        }
+
+        // Forget roll-ups for directories that no longer look the same
+        manifest.directory_summaries.retain(|hash, _| used_hashes.contains(hash));
+        Ok(())
+    }
+
+    /// Render the summary tree, dropping per-file lines if it exceeds the budget
+    fn render_tree(tree: &BTreeMap<PathBuf, DirNode>) -> String {
+        let full = Self::render_dir(tree, Path::new(""), 0, true);
+        if full.len() <= TREE_BUDGET {
+            return full;
+        }
+
+        let dirs_only = Self::render_dir(tree, Path::new(""), 0, false);
+        context_prefix(&dirs_only, TREE_BUDGET).to_string()
+    }
+
+    fn render_dir(tree: &BTreeMap<PathBuf, DirNode>, dir: &Path, depth: usize, with_files: bool) -> String {
+        let Some(node) = tree.get(dir) else {
+            return String::new();
+        };
+
+        let indent = "  ".repeat(depth);
+        let name = if dir.as_os_str().is_empty() {
+            "./".to_string()
+        } else {
+            format!("{}/", dir.file_name().unwrap_or_default().to_string_lossy())
+        };
+
+        let mut out = format!("{}- {} — {}\n", indent, name, node.summary.as_deref().unwrap_or(""));
+        if with_files || depth == 0 {
+            for (file_name, summary) in &node.files {
+                out.push_str(&format!("{}  - {} — {}\n", indent, file_name, summary));
+            }
+        }
+        for child in &node.children {
+            out.push_str(&Self::render_dir(tree, child, depth + 1, with_files));
+        }
+        out
+    }
+
+    /// Pack the highest-priority raw files into the remaining budget
+    fn pack_raw_files(files: &[ScannedFile]) -> String {
+        let mut packed = String::new();
+
+        for file in files {
+            let chunk = file.to_chunk();
+            let remaining = RAW_FILES_BUDGET.saturating_sub(packed.len());
+            if remaining == 0 {
+                break;
+            }
+
+            if chunk.len() <= remaining {
+                packed.push_str(&chunk);
+                packed.push_str("\n\n");
+            } else if packed.is_empty() {
+                // Always include at least the top file, even if it has to be cut
+                packed.push_str(context_prefix(&chunk, remaining));
+                packed.push_str("...[truncated]");
+            }
+        }
+
+        packed
+    }
+}
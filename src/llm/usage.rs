@@ -0,0 +1,251 @@
+use crate::llm::rate_limit::estimate_tokens;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Price of a model in US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (self.input * input_tokens as f64 + self.output * output_tokens as f64) / 1_000_000.0
+    }
+}
+
+/// Model prices, looked up by the longest matching prefix of the model name
+///
+/// The built-in prices are list prices for standard (non-batch) usage and
+/// drift over time; [`PriceTable::set`] overrides them. Ollama models run
+/// locally and are always free.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    prices: BTreeMap<String, ModelPrice>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let prices = [
+            ("gemini-2.5-pro", 1.25, 10.0),
+            ("gemini-2.5-flash", 0.30, 2.50),
+            ("gemini-2.5-flash-lite", 0.10, 0.40),
+            ("gemini-2.0-flash", 0.10, 0.40),
+            ("gemini-2.0-flash-lite", 0.075, 0.30),
+            ("gpt-4o", 2.50, 10.0),
+            ("gpt-4o-mini", 0.15, 0.60),
+            ("gpt-4.1", 2.0, 8.0),
+            ("gpt-4.1-mini", 0.40, 1.60),
+            ("gpt-4.1-nano", 0.10, 0.40),
+            ("claude-opus-4", 15.0, 75.0),
+            ("claude-sonnet-4", 3.0, 15.0),
+            ("claude-haiku-4-5", 1.0, 5.0),
+        ];
+        Self {
+            prices: prices.into_iter()
+                .map(|(model, input, output)| (model.to_string(), ModelPrice { input, output }))
+                .collect(),
+        }
+    }
+}
+
+impl PriceTable {
+    /// Price `model` and every model whose name starts with it
+    pub fn set(&mut self, model: impl Into<String>, price: ModelPrice) {
+        self.prices.insert(model.into(), price);
+    }
+
+    /// The price of a call to `model` through `provider`, if known
+    pub fn price(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        if provider == "ollama" {
+            return Some(ModelPrice { input: 0.0, output: 0.0 });
+        }
+        self.prices.iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)
+    }
+}
+
+/// Limits on what a run may spend; unset limits are not enforced
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    /// Input and output tokens across every call in the run
+    pub max_tokens: Option<u64>,
+    /// Estimated cost in US dollars across every call in the run
+    pub max_cost: Option<f64>,
+}
+
+/// Tokens used by one model over a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// `None` when the model has no known price
+    pub cost_usd: Option<f64>,
+}
+
+/// Tokens used and their estimated cost over a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageSummary {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// `None` when some model used has no known price
+    pub cost_usd: Option<f64>,
+    pub models: Vec<ModelUsage>,
+}
+
+/// A call was stopped before it was sent because it could take the run over its budget
+#[derive(Debug)]
+pub struct BudgetExceeded {
+    message: String,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Adds up the tokens a run uses and holds calls to its [`Budget`]
+///
+/// Before each call the most it could use (the estimated prompt plus the
+/// output token limit) is set aside, so calls running at the same time cannot
+/// overshoot the budget together. Calls whose provider reports no usage are
+/// not counted once they finish.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    prices: PriceTable,
+    budget: Budget,
+    state: Mutex<UsageState>,
+}
+
+#[derive(Debug, Default)]
+struct UsageState {
+    models: BTreeMap<(String, String), ModelUsage>,
+    /// Tokens and cost set aside for calls in flight
+    reserved_tokens: u64,
+    reserved_cost: f64,
+}
+
+impl UsageState {
+    fn used_tokens(&self) -> u64 {
+        self.models.values().map(|usage| usage.input_tokens + usage.output_tokens).sum()
+    }
+
+    /// Known cost so far; models without a price count as free
+    fn used_cost(&self) -> f64 {
+        self.models.values().filter_map(|usage| usage.cost_usd).sum()
+    }
+}
+
+/// The share of the budget set aside for one call, given back when the call ends
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    tracker: &'a UsageTracker,
+    tokens: u64,
+    cost: f64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.tracker.lock();
+        state.reserved_tokens -= self.tokens;
+        state.reserved_cost -= self.cost;
+    }
+}
+
+impl UsageTracker {
+    pub fn new(prices: PriceTable, budget: Budget) -> Self {
+        Self { prices, budget, state: Mutex::default() }
+    }
+
+    /// Set aside the most a call could use, or refuse it if that could break the budget
+    pub fn reserve(
+        &self,
+        provider: &str,
+        model: &str,
+        prompt_chars: usize,
+        max_output_tokens: u32,
+    ) -> Result<Reservation<'_>, BudgetExceeded> {
+        let tokens = estimate_tokens(prompt_chars) + max_output_tokens as u64;
+        let price = self.prices.price(provider, model);
+        let cost = price.map(|price| price.cost(estimate_tokens(prompt_chars), max_output_tokens as u64));
+
+        let mut state = self.lock();
+        if let Some(max_tokens) = self.budget.max_tokens {
+            let projected = state.used_tokens() + state.reserved_tokens + tokens;
+            if projected > max_tokens {
+                return Err(BudgetExceeded {
+                    message: format!(
+                        "Stopping before a {} call that could bring the run to {} tokens, over the budget of {}",
+                        model, projected, max_tokens
+                    ),
+                });
+            }
+        }
+        if let Some(max_cost) = self.budget.max_cost {
+            let Some(cost) = cost else {
+                return Err(BudgetExceeded {
+                    message: format!("No price is known for {}, so it cannot be held to the cost budget; set one with --price", model),
+                });
+            };
+            let projected = state.used_cost() + state.reserved_cost + cost;
+            if projected > max_cost {
+                return Err(BudgetExceeded {
+                    message: format!(
+                        "Stopping before a {} call that could bring the run to ${:.4}, over the budget of ${:.4}",
+                        model, projected, max_cost
+                    ),
+                });
+            }
+        }
+
+        let cost = cost.unwrap_or(0.0);
+        state.reserved_tokens += tokens;
+        state.reserved_cost += cost;
+        Ok(Reservation { tracker: self, tokens, cost })
+    }
+
+    /// Count the tokens a finished call used
+    pub fn record(&self, provider: &str, model: &str, input_tokens: u64, output_tokens: u64) {
+        let price = self.prices.price(provider, model);
+        let mut state = self.lock();
+        let usage = state.models.entry((provider.to_string(), model.to_string())).or_insert_with(|| ModelUsage {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            cost_usd: price.map(|_| 0.0),
+            ..ModelUsage::default()
+        });
+        usage.calls += 1;
+        usage.input_tokens += input_tokens;
+        usage.output_tokens += output_tokens;
+        usage.cost_usd = price.map(|price| price.cost(usage.input_tokens, usage.output_tokens));
+    }
+
+    /// Totals so far, per model and for the whole run
+    pub fn summary(&self) -> UsageSummary {
+        let state = self.lock();
+        let models: Vec<ModelUsage> = state.models.values().cloned().collect();
+        UsageSummary {
+            calls: models.iter().map(|usage| usage.calls).sum(),
+            input_tokens: models.iter().map(|usage| usage.input_tokens).sum(),
+            output_tokens: models.iter().map(|usage| usage.output_tokens).sum(),
+            cost_usd: models.iter().map(|usage| usage.cost_usd).sum(),
+            models,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, UsageState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
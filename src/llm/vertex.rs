@@ -0,0 +1,284 @@
+use crate::error::Error;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Region used when neither `--location` nor `GOOGLE_CLOUD_LOCATION` is set
+pub const DEFAULT_LOCATION: &str = "us-central1";
+
+const DEFAULT_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Lifetime requested for the signed assertion; Google caps it at one hour
+const ASSERTION_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Tokens this close to expiry are refreshed before use
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The fields of a service-account JSON key that are needed to mint tokens
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub token_uri: Option<String>,
+}
+
+impl fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The private key is left out on purpose
+        f.debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("project_id", &self.project_id)
+            .field("token_uri", &self.token_uri)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    token: String,
+    expires_at: Instant,
+}
+
+/// Mints OAuth access tokens from a service-account key and caches them until shortly before they expire
+#[derive(Debug)]
+pub struct ServiceAccountAuth {
+    key: ServiceAccountKey,
+    token_url: String,
+    cached: Mutex<Option<AccessToken>>,
+}
+
+impl ServiceAccountAuth {
+    /// Load a service-account JSON key file
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| Error::config(format!("Could not read service-account key {}: {}", path.display(), e)))?;
+        Self::from_json(&json)
+            .map_err(|e| Error::config(format!("Invalid service-account key {}: {}", path.display(), e)).into())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let key: ServiceAccountKey = serde_json::from_str(json)?;
+        // Fail on a malformed key now rather than on the first request
+        EncodingKey::from_rsa_pem(key.private_key.as_bytes())?;
+
+        let token_url = key.token_uri.clone().unwrap_or_else(|| DEFAULT_TOKEN_URL.to_string());
+        Ok(Self { key, token_url, cached: Mutex::new(None) })
+    }
+
+    /// Exchange assertions at this endpoint instead of the key's `token_uri`
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = token_url.into();
+        self
+    }
+
+    pub fn project_id(&self) -> Option<&str> {
+        self.key.project_id.as_deref()
+    }
+
+    pub fn client_email(&self) -> &str {
+        &self.key.client_email
+    }
+
+    /// A valid access token, exchanging a new assertion if the cached one is missing or about to expire
+    ///
+    /// The lock is held during the exchange so concurrent callers wait for one
+    /// token instead of each requesting their own.
+    pub async fn access_token(&self, client: &reqwest::Client) -> Result<String, Box<dyn std::error::Error>> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = cached.as_ref()
+            && token.expires_at > Instant::now() + REFRESH_MARGIN
+        {
+            return Ok(token.token.clone());
+        }
+
+        let token = self.exchange(client).await?;
+        *cached = Some(token.clone());
+        Ok(token.token)
+    }
+
+    /// Sign a JWT assertion and trade it for an access token
+    async fn exchange(&self, client: &reqwest::Client) -> Result<AccessToken, Box<dyn std::error::Error>> {
+        let requested_at = Instant::now();
+        let response = client
+            .post(&self.token_url)
+            .form(&[("grant_type", JWT_BEARER_GRANT), ("assertion", &self.signed_assertion()?)])
+            .send()
+            .await
+            .map_err(|e| Error::network(format!("the token endpoint at {}", self.token_url), e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            return Err(Error::AuthRejected {
+                api: "Google OAuth".to_string(),
+                status,
+                message: format!("Token exchange for {} failed: {}", self.key.client_email, error_text),
+            }.into());
+        }
+
+        let response_json: serde_json::Value = response.json().await?;
+        let token = response_json["access_token"].as_str()
+            .ok_or_else(|| Error::invalid_response("The token endpoint", "the reply has no access token"))?;
+        let expires_in = response_json["expires_in"].as_u64().unwrap_or(ASSERTION_LIFETIME.as_secs());
+
+        Ok(AccessToken {
+            token: token.to_string(),
+            expires_at: requested_at + Duration::from_secs(expires_in),
+        })
+    }
+
+    fn signed_assertion(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = Claims {
+            iss: &self.key.client_email,
+            scope: CLOUD_PLATFORM_SCOPE,
+            aud: &self.token_url,
+            iat: now,
+            exp: now + ASSERTION_LIFETIME.as_secs(),
+        };
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = self.key.private_key_id.clone();
+        let key = EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())?;
+        Ok(encode(&header, &claims, &key)?)
+    }
+}
+
+/// Where and as whom Vertex AI requests are sent
+///
+/// The key file is only read by [`VertexEndpoint::load`], so a misconfigured
+/// endpoint can be constructed and reports its problem when the provider is
+/// prepared.
+#[derive(Debug, Clone)]
+pub struct VertexEndpoint {
+    credentials: Option<PathBuf>,
+    project: Option<String>,
+    location: String,
+    base_url: Option<String>,
+    token_url: Option<String>,
+    auth: Option<Arc<ServiceAccountAuth>>,
+}
+
+impl VertexEndpoint {
+    /// Describe a Vertex AI endpoint
+    ///
+    /// Unset values fall back to `GOOGLE_APPLICATION_CREDENTIALS`,
+    /// `GOOGLE_CLOUD_PROJECT` (or the key's own project) and
+    /// `GOOGLE_CLOUD_LOCATION` (or `us-central1`).
+    pub fn new(credentials: Option<PathBuf>, project: Option<String>, location: Option<String>) -> Self {
+        let from_env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Self {
+            credentials: credentials.or_else(|| from_env("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from)),
+            project: project.or_else(|| from_env("GOOGLE_CLOUD_PROJECT")),
+            location: location
+                .or_else(|| from_env("GOOGLE_CLOUD_LOCATION"))
+                .unwrap_or_else(|| DEFAULT_LOCATION.to_string()),
+            base_url: None,
+            token_url: None,
+            auth: None,
+        }
+    }
+
+    /// Send requests to this endpoint instead of the regional `aiplatform.googleapis.com` host
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Exchange token assertions at this endpoint instead of the key's `token_uri`
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Read the key file and settle the project
+    pub fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.auth.is_none() {
+            let path = self.credentials.as_ref()
+                .ok_or(Error::MissingApiKey {
+                    provider: "Vertex AI".to_string(),
+                    credential: "service-account key",
+                    env_var: "GOOGLE_APPLICATION_CREDENTIALS",
+                    flag: "--credentials",
+                })?;
+            let mut auth = ServiceAccountAuth::from_file(path)?;
+            if let Some(token_url) = &self.token_url {
+                auth = auth.with_token_url(token_url.clone());
+            }
+            self.auth = Some(Arc::new(auth));
+        }
+
+        if self.project.is_none() {
+            self.project = self.auth.as_ref().and_then(|auth| auth.project_id()).map(str::to_string);
+        }
+        if self.project.is_none() {
+            return Err(Error::config("No Google Cloud project set for Vertex AI. Use --project or set GOOGLE_CLOUD_PROJECT").into());
+        }
+
+        Ok(())
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    pub fn project(&self) -> Option<&str> {
+        self.project.as_deref()
+    }
+
+    fn host(&self) -> String {
+        match &self.base_url {
+            Some(base_url) => base_url.clone(),
+            // The global endpoint has no regional prefix
+            None if self.location == "global" => "https://aiplatform.googleapis.com".to_string(),
+            None => format!("https://{}-aiplatform.googleapis.com", self.location),
+        }
+    }
+
+    /// URL of this project and region, which model URLs are relative to
+    pub fn location_url(&self) -> String {
+        format!(
+            "{}/v1/projects/{}/locations/{}",
+            self.host(),
+            self.project.as_deref().unwrap_or_default(),
+            self.location
+        )
+    }
+
+    /// URL of a model method (`generateContent`, `streamGenerateContent`) in this project and region
+    pub fn model_url(&self, model: &str, method: &str) -> String {
+        format!("{}/publishers/google/models/{}:{}", self.location_url(), model, method)
+    }
+
+    /// URL listing Google's publisher models in Model Garden
+    pub fn publisher_models_url(&self) -> String {
+        format!("{}/v1beta1/publishers/google/models", self.host())
+    }
+
+    /// A valid access token for the loaded credentials
+    pub async fn access_token(&self, client: &reqwest::Client) -> Result<String, Box<dyn std::error::Error>> {
+        let auth = self.auth.as_ref().ok_or_else(|| Error::config("Vertex AI credentials have not been loaded"))?;
+        auth.access_token(client).await
+    }
+}
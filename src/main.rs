@@ -1,26 +1,150 @@
-mod rag;
-mod util;
-mod llm;
-mod cli;
-
-
-
 use clap::Parser;
 use dotenvy::dotenv;
-use cli::{Cli, Commands};
-
-use crate::util::readme;
+use std::process::ExitCode;
+use std::sync::Arc;
+use tldrs::cli::{Cli, Commands, EventFormat, ProviderArgs};
+use tldrs::llm::ProviderRegistry;
+use tldrs::util::analysis::analyze_project;
+use tldrs::util::readme::{api_key_for, default_output_path};
+use tldrs::util::report::auto_reporter;
+use tldrs::{Error, Event, JsonReporter, PlainReporter, QuietReporter, ReadmeBuilder, Reporter, SharedReporter};
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     dotenv().ok();
     let cli = Cli::parse();
 
+    // Progress goes to stderr so stdout stays clean for piping
+    let reporter: SharedReporter = match (cli.quiet, cli.events) {
+        (true, _) => Arc::new(QuietReporter),
+        (false, Some(EventFormat::Json)) => Arc::new(JsonReporter),
+        (false, None) => auto_reporter(),
+    };
+    // Failures are reported even with --quiet
+    let failures: SharedReporter = if cli.quiet { Arc::new(PlainReporter) } else { reporter.clone() };
+
     match cli.command {
-        Commands::Readme { path, provider, api_key, streaming, prompt, prompt_file, instructions } => {
-            if let Err(err) = readme::generate(path, provider, api_key, streaming, prompt, prompt_file, instructions).await{
-                eprintln!("❌ Error generating README: {}", err);
+        Commands::Readme { path, provider, generation, fallbacks, failover_on, cache, budget, streaming, concurrency, prompt, prompt_file, instructions, summarize, structure_section } => {
+            let (retry, rate_limits, fixtures) = (provider.retry_policy(), provider.rate_limits(), provider.fixtures());
+            let ProviderArgs { provider, api_key, model, base_url, headers, deployment, api_version, credentials, project, location, mock_replies, .. } = provider;
+            let settings = generation.settings();
+            let mut builder = ReadmeBuilder::new(&path)
+                .provider(provider)
+                .main_config(settings.main)
+                .section_config(settings.section)
+                .max_continuations(generation.max_continuations)
+                .retry_policy(retry)
+                .rate_limits(rate_limits)
+                .failover_on(&failover_on)
+                .cache(cache.mode())
+                .cache_ttl(cache.ttl())
+                .cache_max_bytes(cache.max_bytes())
+                .budget(budget.budget())
+                .streaming(streaming)
+                .concurrency(concurrency)
+                .summarize(summarize)
+                .structure_section(structure_section)
+                .output(default_output_path(&path))
+                .reporter(reporter);
+
+            if let Some(api_key) = api_key {
+                builder = builder.api_key(api_key);
+            }
+            if let Some(model) = model {
+                builder = builder.model(model);
+            }
+            if let Some(base_url) = base_url {
+                builder = builder.base_url(base_url);
+            }
+            if let Some(deployment) = deployment {
+                builder = builder.deployment(deployment);
+            }
+            if let Some(api_version) = api_version {
+                builder = builder.api_version(api_version);
+            }
+            if let Some(credentials) = credentials {
+                builder = builder.credentials(credentials);
+            }
+            if let Some(project) = project {
+                builder = builder.project(project);
+            }
+            if let Some(location) = location {
+                builder = builder.location(location);
+            }
+            if let Some(fixtures) = fixtures {
+                builder = builder.fixtures(fixtures);
+            }
+            if let Some(mock_replies) = mock_replies {
+                builder = builder.mock_replies(mock_replies);
+            }
+            for (model, price) in budget.prices {
+                builder = builder.price(model, price);
+            }
+            for fallback in fallbacks {
+                builder = builder.fallback(fallback);
+            }
+            for (name, value) in headers {
+                builder = builder.header(name, value);
+            }
+            if let Some(prompt) = prompt {
+                builder = builder.prompt(prompt);
+            }
+            if let Some(prompt_file) = prompt_file {
+                builder = builder.prompt_file(prompt_file);
+            }
+            if let Some(instructions) = instructions {
+                builder = builder.instructions(instructions);
+            }
+
+            if let Err(err) = builder.generate().await {
+                return fail(failures.as_ref(), "generating README", err);
+            }
+        }
+        Commands::Models { provider } => {
+            let registry = ProviderRegistry::default();
+            let name = provider.provider.name();
+            let api_key = match api_key_for(&registry, &name, provider.api_key.clone(), &provider.options()) {
+                Ok(api_key) => api_key,
+                Err(err) => return fail(failures.as_ref(), "listing models", err),
             };
+
+            let mut generator = match registry.build(&name, api_key, provider.options(), reporter) {
+                Ok(generator) => generator,
+                Err(err) => return fail(failures.as_ref(), "listing models", err),
+            };
+            match generator.list_models().await {
+                Ok(models) => {
+                    for model in models {
+                        println!("{}", model);
+                    }
+                }
+                Err(err) => return fail(failures.as_ref(), "listing models", err.into()),
+            }
+        }
+        Commands::Scan { path, compact } => {
+            let analysis = match analyze_project(&path, reporter.as_ref()) {
+                Ok(analysis) => analysis,
+                Err(err) => return fail(failures.as_ref(), "scanning project", err.into()),
+            };
+
+            let json = if compact {
+                serde_json::to_string(&analysis)
+            } else {
+                serde_json::to_string_pretty(&analysis)
+            };
+            match json {
+                Ok(json) => println!("{}", json),
+                Err(err) => return fail(failures.as_ref(), "serializing scan", Error::Other(err.to_string())),
+            }
         }
     }
+
+    ExitCode::SUCCESS
+}
+
+/// Report a failed command and exit with the status for its kind of error
+fn fail(reporter: &dyn Reporter, action: &str, err: Error) -> ExitCode {
+    let exit_code = err.exit_code();
+    reporter.report(&Event::Failed { action: action.to_string(), error: err.to_string(), exit_code });
+    ExitCode::from(exit_code)
 }
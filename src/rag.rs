@@ -1,103 +1,406 @@
+use crate::error::Error;
+use crate::util::archive::{self, ArchiveKind};
 use crate::util::ext::CODE_EXTENSIONS;
-use crate::util::filters::{should_skip_directory, should_skip_file, get_file_priority, IMPORTANT_FILES};
-use std::path::Path;
+use crate::util::filters::{entry_skip_reason, file_skip_reason, should_skip_directory, should_skip_path_components, get_file_priority, SkipReason, IMPORTANT_FILES};
+use crate::util::lang::detect_language;
+use crate::util::manifest::{content_hash, ManifestEntry, ScanManifest};
+use crate::util::notebook::extract_notebook;
+use crate::util::outline::extract_outline;
+use crate::util::report::{Event, Reporter};
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use walkdir::WalkDir;
-use indicatif::{ProgressBar, ProgressStyle};
 
-pub fn collect_code_chunks(path: &Path) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut file_data = Vec::new();
+/// Called for every path left out of the scan, with whether it is a directory, its size and why
+type OnSkip<'a> = dyn FnMut(&Path, bool, Option<u64>, SkipReason) + 'a;
+
+/// A file selected for the context, with its contents if already in memory
+struct Candidate {
+    path: PathBuf,
+    priority: u32,
+    size: u64,
+    modified: Option<u64>,
+    content: Option<String>,
+}
+
+/// The text of a scanned file
+///
+/// Files unchanged since the previous scan are not read during the scan; their
+/// text is read from disk the first time it is needed. It dereferences to
+/// `str`, so it can be used like a string.
+#[derive(Debug, Clone, Default)]
+pub struct FileContent {
+    /// Where to read the text from, if it was not read during the scan
+    path: Option<PathBuf>,
+    text: OnceLock<String>,
+}
+
+impl FileContent {
+    /// Text that is read on first use; a file that has become unreadable reads as empty
+    pub fn deferred(path: impl Into<PathBuf>) -> Self {
+        Self { path: Some(path.into()), text: OnceLock::new() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.text.get_or_init(|| self.path.as_deref().and_then(|path| read_content(path).ok()).unwrap_or_default())
+    }
+}
+
+impl From<String> for FileContent {
+    fn from(text: String) -> Self {
+        Self { path: None, text: OnceLock::from(text) }
+    }
+}
+
+impl Deref for FileContent {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for FileContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+/// A scanned file with its contents, ready to be packed into a prompt
+#[derive(Debug, Clone)]
+pub struct ScannedFile {
+    /// Path as discovered (prefixed with the scan root for directories)
+    pub path: PathBuf,
+    /// Path relative to the scan root or archive root
+    pub relative_path: PathBuf,
+    pub content: FileContent,
+    pub priority: u32,
+    pub size: u64,
+    /// Modification time in nanoseconds since the epoch, when known
+    pub modified: Option<u64>,
+    pub hash: String,
+    pub language: String,
+    pub outline: Vec<String>,
+}
+
+impl ScannedFile {
+    /// Render the file as a `path:\ncontent` chunk
+    pub fn to_chunk(&self) -> String {
+        format!("{}:\n{}", self.path.display(), self.content)
+    }
 
-    // Create a spinner for the discovery phase
-    let discovery_pb = ProgressBar::new_spinner();
-    discovery_pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap()
-    );
-    discovery_pb.set_message("🔍 Discovering files...");
-    discovery_pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    /// Manifest entry describing this file
+    pub fn manifest_entry(&self) -> ManifestEntry {
+        ManifestEntry {
+            hash: self.hash.clone(),
+            size: self.size,
+            modified: self.modified,
+            language: self.language.clone(),
+            priority: self.priority,
+            outline: self.outline.clone(),
+            summary: None,
+        }
+    }
+}
+
+/// A discovered file or directory that was left out of the scan
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    /// Path as discovered (prefixed with the scan root for directories)
+    pub path: PathBuf,
+    /// Path relative to the scan root or archive root
+    pub relative_path: PathBuf,
+    /// Whether a whole directory was pruned
+    pub directory: bool,
+    pub size: Option<u64>,
+    pub reason: SkipReason,
+}
+
+/// Everything a scan found, whether it made it into the context or not
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// Included files in priority order
+    pub files: Vec<ScannedFile>,
+    pub skipped: Vec<SkippedFile>,
+    /// Number of files seen before filtering (pruned directories are not walked)
+    pub discovered: usize,
+}
+
+/// Collect relevant files from a directory or archive in priority order
+///
+/// Files whose size and modification time match the previous manifest are not
+/// read: their hash, language and outline come from the manifest and their
+/// content is only read if it ends up being used.
+pub fn collect_files(
+    path: &Path,
+    previous: &ScanManifest,
+    reporter: &dyn Reporter,
+) -> Result<Vec<ScannedFile>, Box<dyn std::error::Error>> {
+    Ok(scan(path, previous, reporter)?.files)
+}
+
+/// Scan a directory or archive, keeping track of everything that was skipped and why
+pub fn scan(
+    path: &Path,
+    previous: &ScanManifest,
+    reporter: &dyn Reporter,
+) -> Result<ScanResult, Box<dyn std::error::Error>> {
+    reporter.report(&Event::ScanStarted { path: path.display().to_string() });
+
+    let mut skipped = Vec::new();
+    let mut on_skip = |skipped_path: &Path, directory: bool, size: Option<u64>, reason: SkipReason| {
+        reporter.report(&Event::FileSkipped { path: skipped_path.display().to_string(), reason });
+        let relative_path = skipped_path.strip_prefix(path).unwrap_or(skipped_path).to_path_buf();
+        skipped.push(SkippedFile { path: skipped_path.to_path_buf(), relative_path, directory, size, reason });
+    };
 
     // First pass: collect files with filtering
+    let (mut file_data, discovered_count) = match ArchiveKind::detect(path) {
+        Some(kind) if path.is_file() => discover_archive_files(path, kind, reporter, &mut on_skip)
+            .map_err(|e| Error::io(path, e))?,
+        _ => discover_files(path, reporter, &mut on_skip),
+    };
+
+    reporter.report(&Event::ScanFinished { included: file_data.len(), discovered: discovered_count });
+
+    // Sort files by priority (high to low)
+    file_data.sort_by_key(|candidate| std::cmp::Reverse(candidate.priority));
+
+    let total_files = file_data.len();
+    let mut files = Vec::new();
+
+    // Second pass: read file contents
+    for (index, candidate) in file_data.into_iter().enumerate() {
+        reporter.report(&Event::FileRead {
+            path: candidate.path.display().to_string(),
+            index: index + 1,
+            total: total_files,
+        });
+
+        let relative_path = candidate.path.strip_prefix(path)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| candidate.path.clone());
+
+        let cached = previous.get(&relative_path);
+        let unchanged_on_disk = cached.filter(|entry| {
+            candidate.modified.is_some() && entry.modified == candidate.modified && entry.size == candidate.size
+        });
+        if let Some(entry) = unchanged_on_disk {
+            files.push(ScannedFile {
+                content: FileContent::deferred(&candidate.path),
+                path: candidate.path,
+                relative_path,
+                priority: candidate.priority,
+                size: candidate.size,
+                modified: candidate.modified,
+                hash: entry.hash.clone(),
+                language: entry.language.clone(),
+                outline: entry.outline.clone(),
+            });
+            continue;
+        }
+
+        let content = match candidate.content {
+            Some(content) if is_notebook(&candidate.path) => extract_notebook(&content).ok_or(SkipReason::InvalidNotebook),
+            Some(content) => Ok(content),
+            None => read_content(&candidate.path),
+        };
+        let content = match content {
+            Ok(content) => content,
+            Err(reason) => {
+                on_skip(&candidate.path, false, Some(candidate.size), reason);
+                continue;
+            }
+        };
+
+        // Skip empty files or files with only whitespace
+        if content.trim().is_empty() {
+            on_skip(&candidate.path, false, Some(candidate.size), SkipReason::Empty);
+            continue;
+        }
+
+        let hash = content_hash(&content);
+        let language = detect_language(&relative_path).to_string();
+        let outline = match cached.filter(|entry| entry.hash == hash) {
+            Some(entry) => entry.outline.clone(),
+            None => extract_outline(&language, &content),
+        };
+
+        files.push(ScannedFile {
+            path: candidate.path,
+            relative_path,
+            content: content.into(),
+            priority: candidate.priority,
+            size: candidate.size,
+            modified: candidate.modified,
+            hash,
+            language,
+            outline,
+        });
+    }
+
+    reporter.report(&Event::FilesLoaded { count: files.len() });
+    Ok(ScanResult { files, skipped, discovered: discovered_count })
+}
+
+/// Walk a directory tree and collect relevant files
+fn discover_files(
+    path: &Path,
+    reporter: &dyn Reporter,
+    on_skip: &mut OnSkip<'_>,
+) -> (Vec<Candidate>, usize) {
+    let mut file_data = Vec::new();
     let mut discovered_count = 0;
-    for entry in WalkDir::new(path)
+    let mut skipped_dirs = Vec::new();
+
+    let entries = WalkDir::new(path)
         .into_iter()
         .filter_entry(|e| {
             // Skip directories that should be filtered out
-            if e.file_type().is_dir() {
+            if e.file_type().is_dir() && e.depth() > 0 {
                 let dir_name = e.file_name().to_string_lossy();
-                !should_skip_directory(&dir_name)
-            } else {
-                true
+                if should_skip_directory(&dir_name) {
+                    skipped_dirs.push(e.path().to_path_buf());
+                    return false;
+                }
             }
+            true
         })
         .filter_map(Result::ok)
         .filter(|e| e.file_type().is_file())
-    {
+        .collect::<Vec<_>>();
+
+    for dir in &skipped_dirs {
+        on_skip(dir, true, None, SkipReason::IgnoredDirectory);
+    }
+
+    for entry in entries {
         discovered_count += 1;
         if discovered_count % 50 == 0 {
-            discovery_pb.set_message(format!("🔍 Discovered {} files...", discovered_count));
+            reporter.report(&Event::DiscoveryProgress { discovered: discovered_count });
         }
 
         let file_path = entry.path();
-        
+        let metadata = entry.metadata().ok();
+        let size = metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0);
+
         // Skip files that should be filtered out
-        if should_skip_file(file_path) {
+        if let Some(reason) = file_skip_reason(file_path) {
+            on_skip(file_path, false, Some(size), reason);
             continue;
         }
 
-        // Check if it's a code file or important file
-        let ext = file_path.extension().and_then(|s| s.to_str());
-        let file_name = file_path.file_name()
-            .and_then(|name| name.to_str())
-            .unwrap_or("");
+        if !is_relevant_file(file_path) {
+            on_skip(file_path, false, Some(size), SkipReason::UnsupportedType);
+            continue;
+        }
 
-        let is_code_file = ext.is_some_and(|extension| CODE_EXTENSIONS.contains(&extension));
-        let is_important_file = IMPORTANT_FILES.iter().any(|&imp| file_name.eq_ignore_ascii_case(imp));
+        let priority = get_file_priority(file_path);
+        let modified = metadata
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .and_then(|elapsed| u64::try_from(elapsed.as_nanos()).ok());
 
-        if is_code_file || is_important_file {
-            let priority = get_file_priority(file_path);
-            file_data.push((file_path.to_path_buf(), priority));
-        }
+        file_data.push(Candidate { path: file_path.to_path_buf(), priority, size, modified, content: None });
     }
 
-    discovery_pb.finish_with_message(format!("✅ Found {} relevant files from {} total", file_data.len(), discovered_count));
+    (file_data, discovered_count)
+}
 
-    // Sort files by priority (high to low)
-    file_data.sort_by(|a, b| b.1.cmp(&a.1));
+/// Stream entries out of an archive and collect relevant files
+///
+/// Archive streams can only be read once, so the contents of selected entries
+/// are kept in memory rather than re-read in the second pass.
+fn discover_archive_files(
+    path: &Path,
+    kind: ArchiveKind,
+    reporter: &dyn Reporter,
+    on_skip: &mut OnSkip<'_>,
+) -> std::io::Result<(Vec<Candidate>, usize)> {
+    let mut file_data = Vec::new();
+    let mut discovered_count = 0;
+    let root = archive::shared_root(path, kind)?;
 
-    let total_files = file_data.len();
-    
-    if total_files == 0 {
-        println!("⚠️  No code files found to process");
-        return chunks;
-    }
+    archive::for_each_entry(path, kind, |entry, reader| {
+        discovered_count += 1;
+        if discovered_count % 50 == 0 {
+            reporter.report(&Event::DiscoveryProgress { discovered: discovered_count });
+        }
 
-    let pb = ProgressBar::new(total_files as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("#>-")
-    );
-    pb.set_message("� Reading file contents...");
+        // A top-level directory shared by every entry stands in for the scan root, which is never filtered
+        let below_root = root.as_deref()
+            .and_then(|root| entry.path.strip_prefix(root).ok())
+            .unwrap_or(&entry.path);
+        if should_skip_path_components(below_root) {
+            on_skip(&entry.path, false, Some(entry.size), SkipReason::IgnoredDirectory);
+            return Ok(());
+        }
 
-    // Second pass: read file contents
-    for (file_path, _priority) in file_data {
-        pb.inc(1);
-        let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
-        pb.set_message(format!("📄 {}", file_name));
-
-        if let Ok(content) = std::fs::read_to_string(&file_path) {
-            // Skip empty files or files with only whitespace
-            if content.trim().is_empty() {
-                continue;
+        // Decide on name and size first so oversized entries are never buffered
+        if let Some(reason) = entry_skip_reason(&entry.path, entry.size, || None) {
+            on_skip(&entry.path, false, Some(entry.size), reason);
+            return Ok(());
+        }
+
+        if !is_relevant_file(&entry.path) {
+            on_skip(&entry.path, false, Some(entry.size), SkipReason::UnsupportedType);
+            return Ok(());
+        }
+
+        let mut bytes = Vec::with_capacity(entry.size as usize);
+        reader.read_to_end(&mut bytes)?;
+
+        let head = bytes[..bytes.len().min(16)].to_vec();
+        if let Some(reason) = entry_skip_reason(&entry.path, entry.size, || Some(head)) {
+            on_skip(&entry.path, false, Some(entry.size), reason);
+            return Ok(());
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(content) => {
+                let priority = get_file_priority(&entry.path);
+                file_data.push(Candidate {
+                    path: entry.path.clone(),
+                    priority,
+                    size: entry.size,
+                    modified: None,
+                    content: Some(content),
+                });
             }
-            
-            chunks.push(format!("{}:\n{}", file_path.display(), content));
+            Err(_) => on_skip(&entry.path, false, Some(entry.size), SkipReason::Unreadable),
         }
+
+        Ok(())
+    })?;
+
+    Ok((file_data, discovered_count))
+}
+
+/// Read a file as text; notebooks are reduced to their cells, as the raw JSON would waste the budget
+fn read_content(file_path: &Path) -> Result<String, SkipReason> {
+    let content = std::fs::read_to_string(file_path).map_err(|_| SkipReason::Unreadable)?;
+    if is_notebook(file_path) {
+        extract_notebook(&content).ok_or(SkipReason::InvalidNotebook)
+    } else {
+        Ok(content)
     }
+}
+
+fn is_notebook(file_path: &Path) -> bool {
+    file_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Check if a file is a code file or an important project file
+fn is_relevant_file(file_path: &Path) -> bool {
+    let ext = file_path.extension().and_then(|s| s.to_str());
+    let file_name = file_path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+
+    let is_code_file = ext.is_some_and(|extension| CODE_EXTENSIONS.contains(&extension));
+    let is_important_file = IMPORTANT_FILES.iter().any(|&imp| file_name.eq_ignore_ascii_case(imp));
 
-    pb.finish_with_message(format!("✅ Processed {} code files successfully", chunks.len()));
-    chunks
+    is_code_file || is_important_file
 }
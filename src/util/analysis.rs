@@ -0,0 +1,161 @@
+use crate::llm::streaming::DEFAULT_CONTEXT_BUDGET;
+use crate::rag::{scan, ScanResult};
+use crate::util::filters::{get_file_priority, SkipReason};
+use crate::util::lang::detect_language;
+use crate::util::manifest::{manifest_key, ScanManifest};
+use crate::util::readme::{cache_dir, pack_context};
+use crate::util::report::Reporter;
+use crate::util::tree::{root_name, ProjectTree};
+use serde::Serialize;
+use std::path::Path;
+
+/// Build and dependency manifests, with the ecosystem they belong to
+const MANIFEST_FILES: [(&str, &str); 22] = [
+    ("Cargo.toml", "cargo"),
+    ("package.json", "npm"),
+    ("deno.json", "deno"),
+    ("pyproject.toml", "python"),
+    ("setup.py", "python"),
+    ("setup.cfg", "python"),
+    ("requirements.txt", "python"),
+    ("Pipfile", "python"),
+    ("environment.yml", "conda"),
+    ("go.mod", "go"),
+    ("pom.xml", "maven"),
+    ("build.gradle", "gradle"),
+    ("build.gradle.kts", "gradle"),
+    ("composer.json", "composer"),
+    ("Gemfile", "bundler"),
+    ("mix.exs", "mix"),
+    ("Package.swift", "swift"),
+    ("pubspec.yaml", "pub"),
+    ("CMakeLists.txt", "cmake"),
+    ("Makefile", "make"),
+    ("Dockerfile", "docker"),
+    ("docker-compose.yml", "docker"),
+];
+
+/// File names that conventionally start a program or define a library root
+const ENTRY_POINT_NAMES: [&str; 22] = [
+    "main.rs", "lib.rs", "build.rs",
+    "main.py", "__main__.py", "app.py", "manage.py", "wsgi.py",
+    "main.go",
+    "index.js", "index.mjs", "index.ts", "main.js", "main.ts", "server.js",
+    "main.c", "main.cpp",
+    "Main.java", "Main.kt", "Program.cs", "main.swift", "index.php",
+];
+
+/// Everything tldrs would send to a model, without calling one
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanAnalysis {
+    /// Scan target as given
+    pub root: String,
+    /// Project name shown at the top of the tree
+    pub name: String,
+    pub discovered: usize,
+    pub included: usize,
+    pub skipped: usize,
+    /// Every discovered file in priority order, followed by everything that was skipped
+    pub files: Vec<FileAnalysis>,
+    pub manifests: Vec<DetectedManifest>,
+    pub entry_points: Vec<String>,
+    /// The packed context the README prompt would be built from
+    pub context: String,
+}
+
+/// One discovered file or pruned directory
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAnalysis {
+    /// Relative path, with a trailing `/` for pruned directories
+    pub path: String,
+    pub language: Option<String>,
+    pub priority: Option<u32>,
+    pub size: Option<u64>,
+    pub included: bool,
+    pub skip_reason: Option<SkipReason>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedManifest {
+    pub path: String,
+    pub ecosystem: String,
+}
+
+/// Scan a project or archive and describe what a README run would see
+///
+/// The scan cache is only read, never written, so this is safe to run
+/// alongside README generation.
+pub fn analyze_project(path: &Path, reporter: &dyn Reporter) -> Result<ScanAnalysis, Box<dyn std::error::Error>> {
+    let previous = ScanManifest::load(&cache_dir(path));
+    let result = scan(path, &previous, reporter)?;
+
+    let name = root_name(path);
+    let tree = ProjectTree::build(&name, &result.files);
+    let context = pack_context(&tree, &result.files, DEFAULT_CONTEXT_BUDGET);
+
+    let files = file_analyses(&result);
+    let manifests = files.iter()
+        .filter_map(|file| {
+            let file_name = file.path.rsplit('/').next()?;
+            let (_, ecosystem) = MANIFEST_FILES.iter().find(|(manifest, _)| *manifest == file_name)?;
+            Some(DetectedManifest { path: file.path.clone(), ecosystem: ecosystem.to_string() })
+        })
+        .collect();
+    let entry_points = files.iter()
+        .filter(|file| file.included && is_entry_point(&file.path))
+        .map(|file| file.path.clone())
+        .collect();
+
+    Ok(ScanAnalysis {
+        root: path.display().to_string(),
+        name,
+        discovered: result.discovered,
+        included: result.files.len(),
+        skipped: result.skipped.len(),
+        files,
+        manifests,
+        entry_points,
+        context,
+    })
+}
+
+fn file_analyses(result: &ScanResult) -> Vec<FileAnalysis> {
+    let included = result.files.iter().map(|file| FileAnalysis {
+        path: manifest_key(&file.relative_path),
+        language: Some(file.language.clone()),
+        priority: Some(file.priority),
+        size: Some(file.size),
+        included: true,
+        skip_reason: None,
+    });
+
+    let skipped = result.skipped.iter().map(|file| {
+        if file.directory {
+            return FileAnalysis {
+                path: format!("{}/", manifest_key(&file.relative_path)),
+                language: None,
+                priority: None,
+                size: None,
+                included: false,
+                skip_reason: Some(file.reason),
+            };
+        }
+
+        FileAnalysis {
+            path: manifest_key(&file.relative_path),
+            language: Some(detect_language(&file.relative_path).to_string()),
+            priority: Some(get_file_priority(&file.path)),
+            size: file.size,
+            included: false,
+            skip_reason: Some(file.reason),
+        }
+    });
+
+    included.chain(skipped).collect()
+}
+
+fn is_entry_point(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let is_rust_binary = path.contains("src/bin/") && file_name.ends_with(".rs");
+    ENTRY_POINT_NAMES.contains(&file_name) || is_rust_binary
+}
@@ -0,0 +1,157 @@
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Archive formats that can be scanned in place without extracting to disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Gzip-compressed tarballs (`.tar.gz`, `.tgz`, `.crate`, Python sdists)
+    TarGz,
+    /// Uncompressed tarballs (`.tar`)
+    Tar,
+    /// Zip archives (`.zip`, Python wheels)
+    Zip,
+}
+
+/// Recognised archive suffixes, longest first so `.tar.gz` wins over `.gz`
+const ARCHIVE_SUFFIXES: [(&str, ArchiveKind); 6] = [
+    (".tar.gz", ArchiveKind::TarGz),
+    (".tgz", ArchiveKind::TarGz),
+    (".crate", ArchiveKind::TarGz),
+    (".tar", ArchiveKind::Tar),
+    (".zip", ArchiveKind::Zip),
+    (".whl", ArchiveKind::Zip),
+];
+
+impl ArchiveKind {
+    /// Detect the archive kind from a file name
+    pub fn detect(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+        ARCHIVE_SUFFIXES
+            .iter()
+            .find(|(suffix, _)| file_name.ends_with(suffix))
+            .map(|(_, kind)| *kind)
+    }
+
+    /// File name of the archive with its archive suffix removed
+    pub fn stem(path: &Path) -> Option<String> {
+        let file_name = path.file_name()?.to_string_lossy().to_string();
+        let lower = file_name.to_lowercase();
+        ARCHIVE_SUFFIXES
+            .iter()
+            .find(|(suffix, _)| lower.ends_with(suffix))
+            .map(|(suffix, _)| file_name[..file_name.len() - suffix.len()].to_string())
+    }
+}
+
+/// A regular file stored inside an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path of the entry relative to the archive root
+    pub path: PathBuf,
+    /// Uncompressed size in bytes
+    pub size: u64,
+}
+
+/// Stream every regular file in the archive through `visit`
+///
+/// Entries are read sequentially straight from the (decompressed) archive
+/// stream; nothing is written to disk. Entries with absolute paths or `..`
+/// components are ignored.
+pub fn for_each_entry<F>(archive: &Path, kind: ArchiveKind, mut visit: F) -> io::Result<()>
+where
+    F: FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+{
+    let file = BufReader::new(File::open(archive)?);
+
+    match kind {
+        ArchiveKind::TarGz => visit_tar(tar::Archive::new(GzDecoder::new(file)), &mut visit),
+        ArchiveKind::Tar => visit_tar(tar::Archive::new(file), &mut visit),
+        ArchiveKind::Zip => visit_zip(file, &mut visit),
+    }
+}
+
+/// The top-level directory every file in the archive sits in, if there is one
+///
+/// Source tarballs usually wrap the project in one such directory; wheels and
+/// zips made from inside a project do not. Entry contents are not read.
+pub fn shared_root(archive: &Path, kind: ArchiveKind) -> io::Result<Option<PathBuf>> {
+    let mut root: Option<Option<PathBuf>> = None;
+    for_each_entry(archive, kind, |entry, _| {
+        let mut components = entry.path.components();
+        let first = components.next().map(|first| PathBuf::from(first.as_os_str()));
+        // A file directly at the top has no directory to share
+        let candidate = first.filter(|_| components.next().is_some());
+        match &root {
+            Some(shared) if *shared != candidate => root = Some(None),
+            Some(_) => {}
+            None => root = Some(candidate),
+        }
+        Ok(())
+    })?;
+    Ok(root.flatten())
+}
+
+fn visit_tar<R, F>(mut archive: tar::Archive<R>, visit: &mut F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+{
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let Some(path) = sanitize_entry_path(&entry.path()?) else {
+            continue;
+        };
+        let size = entry.header().size()?;
+
+        visit(&ArchiveEntry { path, size }, &mut entry)?;
+    }
+
+    Ok(())
+}
+
+fn visit_zip<F>(reader: BufReader<File>, visit: &mut F) -> io::Result<()>
+where
+    F: FnMut(&ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+{
+    let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        if !entry.is_file() {
+            continue;
+        }
+
+        let Some(path) = entry.enclosed_name().and_then(|path| sanitize_entry_path(&path)) else {
+            continue;
+        };
+        let size = entry.size();
+
+        visit(&ArchiveEntry { path, size }, &mut entry)?;
+    }
+
+    Ok(())
+}
+
+/// Keep only normal path components so entries can never escape the archive root
+fn sanitize_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if clean.as_os_str().is_empty() {
+        None
+    } else {
+        Some(clean)
+    }
+}
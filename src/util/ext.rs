@@ -1,4 +1,4 @@
-pub const CODE_EXTENSIONS: [&str; 175] = [
+pub const CODE_EXTENSIONS: [&str; 176] = [
     "rs",
     "py", "pyx", "pyi", "pyw",
     "js", "jsx", "ts", "tsx", "mjs", "cjs",
@@ -52,4 +52,5 @@ pub const CODE_EXTENSIONS: [&str; 175] = [
     "jenkinsfile", "vagrantfile", "gemfile", "podfile",
     "gitignore", "gitattributes",
     "proto", "graphql", "gql", "sol", "cairo", "move",
+    "ipynb",
 ];
\ No newline at end of file
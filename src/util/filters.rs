@@ -1,5 +1,45 @@
+use serde::Serialize;
+use std::fmt;
 use std::path::Path;
 
+/// Why the scanner left a file out of the context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The directory (or one of its parents) is a build, dependency or hidden directory
+    IgnoredDirectory,
+    /// The file name matches a lock file, artifact or temporary file pattern
+    IgnoredPattern,
+    /// The file is larger than the size limit for its type
+    TooLarge,
+    /// The file looks like a binary
+    Binary,
+    /// The file is neither a known code file nor an important project file
+    UnsupportedType,
+    /// The file is empty or whitespace only
+    Empty,
+    /// The file could not be read as UTF-8 text
+    Unreadable,
+    /// The notebook JSON could not be parsed
+    InvalidNotebook,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::IgnoredDirectory => "ignored directory",
+            Self::IgnoredPattern => "matches an ignored file pattern",
+            Self::TooLarge => "file too large",
+            Self::Binary => "binary file",
+            Self::UnsupportedType => "not a code or project file",
+            Self::Empty => "empty file",
+            Self::Unreadable => "not readable as UTF-8 text",
+            Self::InvalidNotebook => "invalid notebook JSON",
+        };
+        f.write_str(description)
+    }
+}
+
 /// Directories that should be completely skipped during codebase scanning
 /// These typically contain build artifacts, dependencies, or auto-generated files
 pub const SKIP_DIRECTORIES: [&str; 61] = [
@@ -59,6 +99,24 @@ pub const IMPORTANT_FILES: [&str; 20] = [
     "README", "README.md", "README.txt", "LICENSE", "CHANGELOG.md",
 ];
 
+/// Files larger than this are assumed to be binary or generated
+pub const MAX_FILE_SIZE: u64 = 1_000_000;
+
+/// Notebooks are mostly outputs and metadata that get stripped, so they may be much larger
+pub const MAX_NOTEBOOK_SIZE: u64 = 20_000_000;
+
+/// Largest file size that is still worth reading for the given path
+pub fn max_file_size(file_path: &Path) -> u64 {
+    let is_notebook = file_path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"));
+
+    if is_notebook {
+        MAX_NOTEBOOK_SIZE
+    } else {
+        MAX_FILE_SIZE
+    }
+}
+
 /// Check if a directory should be skipped
 pub fn should_skip_directory(dir_name: &str) -> bool {
     let dir_lower = dir_name.to_lowercase();
@@ -91,6 +149,31 @@ pub fn should_skip_directory(dir_name: &str) -> bool {
 
 /// Check if a file should be skipped
 pub fn should_skip_file(file_path: &Path) -> bool {
+    file_skip_reason(file_path).is_some()
+}
+
+/// Why a file on disk should be skipped, if it should
+pub fn file_skip_reason(file_path: &Path) -> Option<SkipReason> {
+    let size = file_path.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    entry_skip_reason(file_path, size, || std::fs::read(file_path).ok())
+}
+
+/// Check if a file should be skipped given its size and a way to read its leading bytes
+///
+/// Used both for files on disk and for entries streamed out of archives, where
+/// metadata and contents are not available through the filesystem.
+pub fn should_skip_entry<F>(file_path: &Path, size: u64, read_head: F) -> bool
+where
+    F: FnOnce() -> Option<Vec<u8>>,
+{
+    entry_skip_reason(file_path, size, read_head).is_some()
+}
+
+/// Why a file should be skipped given its size and a way to read its leading bytes
+pub fn entry_skip_reason<F>(file_path: &Path, size: u64, read_head: F) -> Option<SkipReason>
+where
+    F: FnOnce() -> Option<Vec<u8>>,
+{
     let file_name = file_path.file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("");
@@ -102,7 +185,7 @@ pub fn should_skip_file(file_path: &Path) -> bool {
         file_name_lower == imp.to_lowercase() || 
         file_name.eq_ignore_ascii_case(imp)
     }) {
-        return false;
+        return None;
     }
     
     // Check file patterns
@@ -113,19 +196,17 @@ pub fn should_skip_file(file_path: &Path) -> bool {
             if pattern_parts.len() == 2 {
                 let (prefix, suffix) = (pattern_parts[0], pattern_parts[1]);
                 if file_name_lower.starts_with(prefix) && file_name_lower.ends_with(suffix) {
-                    return true;
+                    return Some(SkipReason::IgnoredPattern);
                 }
             }
         } else if file_name_lower == pattern.to_lowercase() {
-            return true;
+            return Some(SkipReason::IgnoredPattern);
         }
     }
     
     // Skip very large files (likely binary or generated)
-    if let Ok(metadata) = file_path.metadata() {
-        if metadata.len() > 1_000_000 { // 1MB threshold
-            return true;
-        }
+    if size > max_file_size(file_path) {
+        return Some(SkipReason::TooLarge);
     }
     
     // Skip files without extensions that are likely binaries
@@ -139,19 +220,31 @@ pub fn should_skip_file(file_path: &Path) -> bool {
         
         if !is_script {
             // Check if file might be executable/binary by trying to read first few bytes
-            if let Ok(content) = std::fs::read(file_path) {
-                if content.len() > 10 {
-                    // Check for binary file signatures
-                    let first_bytes = &content[..std::cmp::min(8, content.len())];
-                    if first_bytes.contains(&0) { // Contains null bytes, likely binary
-                        return true;
-                    }
+            if let Some(content) = read_head()
+                && content.len() > 10 {
+                // Check for binary file signatures
+                let first_bytes = &content[..std::cmp::min(8, content.len())];
+                if first_bytes.contains(&0) { // Contains null bytes, likely binary
+                    return Some(SkipReason::Binary);
                 }
             }
         }
     }
     
-    false
+    None
+}
+
+/// Check if any directory component of a relative path should be skipped
+pub fn should_skip_path_components(relative_path: &Path) -> bool {
+    relative_path
+        .parent()
+        .map(|parent| {
+            parent.components().any(|component| {
+                let name = component.as_os_str().to_string_lossy();
+                should_skip_directory(&name)
+            })
+        })
+        .unwrap_or(false)
 }
 
 /// Get priority score for a file (higher = more important)
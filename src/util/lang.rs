@@ -0,0 +1,67 @@
+use std::path::Path;
+
+/// Language names keyed by lowercase file extension
+const EXTENSION_LANGUAGES: [(&str, &str); 90] = [
+    ("rs", "Rust"),
+    ("py", "Python"), ("pyx", "Cython"), ("pyi", "Python"), ("pyw", "Python"),
+    ("ipynb", "Jupyter Notebook"),
+    ("js", "JavaScript"), ("jsx", "JavaScript"), ("mjs", "JavaScript"), ("cjs", "JavaScript"),
+    ("ts", "TypeScript"), ("tsx", "TypeScript"),
+    ("java", "Java"), ("kt", "Kotlin"), ("kts", "Kotlin"), ("scala", "Scala"), ("groovy", "Groovy"),
+    ("clj", "Clojure"), ("cljs", "Clojure"),
+    ("c", "C"), ("h", "C"),
+    ("cc", "C++"), ("cpp", "C++"), ("cxx", "C++"), ("hh", "C++"), ("hpp", "C++"), ("hxx", "C++"),
+    ("cs", "C#"), ("go", "Go"), ("php", "PHP"), ("rb", "Ruby"), ("rake", "Ruby"),
+    ("swift", "Swift"), ("m", "Objective-C"), ("mm", "Objective-C++"),
+    ("pl", "Perl"), ("pm", "Perl"),
+    ("sh", "Shell"), ("bash", "Shell"), ("zsh", "Shell"), ("fish", "Shell"),
+    ("ps1", "PowerShell"), ("bat", "Batch"), ("cmd", "Batch"),
+    ("r", "R"), ("lua", "Lua"), ("dart", "Dart"), ("hs", "Haskell"),
+    ("erl", "Erlang"), ("ex", "Elixir"), ("exs", "Elixir"),
+    ("fs", "F#"), ("fsx", "F#"), ("vb", "Visual Basic"),
+    ("asm", "Assembly"), ("s", "Assembly"),
+    ("f90", "Fortran"), ("f95", "Fortran"),
+    ("nim", "Nim"), ("cr", "Crystal"), ("zig", "Zig"), ("jl", "Julia"),
+    ("ml", "OCaml"), ("mli", "OCaml"), ("elm", "Elm"), ("rkt", "Racket"),
+    ("lisp", "Lisp"), ("sql", "SQL"),
+    ("html", "HTML"), ("htm", "HTML"), ("xml", "XML"),
+    ("css", "CSS"), ("scss", "SCSS"), ("sass", "Sass"), ("less", "Less"),
+    ("vue", "Vue"), ("svelte", "Svelte"),
+    ("json", "JSON"), ("yaml", "YAML"), ("yml", "YAML"), ("toml", "TOML"), ("ini", "INI"),
+    ("md", "Markdown"), ("markdown", "Markdown"), ("rst", "reStructuredText"), ("tex", "TeX"),
+    ("txt", "Text"),
+    ("proto", "Protocol Buffers"), ("graphql", "GraphQL"), ("sol", "Solidity"),
+];
+
+/// Language names for well-known files without a telling extension
+const FILE_NAME_LANGUAGES: [(&str, &str); 6] = [
+    ("dockerfile", "Dockerfile"),
+    ("makefile", "Makefile"),
+    ("cmakelists.txt", "CMake"),
+    ("gemfile", "Ruby"),
+    ("rakefile", "Ruby"),
+    ("license", "Text"),
+];
+
+/// Detect the language of a file from its name and extension
+pub fn detect_language(path: &Path) -> &'static str {
+    let file_name = path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    if let Some((_, language)) = FILE_NAME_LANGUAGES.iter().find(|(name, _)| *name == file_name) {
+        return language;
+    }
+
+    let ext = path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    EXTENSION_LANGUAGES
+        .iter()
+        .find(|(candidate, _)| *candidate == ext)
+        .map(|(_, language)| *language)
+        .unwrap_or("Other")
+}
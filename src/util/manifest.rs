@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Bump whenever the manifest layout or the meaning of a field changes
+pub const MANIFEST_VERSION: u32 = 2;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Hex-encoded SHA-256 of some content
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Everything tldrs derived from a file the last time it was scanned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub hash: String,
+    pub size: u64,
+    /// Modification time in nanoseconds since the epoch, when known
+    ///
+    /// Whole seconds would miss an edit that keeps the size within the same second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    pub language: String,
+    pub priority: u32,
+    #[serde(default)]
+    pub outline: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+/// Per-project scan cache stored in `.tldrs/cache/manifest.json`
+///
+/// The manifest is purely a cache: a missing, unreadable or outdated file is
+/// treated as empty, so the whole `.tldrs` directory can be deleted at any time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanManifest {
+    pub version: u32,
+    /// Unix timestamp of the run that wrote this manifest
+    #[serde(default)]
+    pub generated_at: u64,
+    /// Entries keyed by path relative to the scan root
+    #[serde(default)]
+    pub files: BTreeMap<String, ManifestEntry>,
+    /// Directory roll-up summaries keyed by the hash of their inputs
+    #[serde(default)]
+    pub directory_summaries: BTreeMap<String, String>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for ScanManifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            generated_at: 0,
+            files: BTreeMap::new(),
+            directory_summaries: BTreeMap::new(),
+            path: None,
+        }
+    }
+}
+
+/// Difference between two scans of the same project
+#[derive(Debug, Default, Clone)]
+pub struct ChangeSummary {
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+impl ChangeSummary {
+    pub fn changed(&self) -> usize {
+        self.added + self.modified + self.removed
+    }
+}
+
+impl ScanManifest {
+    /// Load the manifest from `cache_dir`, starting empty if it is missing, corrupt or from another version
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(MANIFEST_FILE);
+        let manifest = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<ScanManifest>(&raw).ok())
+            .filter(|manifest| manifest.version == MANIFEST_VERSION)
+            .unwrap_or_default();
+
+        Self { path: Some(path), ..manifest }
+    }
+
+    /// Whether this manifest was loaded from a previous run
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn get(&self, relative_path: &Path) -> Option<&ManifestEntry> {
+        self.files.get(&manifest_key(relative_path))
+    }
+
+    /// Cached summary for a file, if its content has not changed since it was summarized
+    pub fn file_summary(&self, relative_path: &Path, hash: &str) -> Option<&String> {
+        self.get(relative_path)
+            .filter(|entry| entry.hash == hash)
+            .and_then(|entry| entry.summary.as_ref())
+    }
+
+    pub fn set_file_summary(&mut self, relative_path: &Path, summary: String) {
+        if let Some(entry) = self.files.get_mut(&manifest_key(relative_path)) {
+            entry.summary = Some(summary);
+        }
+    }
+
+    pub fn directory_summary(&self, hash: &str) -> Option<&String> {
+        self.directory_summaries.get(hash)
+    }
+
+    pub fn set_directory_summary(&mut self, hash: String, summary: String) {
+        self.directory_summaries.insert(hash, summary);
+    }
+
+    /// Build the manifest for a new scan, carrying over what is still valid
+    pub fn rescan(&self, entries: BTreeMap<String, ManifestEntry>) -> Self {
+        let files = entries
+            .into_iter()
+            .map(|(key, mut entry)| {
+                if entry.summary.is_none() {
+                    entry.summary = self.files.get(&key)
+                        .filter(|previous| previous.hash == entry.hash)
+                        .and_then(|previous| previous.summary.clone());
+                }
+                (key, entry)
+            })
+            .collect();
+
+        Self {
+            version: MANIFEST_VERSION,
+            generated_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            files,
+            directory_summaries: self.directory_summaries.clone(),
+            path: self.path.clone(),
+        }
+    }
+
+    /// Compare this (newer) manifest against a previous one
+    pub fn changes_since(&self, previous: &ScanManifest) -> ChangeSummary {
+        let mut summary = ChangeSummary::default();
+
+        for (key, entry) in &self.files {
+            match previous.files.get(key) {
+                None => summary.added += 1,
+                Some(old) if old.hash != entry.hash => summary.modified += 1,
+                Some(_) => summary.unchanged += 1,
+            }
+        }
+        summary.removed = previous.files.keys().filter(|key| !self.files.contains_key(*key)).count();
+
+        summary
+    }
+
+    /// Persist the manifest to disk
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Manifest keys always use forward slashes so caches are portable
+pub fn manifest_key(relative_path: &Path) -> String {
+    relative_path.to_string_lossy().replace('\\', "/")
+}
@@ -1,3 +1,11 @@
+pub mod analysis;
+pub mod archive;
 pub mod ext;
 pub mod filters;
-pub mod readme;
\ No newline at end of file
+pub mod lang;
+pub mod manifest;
+pub mod notebook;
+pub mod outline;
+pub mod readme;
+pub mod report;
+pub mod tree;
\ No newline at end of file
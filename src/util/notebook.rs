@@ -0,0 +1,64 @@
+use serde_json::Value;
+
+/// Marker that starts every rendered cell, in the spirit of the jupytext percent format
+pub const CELL_MARKER: &str = "# %%";
+
+/// Extract the markdown and code cells of a Jupyter notebook as labelled plain text
+///
+/// Outputs, execution counts and all metadata are dropped, so embedded images
+/// and large result tables never reach the context. Returns `None` if the
+/// content is not a notebook we understand.
+pub fn extract_notebook(raw: &str) -> Option<String> {
+    let notebook: Value = serde_json::from_str(raw).ok()?;
+
+    // nbformat 4 keeps cells at the top level, nbformat 3 nests them in worksheets
+    let cells: Vec<&Value> = match notebook.get("cells").and_then(Value::as_array) {
+        Some(cells) => cells.iter().collect(),
+        None => notebook.get("worksheets")?
+            .as_array()?
+            .iter()
+            .filter_map(|sheet| sheet.get("cells").and_then(Value::as_array))
+            .flatten()
+            .collect(),
+    };
+
+    let language = notebook_language(&notebook);
+    let mut out = format!("Jupyter notebook ({}), {} cells\n", language, cells.len());
+
+    for (index, cell) in cells.iter().enumerate() {
+        let cell_type = cell.get("cell_type").and_then(Value::as_str).unwrap_or("code");
+        // nbformat 3 code cells store their source under `input`
+        let source = cell.get("source").or_else(|| cell.get("input")).map(join_source).unwrap_or_default();
+        if source.trim().is_empty() {
+            continue;
+        }
+
+        let label = match cell_type {
+            "markdown" | "heading" => "markdown",
+            "raw" => "raw",
+            _ => "code",
+        };
+        out.push_str(&format!("\n{} [{}] cell {}\n", CELL_MARKER, label, index + 1));
+        out.push_str(source.trim_end());
+        out.push('\n');
+    }
+
+    Some(out)
+}
+
+/// Cell sources are either a single string or a list of lines
+fn join_source(source: &Value) -> String {
+    match source {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn notebook_language(notebook: &Value) -> String {
+    let metadata = &notebook["metadata"];
+    metadata["language_info"]["name"].as_str()
+        .or_else(|| metadata["kernelspec"]["language"].as_str())
+        .unwrap_or("python")
+        .to_string()
+}
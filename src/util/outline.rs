@@ -0,0 +1,74 @@
+use crate::util::notebook::CELL_MARKER;
+
+/// Maximum number of outline lines kept per file
+const MAX_OUTLINE_LINES: usize = 50;
+
+/// Maximum length of a single outline line
+const MAX_LINE_LENGTH: usize = 120;
+
+/// Line prefixes that introduce a declaration worth listing in an outline
+const DECLARATION_PREFIXES: [&str; 37] = [
+    // Rust
+    "pub fn ", "fn ", "pub async fn ", "async fn ", "pub struct ", "struct ", "pub enum ", "enum ",
+    "pub trait ", "trait ", "impl ", "impl<", "pub mod ", "mod ", "pub type ",
+    // Python
+    "def ", "async def ", "class ",
+    // JavaScript / TypeScript
+    "function ", "async function ", "export ", "interface ", "type ",
+    // Go
+    "func ", "package ",
+    // Java / Kotlin / C#
+    "public class ", "public interface ", "public enum ", "public static ", "public abstract ",
+    "data class ", "object ", "namespace ",
+    // Ruby / Elixir
+    "module ", "defmodule ", "defp ",
+    // C / C++
+    "typedef ",
+];
+
+/// Extract a short outline of top-level declarations from a file
+///
+/// This is deliberately language-agnostic: it keeps unindented lines that
+/// start with a declaration keyword, markdown headings and TOML/INI tables.
+pub fn extract_outline(language: &str, content: &str) -> Vec<String> {
+    let mut outline = Vec::new();
+
+    for line in content.lines() {
+        if outline.len() >= MAX_OUTLINE_LINES {
+            break;
+        }
+
+        // Only consider top-level (or one level indented) declarations
+        let indent = line.len() - line.trim_start().len();
+        if indent > 4 {
+            continue;
+        }
+        let trimmed = line.trim();
+
+        let keep = match language {
+            "Markdown" => trimmed.starts_with('#'),
+            // Markdown headings from text cells plus definitions from code cells
+            "Jupyter Notebook" => {
+                (trimmed.starts_with('#') && !trimmed.starts_with(CELL_MARKER))
+                    || DECLARATION_PREFIXES.iter().any(|prefix| trimmed.starts_with(prefix))
+            }
+            "TOML" | "INI" => trimmed.starts_with('['),
+            _ => DECLARATION_PREFIXES.iter().any(|prefix| trimmed.starts_with(prefix)),
+        };
+
+        if keep {
+            outline.push(clean_outline_line(trimmed));
+        }
+    }
+
+    outline
+}
+
+/// Drop trailing block openers and cap the line length
+fn clean_outline_line(line: &str) -> String {
+    let line = line.trim_end_matches(['{', ':', ' ']);
+    match line.char_indices().nth(MAX_LINE_LENGTH) {
+        Some((index, _)) => format!("{}…", &line[..index]),
+        None => line.to_string(),
+    }
+}
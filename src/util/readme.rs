@@ -1,91 +1,665 @@
-use crate::llm::LlmGenerator;
+use crate::error::Error;
+use crate::llm::cache::{DEFAULT_CACHE_MAX_BYTES, DEFAULT_CACHE_TTL};
+use crate::llm::{Budget, CacheMode, FailoverOn, FallbackChain, GenerationConfig, GenerationSettings, LlmGenerator, ModelPrice, PriceTable};
+use crate::llm::{FixtureMode, Fixtures, ProviderOptions, ProviderRegistry, ProviderSpec, RateLimits, ResponseCache, RetryPolicy, UsageSummary, UsageTracker};
 use crate::llm::prompt::SYSTEM_PROMPT;
+use crate::llm::streaming::{StreamingSectionGenerator, DEFAULT_SECTION_CONCURRENCY};
 use crate::cli::LlmProvider;
-use crate::rag::collect_code_chunks;
-use std::path::PathBuf;
+use crate::rag::{collect_files, ScannedFile};
+use crate::util::archive::ArchiveKind;
+use crate::util::manifest::{manifest_key, ChangeSummary, ScanManifest};
+use crate::util::report::{Event, PromptSource, QuietReporter, SharedReporter};
+use crate::util::tree::{root_name, ProjectTree};
+use std::path::{Path, PathBuf};
 use std::fs;
-use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub async fn generate(
-    path: PathBuf, 
-    provider: LlmProvider, 
+/// How the README body was produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationMode {
+    /// One call that generates the whole README
+    Fast,
+    /// One call per section, written out as each section completes
+    Streaming,
+}
+
+impl fmt::Display for GenerationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fast => write!(f, "fast"),
+            Self::Streaming => write!(f, "streaming"),
+        }
+    }
+}
+
+/// Result of a README generation run
+#[derive(Debug, Clone)]
+pub struct ReadmeOutput {
+    /// The generated README markdown
+    pub content: String,
+    /// Where the README was written, if an output path was set
+    pub output_path: Option<PathBuf>,
+    /// Registry name of the primary provider, e.g. `openai-compatible`
+    pub provider: String,
+    pub mode: GenerationMode,
+    /// Number of files that made it into the scan
+    pub files_scanned: usize,
+    /// What changed since the previous run's scan cache
+    pub changes: ChangeSummary,
+    /// Tokens the run used and their estimated cost
+    pub usage: UsageSummary,
+}
+
+/// Builder for a README generation run
+///
+/// ```no_run
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// use tldrs::{LlmProvider, ReadmeBuilder};
+///
+/// let readme = ReadmeBuilder::new("path/to/project")
+///     .provider(LlmProvider::Gemini)
+///     .instructions("Focus on the public API")
+///     .generate()
+///     .await?;
+/// println!("{}", readme.content);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReadmeBuilder {
+    path: PathBuf,
+    registry: ProviderRegistry,
+    provider: String,
     api_key: Option<String>,
+    options: ProviderOptions,
+    fallbacks: Vec<ProviderSpec>,
+    failover_on: Vec<FailoverOn>,
+    cache: CacheMode,
+    cache_ttl: Duration,
+    cache_max_bytes: u64,
+    budget: Budget,
+    prices: PriceTable,
     streaming: bool,
+    concurrency: usize,
     custom_prompt: Option<String>,
     prompt_file: Option<PathBuf>,
     instructions: Option<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let chunks = collect_code_chunks(&path);
-    let out_path = path.join("README.md");
-    
-    let api_key = match api_key {
-        Some(key) => key,
-        None => {
-            let env_var = match provider {
-                LlmProvider::Gemini => "GEMINI_API_KEY",
-                LlmProvider::OpenAI => "OPENAI_API_KEY",
-            };
-            env::var(env_var).map_err(|_| {
-                format!("API key not provided. Set {} environment variable or use --api-key flag", env_var)
-            })?
+    summarize: bool,
+    structure_section: bool,
+    output_path: Option<PathBuf>,
+    reporter: SharedReporter,
+}
+
+impl fmt::Debug for ReadmeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The API key and reporter are left out on purpose
+        f.debug_struct("ReadmeBuilder")
+            .field("path", &self.path)
+            .field("provider", &self.provider)
+            .field("options", &self.options)
+            .field("fallbacks", &self.fallbacks)
+            .field("failover_on", &self.failover_on)
+            .field("cache", &self.cache)
+            .field("cache_ttl", &self.cache_ttl)
+            .field("cache_max_bytes", &self.cache_max_bytes)
+            .field("budget", &self.budget)
+            .field("streaming", &self.streaming)
+            .field("concurrency", &self.concurrency)
+            .field("summarize", &self.summarize)
+            .field("structure_section", &self.structure_section)
+            .field("output_path", &self.output_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadmeBuilder {
+    /// Start a run for a project directory or source archive
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            registry: ProviderRegistry::default(),
+            provider: LlmProvider::Gemini.name(),
+            api_key: None,
+            options: ProviderOptions::default(),
+            fallbacks: Vec::new(),
+            failover_on: FailoverOn::DEFAULT.to_vec(),
+            cache: CacheMode::default(),
+            cache_ttl: DEFAULT_CACHE_TTL,
+            cache_max_bytes: DEFAULT_CACHE_MAX_BYTES,
+            budget: Budget::default(),
+            prices: PriceTable::default(),
+            streaming: false,
+            concurrency: DEFAULT_SECTION_CONCURRENCY,
+            custom_prompt: None,
+            prompt_file: None,
+            instructions: None,
+            summarize: false,
+            structure_section: false,
+            output_path: None,
+            reporter: Arc::new(QuietReporter),
         }
-    };
+    }
+
+    pub fn provider(mut self, provider: LlmProvider) -> Self {
+        self.provider = provider.name();
+        self
+    }
+
+    /// Use the provider registered under `name`, e.g. one added with [`Self::registry`]
+    pub fn provider_name(mut self, name: impl Into<String>) -> Self {
+        self.provider = name.into();
+        self
+    }
+
+    /// Pick the provider and fallbacks from `registry` instead of the built-in providers
+    pub fn registry(mut self, registry: ProviderRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// API key for the provider (defaults to the provider's environment variable)
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Model for README and section calls (Azure OpenAI uses its deployment's model)
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.options.model = Some(model.into());
+        self
+    }
+
+    /// Send requests to this endpoint instead of the provider's default
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.options.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Azure OpenAI deployment to call
+    pub fn deployment(mut self, deployment: impl Into<String>) -> Self {
+        self.options.deployment = Some(deployment.into());
+        self
+    }
+
+    /// Azure OpenAI `api-version` to request
+    pub fn api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.options.api_version = Some(api_version.into());
+        self
+    }
+
+    /// Models and sampling settings for whole-README calls (fast mode)
+    pub fn main_config(mut self, config: GenerationConfig) -> Self {
+        self.options.generation.main = config;
+        self
+    }
+
+    /// Models and sampling settings for section calls (streaming mode and the structure section)
+    pub fn section_config(mut self, config: GenerationConfig) -> Self {
+        self.options.generation.section = config;
+        self
+    }
 
-    // Build the final prompt
-    let final_prompt = build_final_prompt(custom_prompt, prompt_file, instructions)?;
-    
-    let generator = LlmGenerator::new(provider.clone(), api_key);
-    
-    let provider_name = match provider {
-        LlmProvider::Gemini => "Gemini",
-        LlmProvider::OpenAI => "OpenAI",
+    /// Service-account JSON key for Vertex AI
+    pub fn credentials(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.credentials = Some(path.into());
+        self
+    }
+
+    /// Google Cloud project for Vertex AI
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.options.project = Some(project.into());
+        self
+    }
+
+    /// Google Cloud region for Vertex AI
+    pub fn location(mut self, location: impl Into<String>) -> Self {
+        self.options.location = Some(location.into());
+        self
+    }
+
+    /// How failed provider calls are retried (3 retries with exponential backoff by default)
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.options.retry = retry;
+        self
+    }
+
+    /// Requests and tokens per minute to stay under for each model
+    ///
+    /// The limits are shared by every run in the process, so runs over several
+    /// repositories at once stay under one quota together.
+    pub fn rate_limits(mut self, limits: RateLimits) -> Self {
+        self.options.rate_limits = limits;
+        self
+    }
+
+    /// Continuation requests made when a reply stops at the output token limit (2 by default; 0 disables them)
+    ///
+    /// Each continuation sends the reply so far back to the model and stitches
+    /// what it adds onto the end, so sections are not cut off mid-sentence.
+    pub fn max_continuations(mut self, max_continuations: u32) -> Self {
+        self.options.max_continuations = Some(max_continuations);
+        self
+    }
+
+    /// Provider to fall back to when a call fails, after any added before it
+    ///
+    /// Fallbacks take their API keys from the environment and share the retry
+    /// and rate limit settings; sampling settings apply, but configured models
+    /// do not. Each section of the README notes which provider wrote it.
+    pub fn fallback(mut self, fallback: ProviderSpec) -> Self {
+        self.fallbacks.push(fallback);
+        self
+    }
+
+    /// Failures that move a call on to the next fallback (unavailable and blocked by default)
+    pub fn failover_on(mut self, failover_on: &[FailoverOn]) -> Self {
+        self.failover_on = failover_on.to_vec();
+        self
+    }
+
+    /// Whether replies are reused from and stored in `.tldrs/cache/responses` (used by default)
+    ///
+    /// A reply is only reused for the same provider, model, sampling settings
+    /// and prompt, so a changed file or prompt always calls the provider.
+    pub fn cache(mut self, cache: CacheMode) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// How long a cached reply is reused (a week by default)
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Size the reply cache is trimmed back to, oldest replies first (100 MB by default)
+    pub fn cache_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.cache_max_bytes = max_bytes;
+        self
+    }
+
+    /// Record every HTTP exchange with the provider, or replay recorded ones instead of calling it
+    ///
+    /// Replayed runs need no API key. Recording calls the provider even when a
+    /// reply is cached, so every request of the run ends up in the fixtures.
+    pub fn fixtures(mut self, fixtures: Fixtures) -> Self {
+        self.options.fixtures = Some(fixtures);
+        self
+    }
+
+    /// JSON file of [`MockReply`](crate::llm::MockReply)s for the mock provider to give in order
+    pub fn mock_replies(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.mock_replies = Some(path.into());
+        self
+    }
+
+    /// Tokens and estimated cost the run may use; a call that could go over is not made
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Price `model`, and models whose names start with it, instead of the built-in price
+    pub fn price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.prices.set(model, price);
+        self
+    }
+
+    /// Send an extra header with every request, for providers that support it
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Generate section by section instead of in a single call
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Number of sections generated at once in streaming mode (defaults to 3)
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Replace the default system prompt
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.custom_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Replace the default system prompt with the contents of a file
+    pub fn prompt_file(mut self, prompt_file: impl Into<PathBuf>) -> Self {
+        self.prompt_file = Some(prompt_file.into());
+        self
+    }
+
+    /// Extra instructions appended to whichever prompt is used
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Summarize files and directories hierarchically before generation
+    pub fn summarize(mut self, summarize: bool) -> Self {
+        self.summarize = summarize;
+        self
+    }
+
+    /// Add a "Project Structure" section with per-directory descriptions
+    pub fn structure_section(mut self, structure_section: bool) -> Self {
+        self.structure_section = structure_section;
+        self
+    }
+
+    /// Write the README to this path (nothing is written by default)
+    pub fn output(mut self, output_path: impl Into<PathBuf>) -> Self {
+        self.output_path = Some(output_path.into());
+        self
+    }
+
+    /// Send progress events to this reporter (runs are silent by default)
+    pub fn reporter(mut self, reporter: SharedReporter) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Scan the project, call the model and return the generated README
+    ///
+    /// The run's token usage is reported at the end, whether it succeeded or not.
+    /// Failures are sorted into the categories of [`Error`].
+    pub async fn generate(self) -> Result<ReadmeOutput, Error> {
+        let usage = Arc::new(UsageTracker::new(self.prices.clone(), self.budget));
+        let reporter = self.reporter.clone();
+        let result = self.run(usage.clone()).await;
+        reporter.report(&Event::UsageSummary(usage.summary()));
+        result.map_err(Error::from)
+    }
+
+    async fn run(self, usage: Arc<UsageTracker>) -> Result<ReadmeOutput, Box<dyn std::error::Error>> {
+        let ReadmeBuilder {
+            path,
+            registry,
+            provider,
+            api_key,
+            mut options,
+            fallbacks,
+            failover_on,
+            cache,
+            cache_ttl,
+            cache_max_bytes,
+            budget: _,
+            prices: _,
+            streaming,
+            concurrency,
+            custom_prompt,
+            prompt_file,
+            instructions,
+            summarize,
+            structure_section,
+            output_path,
+            reporter,
+        } = self;
+
+        let previous = ScanManifest::load(&cache_dir(&path));
+        let files = collect_files(&path, &previous, reporter.as_ref())?;
+        let mut manifest = previous.rescan(
+            files.iter()
+                .map(|file| (manifest_key(&file.relative_path), file.manifest_entry()))
+                .collect(),
+        );
+        let changes = manifest.changes_since(&previous);
+        reporter.report(&Event::CacheCompared {
+            first_run: previous.is_empty(),
+            total: manifest.files.len(),
+            added: changes.added,
+            modified: changes.modified,
+            removed: changes.removed,
+            unchanged: changes.unchanged,
+        });
+
+        let api_key = api_key_for(&registry, &provider, api_key, &options)?;
+
+        // Build the final prompt
+        let final_prompt = build_final_prompt(custom_prompt, prompt_file, instructions, &reporter)?;
+
+        options.usage = Some(usage.clone());
+        // Cached replies would leave their requests out of the recording
+        let recording = options.fixtures.as_ref().is_some_and(|fixtures| fixtures.mode() == FixtureMode::Record);
+        let cache = if recording && cache == CacheMode::Use { CacheMode::Refresh } else { cache };
+        if cache != CacheMode::Off {
+            options.cache = Some(
+                ResponseCache::new(cache_dir(&path).join("responses"))
+                    .with_mode(cache)
+                    .with_ttl(cache_ttl)
+                    .with_max_bytes(cache_max_bytes),
+            );
+        }
+
+        let primary = ProviderSpec::new(provider.clone(), options.model.clone());
+        let mut generator = registry.build(&provider, api_key, options.clone(), reporter.clone())?;
+        if !fallbacks.is_empty() {
+            let chain = fallback_chain(&registry, primary, generator, &fallbacks, &failover_on, &options, &reporter)?;
+            generator = LlmGenerator::Fallback(chain);
+        }
+        generator.prepare().await?;
+
+        let provider_name = registry.get(&provider).map_or("", |entry| entry.label);
+
+        let tree = ProjectTree::build(&root_name(&path), &files);
+
+        // Either summarize everything hierarchically or pack the top files directly
+        let context = if summarize {
+            context_with_tree(&tree, &generator.build_summarized_context(&files, &mut manifest).await?)
+        } else {
+            pack_context(&tree, &files, generator.context_budget())
+        };
+
+        let structure = if structure_section {
+            reporter.report(&Event::SectionStarted { section: "structure".to_string() });
+            let section = generator.generate_structure_section(&tree, &context, &final_prompt).await?;
+            reporter.report(&Event::SectionFinished { section: "structure".to_string() });
+            Some(section)
+        } else {
+            None
+        };
+
+        // Use streaming mode when requested, otherwise use fast mode
+        let mode = if streaming { GenerationMode::Streaming } else { GenerationMode::Fast };
+        reporter.report(&Event::GenerationStarted { provider: provider_name.to_string(), mode: mode.to_string() });
+        let content = if streaming {
+            match &output_path {
+                // Sections land in the file as soon as they are generated
+                Some(out_path) => {
+                    let mut file = fs::File::create(out_path).map_err(|e| Error::io(out_path, e))?;
+                    generator.generate_readme_streaming(&context, &mut file, &final_prompt, structure.as_deref(), concurrency).await?;
+                    fs::read_to_string(out_path).map_err(|e| Error::io(out_path, e))?
+                }
+                None => {
+                    let mut buffer = Vec::new();
+                    generator.generate_readme_streaming(&context, &mut buffer, &final_prompt, structure.as_deref(), concurrency).await?;
+                    String::from_utf8(buffer)?
+                }
+            }
+        } else {
+            let mut content = generator.generate_readme_fast(&context, &final_prompt).await?;
+            if let Some(section) = &structure {
+                content = insert_section(&content, section);
+            }
+            if let Some(out_path) = &output_path {
+                fs::write(out_path, &content).map_err(|e| Error::io(out_path, e))?;
+            }
+            content
+        };
+        reporter.report(&Event::GenerationFinished);
+
+        if let Some(out_path) = &output_path {
+            reporter.report(&Event::OutputWritten { path: out_path.display().to_string() });
+        }
+
+        manifest.save()?;
+
+        Ok(ReadmeOutput {
+            content,
+            output_path,
+            provider,
+            mode,
+            files_scanned: files.len(),
+            changes,
+            usage: usage.summary(),
+        })
+    }
+}
+
+/// Chain the primary generator with a generator for each fallback from `registry`
+///
+/// A fallback whose API key is missing is left out with a warning; one that
+/// is not registered fails the run.
+fn fallback_chain(
+    registry: &ProviderRegistry,
+    primary: ProviderSpec,
+    generator: LlmGenerator,
+    fallbacks: &[ProviderSpec],
+    failover_on: &[FailoverOn],
+    options: &ProviderOptions,
+    reporter: &SharedReporter,
+) -> Result<FallbackChain, Error> {
+    // Models configured for the primary provider mean nothing to the others
+    let generation = GenerationSettings {
+        main: GenerationConfig { model: None, ..options.generation.main.clone() },
+        section: GenerationConfig { model: None, ..options.generation.section.clone() },
     };
-    
-    // Use streaming mode when flag is present, otherwise use fast mode
-    if streaming {
-        println!("🚀 Generating README with {} (streaming mode)...", provider_name);
-        generator.generate_readme_streaming(&chunks, &out_path, &final_prompt).await?;
+
+    let mut chain = FallbackChain::new(primary.to_string(), generator).failover_on(failover_on);
+    for fallback in fallbacks {
+        let api_key = match api_key_for(registry, &fallback.provider, None, options) {
+            Ok(api_key) => api_key,
+            Err(err @ Error::MissingApiKey { .. }) => {
+                reporter.report(&Event::Warning { message: format!("Leaving {} out of the fallback chain: {}", fallback, err) });
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let fallback_options = ProviderOptions {
+            model: fallback.model.clone(),
+            generation: generation.clone(),
+            retry: options.retry.clone(),
+            rate_limits: options.rate_limits,
+            cache: options.cache.clone(),
+            usage: options.usage.clone(),
+            max_continuations: options.max_continuations,
+            fixtures: options.fixtures.clone(),
+            ..ProviderOptions::default()
+        };
+        let generator = registry.build(&fallback.provider, api_key, fallback_options, reporter.clone())?;
+        chain = chain.then(fallback.to_string(), generator);
+    }
+    Ok(chain)
+}
+
+/// The API key for the provider registered as `provider`; replayed runs send nothing, so they can do without one
+pub fn api_key_for(registry: &ProviderRegistry, provider: &str, api_key: Option<String>, options: &ProviderOptions) -> Result<String, Error> {
+    let replaying = options.fixtures.as_ref().is_some_and(|fixtures| fixtures.mode() == FixtureMode::Replay);
+    match registry.resolve_api_key(provider, api_key) {
+        Err(Error::MissingApiKey { .. }) if replaying => Ok(String::new()),
+        result => result,
+    }
+}
+
+/// Insert a section before the closing Contributing/License sections, or append it
+fn insert_section(readme: &str, section: &str) -> String {
+    let mut offset = 0;
+    for line in readme.split_inclusive('\n') {
+        let heading = line.trim_start().to_lowercase();
+        if heading.starts_with("## ") && (heading.contains("contributing") || heading.contains("license")) {
+            return format!("{}{}\n{}", &readme[..offset], section, &readme[offset..]);
+        }
+        offset += line.len();
+    }
+
+    format!("{}\n\n{}", readme.trim_end(), section)
+}
+
+/// Pack the most important files into a context of at most `max_len` bytes
+pub fn pack_files(files: &[ScannedFile], max_len: usize) -> String {
+    let chunks: Vec<String> = files.iter().map(ScannedFile::to_chunk).collect();
+    let filtered_chunks = StreamingSectionGenerator::filter_important_chunks(&chunks);
+    StreamingSectionGenerator::build_smart_context_within(&filtered_chunks, max_len)
+}
+
+/// Lead the code context with the project tree so models don't have to guess the layout
+pub fn context_with_tree(tree: &ProjectTree, code_context: &str) -> String {
+    with_rendered_tree(&tree.render(), code_context)
+}
+
+/// The project tree followed by the top files, together within `budget` characters
+///
+/// The tree takes at most a quarter of the budget; the files get the rest.
+pub fn pack_context(tree: &ProjectTree, files: &[ScannedFile], budget: usize) -> String {
+    let tree_block = with_rendered_tree(&tree.render_within(budget / 4), "");
+    let code_context = pack_files(files, budget.saturating_sub(tree_block.len()));
+    tree_block + &code_context
+}
+
+fn with_rendered_tree(tree: &str, code_context: &str) -> String {
+    format!("Project structure:\n```\n{}```\n\n{}", tree, code_context)
+}
+
+/// Directory holding tldrs caches for a scan target
+///
+/// Archives are never modified, so their cache lives next to the archive.
+pub fn cache_dir(path: &Path) -> PathBuf {
+    let root = if path.is_file() {
+        path.parent().unwrap_or(Path::new("."))
     } else {
-        println!("🚀 Generating README with {} (fast mode)...", provider_name);
-        let readme_content = generator.generate_readme_fast(&chunks, &final_prompt).await?;
-        fs::write(&out_path, readme_content)?;
-        println!("🎉 README.md generated successfully at: {}", out_path.display());
+        path
+    };
+    root.join(".tldrs").join("cache")
+}
+
+/// Where the CLI writes the generated README
+///
+/// Directories get a `README.md` inside them. Archives are never modified, so
+/// the README is written next to the archive as `<archive-name>.README.md`.
+pub fn default_output_path(path: &Path) -> PathBuf {
+    match ArchiveKind::stem(path) {
+        Some(stem) if path.is_file() => path.with_file_name(format!("{}.README.md", stem)),
+        _ => path.join("README.md"),
     }
-    
-    Ok(())
 }
 
 fn build_final_prompt(
     custom_prompt: Option<String>,
     prompt_file: Option<PathBuf>,
     instructions: Option<String>,
+    reporter: &SharedReporter,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    // Start with the base prompt - either custom, from file, or default
-    let mut final_prompt = match (custom_prompt, prompt_file) {
-        (Some(prompt), None) => {
-            println!("📝 Using custom prompt from CLI");
-            prompt
-        }
-        (None, Some(file_path)) => {
-            println!("📂 Loading prompt from file: {}", file_path.display());
-            fs::read_to_string(file_path)
-                .map_err(|e| format!("Failed to read prompt file: {}", e))?
-        }
+    let (source, source_path) = match (&custom_prompt, &prompt_file) {
         (Some(_), Some(_)) => {
-            return Err("Cannot specify both --prompt and --prompt-file options".into());
-        }
-        (None, None) => {
-            println!("📋 Using default system prompt");
-            SYSTEM_PROMPT.to_string()
+            return Err(Error::config("Cannot specify both --prompt and --prompt-file options").into());
         }
+        (Some(_), None) => (PromptSource::Custom, None),
+        (None, Some(file_path)) => (PromptSource::File, Some(file_path.display().to_string())),
+        (None, None) => (PromptSource::Default, None),
+    };
+    reporter.report(&Event::PromptSelected { source, path: source_path, instructions: instructions.is_some() });
+
+    // Start with the base prompt - either custom, from file, or default
+    let mut final_prompt = match (custom_prompt, prompt_file) {
+        (Some(prompt), _) => prompt,
+        (None, Some(file_path)) => fs::read_to_string(&file_path)
+            .map_err(|e| Error::config_file(format!("Failed to read prompt file {}", file_path.display()), e))?,
+        (None, None) => SYSTEM_PROMPT.to_string(),
     };
 
     // Append extra instructions if provided
     if let Some(extra_instructions) = instructions {
-        println!("📝 Appending extra instructions");
         final_prompt.push_str("\n\n**Additional Instructions:**\n");
         final_prompt.push_str(&extra_instructions);
     }
 
     Ok(final_prompt)
-}
\ No newline at end of file
+}
@@ -0,0 +1,353 @@
+use crate::llm::usage::UsageSummary;
+use crate::util::filters::SkipReason;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where the prompt for a run came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptSource {
+    Default,
+    Custom,
+    File,
+}
+
+/// Everything a run reports while it works
+///
+/// Events are the only way progress leaves the library; how they are shown
+/// (spinners, plain lines, JSON or nothing) is up to the [`Reporter`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ScanStarted { path: String },
+    DiscoveryProgress { discovered: usize },
+    FileSkipped { path: String, reason: SkipReason },
+    ScanFinished { included: usize, discovered: usize },
+    FileRead { path: String, index: usize, total: usize },
+    FilesLoaded { count: usize },
+    CacheCompared { first_run: bool, total: usize, added: usize, modified: usize, removed: usize, unchanged: usize },
+    PromptSelected { source: PromptSource, path: Option<String>, instructions: bool },
+    SummarizeStarted { files: usize },
+    FileSummarized { path: String, cached: bool },
+    DirectoriesSummarizing { directories: usize },
+    SummarizeFinished { files: usize },
+    GenerationStarted { provider: String, mode: String },
+    SectionStarted { section: String },
+    /// Generated text, as it streams in
+    TextDelta { section: String, text: String },
+    SectionFinished { section: String },
+    /// A section's call failed; other sections carry on
+    SectionFailed { section: String, error: String },
+    /// A provider call failed in a way that may pass, and will be tried again
+    RetryScheduled { api: String, reason: String, retry: u32, max_retries: u32, delay_ms: u64 },
+    /// A provider in a fallback chain failed, so the call moves on to the next one
+    ProviderFailedOver { from: String, to: String, error: String },
+    /// A call is held back to stay under the configured requests or tokens per minute
+    RateLimited { provider: String, model: String, delay_ms: u64 },
+    /// A call was answered from the response cache instead of the provider
+    CacheHit { provider: String, model: String },
+    /// A reply stopped at the output token limit, so the model is asked to continue it
+    ReplyContinued { provider: String, model: String, round: u32, max_rounds: u32 },
+    TokensUsed { provider: String, model: String, input_tokens: u64, output_tokens: u64 },
+    GenerationFinished,
+    OutputWritten { path: String },
+    /// Tokens used over the whole run, reported once at the end
+    UsageSummary(UsageSummary),
+    Warning { message: String },
+    /// The command failed and the process exits with `exit_code`
+    Failed { action: String, error: String, exit_code: u8 },
+}
+
+/// Receives events from a run
+pub trait Reporter: Send + Sync {
+    fn report(&self, event: &Event);
+}
+
+/// Reporter shared between the scanner, generators and providers
+pub type SharedReporter = Arc<dyn Reporter>;
+
+/// Reporter that discards every event
+#[derive(Debug, Default)]
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {
+    fn report(&self, _event: &Event) {}
+}
+
+/// Reporter that writes one JSON object per event to stderr
+#[derive(Debug, Default)]
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: &Event) {
+        if let Ok(line) = serde_json::to_string(event) {
+            let mut stderr = std::io::stderr().lock();
+            let _ = writeln!(stderr, "{}", line);
+        }
+    }
+}
+
+/// Reporter that writes plain status lines to stderr, without emoji or colors
+///
+/// Suited for CI logs and terminals with `NO_COLOR` set.
+#[derive(Debug, Default)]
+pub struct PlainReporter;
+
+impl Reporter for PlainReporter {
+    fn report(&self, event: &Event) {
+        if let Some(line) = describe(event, false) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// Interactive reporter with spinners and progress bars on stderr
+///
+/// Streamed text is rendered live in place of the spinner.
+#[derive(Debug, Default)]
+pub struct TtyReporter {
+    active: Mutex<Option<ProgressBar>>,
+    /// Streamed text was printed without a final newline
+    mid_line: AtomicBool,
+}
+
+impl TtyReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn spinner(message: String) -> ProgressBar {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ ")
+                .template("{spinner:.cyan} {msg}")
+                .unwrap()
+        );
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb.set_message(message);
+        pb
+    }
+
+    fn bar(len: usize, message: String) -> ProgressBar {
+        let pb = ProgressBar::new(len as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("#>-")
+        );
+        pb.set_message(message);
+        pb
+    }
+}
+
+impl Reporter for TtyReporter {
+    fn report(&self, event: &Event) {
+        let mut active = self.active.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Event::TextDelta { text, .. } = event {
+            // The text itself shows progress, so the spinner makes way for it
+            if let Some(pb) = active.take() {
+                pb.finish_and_clear();
+            }
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "{}", text);
+            let _ = stderr.flush();
+            self.mid_line.store(!text.ends_with('\n'), Ordering::Relaxed);
+            return;
+        }
+        if self.mid_line.swap(false, Ordering::Relaxed) {
+            eprintln!();
+        }
+
+        // Replace, update or finish the active progress display
+        match event {
+            Event::ScanStarted { .. } => {
+                *active = Some(Self::spinner("🔍 Discovering files...".to_string()));
+                return;
+            }
+            Event::DiscoveryProgress { discovered } => {
+                if let Some(pb) = active.as_ref() {
+                    pb.set_message(format!("🔍 Discovered {} files...", discovered));
+                }
+                return;
+            }
+            Event::FileSkipped { .. } | Event::TokensUsed { .. } => return,
+            Event::ScanFinished { included, .. } => {
+                if let Some(pb) = active.take() {
+                    pb.finish_with_message(describe(event, true).unwrap_or_default());
+                }
+                if *included > 0 {
+                    *active = Some(Self::bar(*included, "📖 Reading file contents...".to_string()));
+                }
+                return;
+            }
+            Event::FileRead { path, .. } => {
+                if let Some(pb) = active.as_ref() {
+                    pb.inc(1);
+                    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+                    pb.set_message(format!("📄 {}", file_name));
+                }
+                return;
+            }
+            Event::SummarizeStarted { files } => {
+                *active = Some(Self::bar(*files, "🧩 Summarizing files...".to_string()));
+                return;
+            }
+            Event::FileSummarized { .. } => {
+                if let Some(pb) = active.as_ref() {
+                    pb.inc(1);
+                }
+                return;
+            }
+            Event::DirectoriesSummarizing { .. } => {
+                if let Some(pb) = active.as_ref() {
+                    pb.set_message("🌳 Rolling up directory summaries...");
+                }
+                return;
+            }
+            Event::FilesLoaded { .. } | Event::SummarizeFinished { .. } | Event::GenerationFinished => {
+                match active.take() {
+                    Some(pb) => pb.finish_with_message(describe(event, true).unwrap_or_default()),
+                    // Streamed text already replaced the spinner
+                    None if matches!(event, Event::GenerationFinished) => {
+                        eprintln!("{}", describe(event, true).unwrap_or_default());
+                    }
+                    None => {}
+                }
+                return;
+            }
+            Event::SectionStarted { section } => {
+                if let Some(pb) = active.as_ref() {
+                    pb.set_message(format!("📝 Generating {}...", section));
+                }
+            }
+            Event::Failed { .. } => {
+                if let Some(pb) = active.take() {
+                    pb.finish_and_clear();
+                }
+            }
+            _ => {}
+        }
+
+        let Some(line) = describe(event, true) else {
+            return;
+        };
+        match active.as_ref() {
+            Some(pb) => pb.println(line),
+            None => eprintln!("{}", line),
+        }
+
+        if let Event::GenerationStarted { provider, .. } = event {
+            *active = Some(Self::spinner(format!("📝 Calling {} API...", provider)));
+        }
+    }
+}
+
+/// Human-readable line for an event, with or without emoji
+fn describe(event: &Event, emoji: bool) -> Option<String> {
+    let icon = |symbol: &str| if emoji { format!("{} ", symbol) } else { String::new() };
+
+    let line = match event {
+        Event::ScanStarted { path } => format!("{}Scanning {}", icon("🔍"), path),
+        Event::DiscoveryProgress { .. } | Event::FileRead { .. } | Event::FileSummarized { .. } => return None,
+        Event::FileSkipped { .. } | Event::TokensUsed { .. } | Event::TextDelta { .. } => return None,
+        Event::ScanFinished { included, discovered } => {
+            format!("{}Found {} relevant files from {} total", icon("✅"), included, discovered)
+        }
+        Event::FilesLoaded { count } if *count == 0 => format!("{}No code files found to process", icon("⚠️ ")),
+        Event::FilesLoaded { count } => format!("{}Processed {} code files successfully", icon("✅"), count),
+        Event::CacheCompared { first_run: true, total, .. } => {
+            format!("{}No scan cache found, processing all {} files", icon("🗂️ "), total)
+        }
+        Event::CacheCompared { added, modified, removed, unchanged, .. } => format!(
+            "{}{} files changed since last generation ({} added, {} modified, {} removed, {} unchanged)",
+            icon("🔁"), added + modified + removed, added, modified, removed, unchanged
+        ),
+        Event::PromptSelected { source, path, instructions } => {
+            let mut line = match source {
+                PromptSource::Default => format!("{}Using default system prompt", icon("📋")),
+                PromptSource::Custom => format!("{}Using custom prompt from CLI", icon("📝")),
+                PromptSource::File => format!(
+                    "{}Loading prompt from file: {}",
+                    icon("📂"),
+                    path.as_deref().unwrap_or("")
+                ),
+            };
+            if *instructions {
+                line.push_str(" (with extra instructions)");
+            }
+            line
+        }
+        Event::SummarizeStarted { files } => format!("{}Summarizing {} files", icon("🧩"), files),
+        Event::DirectoriesSummarizing { directories } => {
+            format!("{}Rolling up {} directory summaries", icon("🌳"), directories)
+        }
+        Event::SummarizeFinished { files } => format!("{}Summarized {} files", icon("✅"), files),
+        Event::GenerationStarted { provider, mode } => {
+            format!("{}Generating README with {} ({} mode)...", icon("🚀"), provider, mode)
+        }
+        Event::SectionStarted { section } => format!("{}Generating {}...", icon("🔄"), section),
+        Event::SectionFinished { section } => format!("{}{} written", icon("✅"), capitalize(section)),
+        Event::SectionFailed { section, error } => format!("{}{} failed: {}", icon("❌"), capitalize(section), error),
+        Event::RetryScheduled { api, reason, retry, max_retries, delay_ms } => format!(
+            "{}{}: {}, retrying in {:.1}s (retry {} of {})",
+            icon("⏳"), api, reason, *delay_ms as f64 / 1000.0, retry, max_retries
+        ),
+        Event::ProviderFailedOver { from, to, error } => {
+            format!("{}{} failed, falling back to {}: {}", icon("↪️ "), from, to, error)
+        }
+        Event::RateLimited { model, delay_ms, .. } => format!(
+            "{}Waiting {:.1}s to stay under the {} rate limit",
+            icon("⏳"), *delay_ms as f64 / 1000.0, model
+        ),
+        Event::CacheHit { model, .. } => format!("{}Reusing a cached {} response", icon("♻️ "), model),
+        Event::ReplyContinued { model, round, max_rounds, .. } => format!(
+            "{}{} reply hit the output limit, asking it to continue ({} of {})",
+            icon("✂️ "), model, round, max_rounds
+        ),
+        Event::GenerationFinished => format!("{}README generated successfully!", icon("✅")),
+        Event::OutputWritten { path } => format!("{}README.md generated successfully at: {}", icon("🎉"), path),
+        Event::UsageSummary(usage) => {
+            let cost = match usage.cost_usd {
+                Some(cost) => format!("about ${:.4}", cost),
+                None => "cost unknown".to_string(),
+            };
+            format!(
+                "{}{} calls used {} input and {} output tokens ({})",
+                icon("📊"), usage.calls, usage.input_tokens, usage.output_tokens, cost
+            )
+        }
+        Event::Warning { message } => format!("{}{}", icon("⚠️ "), message),
+        Event::Failed { action, error, .. } => format!("{}Error {}: {}", icon("❌"), action, error),
+    };
+
+    Some(line)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Pick the interactive reporter on a color-capable terminal, plain lines otherwise
+///
+/// Honors the `NO_COLOR` convention and `TERM=dumb`.
+pub fn auto_reporter() -> SharedReporter {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb_terminal = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+
+    if std::io::stderr().is_terminal() && !no_color && !dumb_terminal {
+        Arc::new(TtyReporter::new())
+    } else {
+        Arc::new(PlainReporter)
+    }
+}
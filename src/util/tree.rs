@@ -0,0 +1,229 @@
+use crate::rag::ScannedFile;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Leaf directories with more files than this are shown collapsed
+const MAX_LEAF_FILES: usize = 8;
+
+/// Rendered trees longer than this drop file lines below the top level, then deep directories
+const MAX_TREE_LINES: usize = 150;
+
+/// How much of the tree is shown
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    /// Whether files below the top level are listed
+    nested_files: bool,
+    /// Directory levels expanded below the root
+    max_depth: usize,
+}
+
+/// Layouts tried in turn until the tree fits, from the full tree to top-level directories only
+const LAYOUTS: [Layout; 6] = [
+    Layout { nested_files: true, max_depth: usize::MAX },
+    Layout { nested_files: false, max_depth: usize::MAX },
+    Layout { nested_files: false, max_depth: 4 },
+    Layout { nested_files: false, max_depth: 3 },
+    Layout { nested_files: false, max_depth: 2 },
+    Layout { nested_files: false, max_depth: 1 },
+];
+
+/// A directory in the project tree
+#[derive(Debug, Default)]
+struct TreeDir {
+    dirs: BTreeMap<String, TreeDir>,
+    files: Vec<(String, String)>,
+}
+
+impl TreeDir {
+    fn file_count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(TreeDir::file_count).sum::<usize>()
+    }
+
+    fn count_languages(&self, counts: &mut HashMap<String, usize>) {
+        for (_, language) in &self.files {
+            *counts.entry(language.clone()).or_default() += 1;
+        }
+        for dir in self.dirs.values() {
+            dir.count_languages(counts);
+        }
+    }
+
+    /// Most common language among code files below this directory
+    fn primary_language(&self) -> Option<String> {
+        let mut counts = HashMap::new();
+        self.count_languages(&mut counts);
+
+        // Prose and data files say little about what a directory is written in
+        let is_code = |language: &str| !matches!(language, "Other" | "Text" | "Markdown" | "JSON" | "YAML" | "TOML");
+        let pick = |code_only: bool| {
+            counts.iter()
+                .filter(|(language, _)| !code_only || is_code(language))
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(language, _)| language.clone())
+        };
+        pick(true).or_else(|| pick(false))
+    }
+
+    fn annotation(&self) -> String {
+        let count = self.file_count();
+        let noun = if count == 1 { "file" } else { "files" };
+        match self.primary_language() {
+            Some(language) => format!("({} {}, {})", count, noun, language),
+            None => format!("({} {})", count, noun),
+        }
+    }
+}
+
+/// Deterministic, pruned directory tree of the scanned files
+///
+/// Only files that survived the scanner's skip rules appear in the tree.
+/// Every directory is annotated with its file count and primary language,
+/// and large leaf directories are collapsed to a single line.
+#[derive(Debug)]
+pub struct ProjectTree {
+    root_name: String,
+    root: TreeDir,
+}
+
+impl ProjectTree {
+    pub fn build(root_name: &str, files: &[ScannedFile]) -> Self {
+        let mut root = TreeDir::default();
+
+        for file in files {
+            let mut dir = &mut root;
+            let components: Vec<String> = file.relative_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let Some((file_name, parents)) = components.split_last() else {
+                continue;
+            };
+
+            for parent in parents {
+                dir = dir.dirs.entry(parent.clone()).or_default();
+            }
+            dir.files.push((file_name.clone(), file.language.clone()));
+        }
+
+        sort_files(&mut root);
+        Self { root_name: root_name.to_string(), root }
+    }
+
+    /// Render the tree in at most 150 lines
+    ///
+    /// Trees that are too long first drop file lines below the top level,
+    /// then show fewer directory levels, and are cut off as a last resort.
+    pub fn render(&self) -> String {
+        self.render_within(usize::MAX)
+    }
+
+    /// Like [`Self::render`], also keeping the tree within `max_chars` characters
+    pub fn render_within(&self, max_chars: usize) -> String {
+        let (_, rendered) = self.fit(max_chars);
+        if rendered.lines().count() <= MAX_TREE_LINES && rendered.len() <= max_chars {
+            return rendered;
+        }
+
+        let lines: Vec<&str> = rendered.lines().collect();
+        let mut out = String::new();
+        for (kept, line) in lines.iter().enumerate() {
+            let marker = format!("└── … {} more lines\n", lines.len() - kept);
+            if kept + 1 >= MAX_TREE_LINES || out.len() + line.len() + 1 + marker.len() > max_chars {
+                out.push_str(&marker);
+                break;
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Paths of the directories shown in the tree (relative, with trailing slash)
+    pub fn directories(&self) -> Vec<String> {
+        let (layout, _) = self.fit(usize::MAX);
+        let mut dirs = Vec::new();
+        collect_dirs(&self.root, "", 0, layout, &mut dirs);
+        dirs
+    }
+
+    /// The first layout whose rendering fits, or the smallest one
+    fn fit(&self, max_chars: usize) -> (Layout, String) {
+        let mut smallest = None;
+        for layout in LAYOUTS {
+            let rendered = self.render_with(layout);
+            if rendered.lines().count() <= MAX_TREE_LINES && rendered.len() <= max_chars {
+                return (layout, rendered);
+            }
+            smallest = Some((layout, rendered));
+        }
+        smallest.expect("there is always a layout")
+    }
+
+    fn render_with(&self, layout: Layout) -> String {
+        let mut out = format!("{}/ {}\n", self.root_name, self.root.annotation());
+        render_dir(&self.root, "", 0, layout, &mut out);
+        out
+    }
+}
+
+fn sort_files(dir: &mut TreeDir) {
+    dir.files.sort();
+    for child in dir.dirs.values_mut() {
+        sort_files(child);
+    }
+}
+
+fn collect_dirs(dir: &TreeDir, prefix: &str, depth: usize, layout: Layout, out: &mut Vec<String>) {
+    for (name, child) in &dir.dirs {
+        let path = format!("{}{}/", prefix, name);
+        out.push(path.clone());
+        if is_expanded(child, depth, layout) {
+            collect_dirs(child, &path, depth + 1, layout, out);
+        }
+    }
+}
+
+/// Leaf directories with many files, and directories past the layout's depth, are listed as a single annotated line
+fn is_expanded(dir: &TreeDir, depth: usize, layout: Layout) -> bool {
+    let collapsed = dir.dirs.is_empty() && dir.files.len() > MAX_LEAF_FILES;
+    !collapsed && depth + 1 < layout.max_depth
+}
+
+fn render_dir(dir: &TreeDir, indent: &str, depth: usize, layout: Layout, out: &mut String) {
+    let show_files = layout.nested_files || depth == 0;
+    let mut entries: Vec<(String, Option<&TreeDir>)> = dir.dirs.iter()
+        .map(|(name, child)| (name.clone(), Some(child)))
+        .collect();
+    if show_files {
+        entries.extend(dir.files.iter().map(|(name, _)| (name.clone(), None)));
+    }
+
+    let total = entries.len();
+    for (index, (name, child)) in entries.into_iter().enumerate() {
+        let last = index + 1 == total;
+        let (branch, continuation) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+        match child {
+            Some(child) => {
+                out.push_str(&format!("{}{}{}/ {}\n", indent, branch, name, child.annotation()));
+                if is_expanded(child, depth, layout) {
+                    let child_indent = format!("{}{}", indent, continuation);
+                    render_dir(child, &child_indent, depth + 1, layout, out);
+                }
+            }
+            None => out.push_str(&format!("{}{}{}\n", indent, branch, name)),
+        }
+    }
+}
+
+/// Name shown at the root of the tree for a scan target
+pub fn root_name(path: &Path) -> String {
+    if let Some(stem) = crate::util::archive::ArchiveKind::stem(path) {
+        return stem;
+    }
+
+    path.canonicalize()
+        .ok()
+        .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or_else(|| ".".to_string())
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{AnthropicGenerator, ChatProvider, GenerationConfig, GenerationSettings};
+use tldrs::{Event, LlmGenerator, QuietReporter};
+
+fn message(text: &str, stop_reason: &str) -> StubResponse {
+    StubResponse::json(200, json!({
+        "id": "msg_01",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-sonnet-4-5",
+        "content": [{ "type": "text", "text": text }],
+        "stop_reason": stop_reason,
+        "usage": { "input_tokens": 120, "output_tokens": 40 }
+    }))
+}
+
+fn generator(server: &StubServer) -> AnthropicGenerator {
+    AnthropicGenerator::new("test-key".to_string(), Arc::new(QuietReporter)).with_base_url(&server.url)
+}
+
+#[tokio::test]
+async fn main_call_sends_messages_api_request() {
+    let server = StubServer::always(message("```markdown\n# Demo\n\nA demo project.\n```", "end_turn")).await;
+
+    let readme = generator(&server).call_main("src/main.rs:\nfn main() {}", "Be concise.").await.unwrap();
+    assert_eq!(readme, "# Demo\n\nA demo project.");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/v1/messages");
+    assert_eq!(request.header("x-api-key"), Some("test-key"));
+    assert_eq!(request.header("anthropic-version"), Some("2023-06-01"));
+    assert_eq!(request.header("authorization"), None);
+
+    let body = request.json();
+    assert_eq!(body["system"], "Be concise.");
+    assert_eq!(body["max_tokens"], 4000);
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert!(body["messages"][0]["content"].as_str().unwrap().contains("fn main() {}"));
+}
+
+#[tokio::test]
+async fn max_tokens_stop_reason_is_reported_as_warning() {
+    let server = StubServer::always(message("# Demo\n\nA demo project that", "max_tokens")).await;
+    let events = Arc::new(EventLog::default());
+    let generator = AnthropicGenerator::new("test-key".to_string(), events.clone())
+        .with_base_url(&server.url)
+        .with_max_continuations(0);
+
+    let readme = generator.call_main("context", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo\n\nA demo project that");
+
+    let events = events.events();
+    assert!(events.iter().any(|event| matches!(event, Event::Warning { message } if message.contains("4000"))));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        Event::TokensUsed { provider, input_tokens: 120, output_tokens: 40, .. } if provider == "anthropic"
+    )));
+}
+
+#[tokio::test]
+async fn refusal_is_an_error() {
+    let server = StubServer::always(message("", "refusal")).await;
+
+    let error = generator(&server).call_main("context", "prompt").await.unwrap_err();
+    assert!(error.to_string().contains("refused"));
+}
+
+#[tokio::test]
+async fn api_errors_include_the_response_body() {
+    let server = StubServer::always(StubResponse::json(401, json!({
+        "type": "error",
+        "error": { "type": "authentication_error", "message": "invalid x-api-key" }
+    }))).await;
+
+    let error = generator(&server).call_summary("summarize").await.unwrap_err();
+    assert!(error.to_string().contains("Anthropic API error"));
+    assert!(error.to_string().contains("invalid x-api-key"));
+}
+
+#[tokio::test]
+async fn section_mode_writes_every_section() {
+    let server = StubServer::start(|request| {
+        let task = request.json()["messages"][0]["content"].as_str().unwrap_or_default().to_string();
+        let text = if task.contains("project title") {
+            "# Demo"
+        } else if task.contains("## Features") {
+            "## Features\n\n- Fast"
+        } else if task.contains("## Installation") {
+            "## Installation\n\ncargo install demo"
+        } else if task.contains("## Usage") {
+            "## Usage\n\ndemo run"
+        } else {
+            "A demo project."
+        };
+        message(text, "end_turn")
+    }).await;
+
+    let generator = LlmGenerator::single(generator(&server));
+    let mut buffer = Vec::new();
+    generator.generate_readme_streaming("context", &mut buffer, "prompt", None, 3).await.unwrap();
+    let readme = String::from_utf8(buffer).unwrap();
+
+    for expected in ["# Demo", "A demo project.", "## Features", "## Installation", "## Usage", "## 🤝 Contributing"] {
+        assert!(readme.contains(expected), "missing {:?} in:\n{}", expected, readme);
+    }
+    assert_eq!(server.requests().len(), 5);
+    assert!(server.requests().iter().all(|request| request.json()["system"].as_str().unwrap().contains("prompt")));
+}
+
+#[tokio::test]
+async fn temperatures_above_the_api_maximum_are_capped() {
+    let server = StubServer::always(message("# Demo", "end_turn")).await;
+    let generator = generator(&server).with_generation(GenerationSettings {
+        main: GenerationConfig { temperature: Some(1.5), ..Default::default() },
+        ..Default::default()
+    });
+
+    generator.call_main("context", "prompt").await.unwrap();
+    assert_eq!(server.requests()[0].json()["temperature"], 1.0);
+}
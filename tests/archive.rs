@@ -0,0 +1,91 @@
+mod common;
+
+use common::scratch;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+use tldrs::util::filters::SkipReason;
+use tldrs::util::manifest::ScanManifest;
+use tldrs::{scan, QuietReporter};
+
+const FILES: [(&str, &str); 3] = [
+    ("src/lib.rs", "pub fn create() {}\n"),
+    ("Cargo.toml", "[package]\nname = \"demo\"\n"),
+    ("target/debug/build.rs", "fn main() {}\n"),
+];
+
+/// `name` inside the archive's top-level directory, or at the top when `root` is empty
+fn entry_name(root: &str, name: &str) -> String {
+    if root.is_empty() { name.to_string() } else { format!("{}/{}", root, name) }
+}
+
+fn tar_gz(path: &Path, root: &str) {
+    let mut builder = tar::Builder::new(GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::default()));
+    for (name, content) in FILES {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, entry_name(root, name), content.as_bytes()).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+fn zip(path: &Path, root: &str) {
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+    for (name, content) in FILES {
+        writer.start_file(entry_name(root, name), zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn included_and_ignored(archive: &Path) -> (Vec<String>, Vec<String>) {
+    let result = scan(archive, &ScanManifest::default(), &QuietReporter).unwrap();
+    let mut included: Vec<String> = result.files.iter().map(|file| file.relative_path.display().to_string()).collect();
+    included.sort();
+    let ignored = result.skipped.iter()
+        .filter(|file| file.reason == SkipReason::IgnoredDirectory)
+        .map(|file| file.relative_path.display().to_string())
+        .collect();
+    (included, ignored)
+}
+
+#[test]
+fn archive_roots_named_like_ignored_directories_are_scanned() {
+    let dir = scratch("roots");
+    let crate_path = dir.join("tempfile-3.8.0.crate");
+    tar_gz(&crate_path, "tempfile-3.8.0");
+
+    let (included, ignored) = included_and_ignored(&crate_path);
+    assert_eq!(included, ["tempfile-3.8.0/Cargo.toml", "tempfile-3.8.0/src/lib.rs"]);
+    // Directories below the root are still filtered
+    assert_eq!(ignored, ["tempfile-3.8.0/target/debug/build.rs"]);
+
+    let zip_path = dir.join("cached-0.1.zip");
+    zip(&zip_path, "cached-0.1");
+    let (included, ignored) = included_and_ignored(&zip_path);
+    assert_eq!(included, ["cached-0.1/Cargo.toml", "cached-0.1/src/lib.rs"]);
+    assert_eq!(ignored, ["cached-0.1/target/debug/build.rs"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn archives_without_a_top_level_directory_filter_from_the_top() {
+    let dir = scratch("flat");
+    let wheel = dir.join("demo-0.1-py3-none-any.whl");
+    zip(&wheel, "");
+    let (included, ignored) = included_and_ignored(&wheel);
+    assert_eq!(included, ["Cargo.toml", "src/lib.rs"]);
+    assert_eq!(ignored, ["target/debug/build.rs"]);
+
+    let tarball = dir.join("flat.tar.gz");
+    tar_gz(&tarball, "");
+    let (included, ignored) = included_and_ignored(&tarball);
+    assert_eq!(included, ["Cargo.toml", "src/lib.rs"]);
+    assert_eq!(ignored, ["target/debug/build.rs"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::{completion, scratch, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{CacheMode, ChatProvider, OpenAiGenerator, ResponseCache};
+use tldrs::Event;
+
+fn generator(server: &StubServer, cache: ResponseCache, reporter: Arc<EventLog>) -> OpenAiGenerator {
+    common::generator(server, reporter).with_response_cache(Some(cache))
+}
+
+#[tokio::test]
+async fn identical_calls_are_answered_from_the_cache() {
+    let server = StubServer::always(completion("# Demo", "stop")).await;
+    let dir = scratch("hits");
+    let reporter = Arc::new(EventLog::default());
+    let cached = generator(&server, ResponseCache::new(&dir), reporter.clone());
+
+    assert_eq!(cached.call_main("fn main() {}", "prompt").await.unwrap(), "# Demo");
+    assert_eq!(cached.call_main("fn main() {}", "prompt").await.unwrap(), "# Demo");
+    assert_eq!(server.requests().len(), 1);
+    assert!(reporter.events().iter().any(|event| matches!(event, Event::CacheHit { .. })));
+
+    // A different prompt is a different call
+    cached.call_main("fn main() { run() }", "prompt").await.unwrap();
+    assert_eq!(server.requests().len(), 2);
+
+    // Refreshing calls the provider again but keeps the cache up to date
+    let refreshing = generator(&server, ResponseCache::new(&dir).with_mode(CacheMode::Refresh), reporter.clone());
+    refreshing.call_main("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(server.requests().len(), 3);
+    cached.call_main("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(server.requests().len(), 3);
+
+    // Another server with the same model name has its own entries
+    let other = StubServer::always(completion("# Other", "stop")).await;
+    let elsewhere = generator(&other, ResponseCache::new(&dir), reporter.clone());
+    assert_eq!(elsewhere.call_main("fn main() {}", "prompt").await.unwrap(), "# Other");
+    assert_eq!(other.requests().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn streamed_replies_are_cached_whole() {
+    let body = ["# De", "mo"].iter()
+        .map(|text| format!("data: {}\n\n", json!({ "choices": [{ "index": 0, "delta": { "content": text }, "finish_reason": null }] })))
+        .chain(["data: [DONE]\n\n".to_string()])
+        .collect::<String>();
+    let server = StubServer::always(StubResponse::with_content_type(200, "text/event-stream", body)).await;
+    let dir = scratch("stream");
+    let generator = generator(&server, ResponseCache::new(&dir), Arc::new(EventLog::default()));
+
+    for _ in 0..2 {
+        let mut text = String::new();
+        generator.stream_with_custom_context("section", "context", &mut |delta| {
+            text.push_str(delta);
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(text, "# Demo");
+    }
+    assert_eq!(server.requests().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn failed_calls_and_oversized_caches_are_not_reused() {
+    let server = StubServer::always(StubResponse::text(400, "bad request")).await;
+    let dir = scratch("limits");
+    let failing = generator(&server, ResponseCache::new(&dir), Arc::new(EventLog::default()));
+    assert!(failing.call_main("fn main() {}", "prompt").await.is_err());
+    assert!(failing.call_main("fn main() {}", "prompt").await.is_err());
+    assert_eq!(server.requests().len(), 2);
+
+    let server = StubServer::always(completion("# Demo", "stop")).await;
+    let tiny = generator(&server, ResponseCache::new(&dir).with_max_bytes(0), Arc::new(EventLog::default()));
+    tiny.call_main("fn main() {}", "prompt").await.unwrap();
+    tiny.call_main("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(server.requests().len(), 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
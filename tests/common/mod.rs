@@ -0,0 +1,230 @@
+//! Shared helpers for integration tests: scratch directories, a minimal local
+//! HTTP stub server, canned provider replies and a reporter that records events.
+
+// Each test binary only uses some of these helpers
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tldrs::llm::OpenAiGenerator;
+use tldrs::{Event, Reporter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A fresh, empty directory for one test, unique to the test binary's process
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tldrs-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A request received by the stub server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path including the query string
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// A canned response
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub content_type: String,
+    /// Headers besides `Content-Type` and `Content-Length`
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self::with_content_type(status, "application/json", body.to_string())
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Self::with_content_type(status, "text/plain", body.to_string())
+    }
+
+    pub fn with_content_type(status: u16, content_type: &str, body: String) -> Self {
+        Self { status, content_type: content_type.to_string(), headers: Vec::new(), body }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// An OpenAI-style chat completion answering with `text`
+pub fn completion(text: &str, finish_reason: &str) -> StubResponse {
+    StubResponse::json(200, completion_body(text, finish_reason))
+}
+
+/// Like [`completion`], reporting the tokens the call used
+pub fn completion_with_usage(text: &str, prompt_tokens: u64, completion_tokens: u64) -> StubResponse {
+    let mut body = completion_body(text, "stop");
+    body["usage"] = json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens
+    });
+    StubResponse::json(200, body)
+}
+
+fn completion_body(text: &str, finish_reason: &str) -> Value {
+    json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "choices": [{ "index": 0, "message": { "role": "assistant", "content": text }, "finish_reason": finish_reason }]
+    })
+}
+
+/// OpenAI-compatible generator for model `m` on the stub server, without an API key
+pub fn generator(server: &StubServer, reporter: Arc<EventLog>) -> OpenAiGenerator {
+    model_generator(server, "m", reporter)
+}
+
+pub fn model_generator(server: &StubServer, model: &str, reporter: Arc<EventLog>) -> OpenAiGenerator {
+    OpenAiGenerator::compatible(server.url.clone(), Some(model.to_string()), String::new(), reporter)
+}
+
+type Handler = dyn Fn(&RecordedRequest) -> StubResponse + Send + Sync;
+
+/// HTTP/1.1 server on a random local port that answers every request with a handler
+///
+/// Connections are closed after each response, which keeps the parser trivial.
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl StubServer {
+    pub async fn start(handler: impl Fn(&RecordedRequest) -> StubResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind stub server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, recorded, handler).await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Answer every request with the same response
+    pub async fn always(response: StubResponse) -> Self {
+        Self::start(move |_| response.clone()).await
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    recorded: Arc<Mutex<Vec<RecordedRequest>>>,
+    handler: Arc<Handler>,
+) -> std::io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    // Read until the end of the headers
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers.iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    let request = RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&buffer[header_end..]).to_string(),
+    };
+    let response = handler(&request);
+    recorded.lock().unwrap().push(request);
+
+    let extra_headers: String = response.headers.iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    let reply = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        extra_headers,
+        response.body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reporter that keeps every event for later assertions
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: Mutex<Vec<Event>>,
+}
+
+impl EventLog {
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl Reporter for EventLog {
+    fn report(&self, event: &Event) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{completion, generator, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tldrs::llm::client::CONTINUE_PROMPT;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ChatProvider, SpeedOptimizedGenerator, VertexEndpoint};
+use tldrs::Event;
+
+fn stream(texts: &[&str], finish_reason: &str) -> StubResponse {
+    let body = texts.iter()
+        .map(|text| format!("data: {}\n\n", json!({ "choices": [{ "index": 0, "delta": { "content": text }, "finish_reason": null }] })))
+        .chain([
+            format!("data: {}\n\n", json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": finish_reason }] })),
+            "data: [DONE]\n\n".to_string(),
+        ])
+        .collect::<String>();
+    StubResponse::with_content_type(200, "text/event-stream", body)
+}
+
+#[tokio::test]
+async fn cut_off_replies_are_continued_and_stitched_together() {
+    // The first call stops at the limit; the continuation sees the whole conversation so far
+    let server = StubServer::start(|request| {
+        match request.json()["messages"].as_array().unwrap().len() {
+            2 => completion("# Demo\n\n```rust\nfn ma", "length"),
+            _ => completion("in() {}\n```\n\nDone.", "stop"),
+        }
+    }).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let readme = generator(&server, reporter.clone()).call_main("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo\n\n```rust\nfn main() {}\n```\n\nDone.");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    let messages = requests[1].json()["messages"].clone();
+    assert_eq!(messages[2], json!({ "role": "assistant", "content": "# Demo\n\n```rust\nfn ma" }));
+    assert_eq!(messages[3], json!({ "role": "user", "content": CONTINUE_PROMPT }));
+    assert!(reporter.events().iter().any(|event| matches!(event, Event::ReplyContinued { round: 1, max_rounds: 2, .. })));
+}
+
+#[tokio::test]
+async fn streamed_continuations_follow_the_cut_off_text() {
+    let server = StubServer::start(|request| {
+        match request.json()["messages"].as_array().unwrap().len() {
+            2 => stream(&["## Usage\n\nRun `car"], "length"),
+            _ => stream(&["go run", "`."], "stop"),
+        }
+    }).await;
+
+    let mut text = String::new();
+    generator(&server, Arc::new(EventLog::default())).stream_with_custom_context("section", "context", &mut |delta| {
+        text.push_str(delta);
+        Ok(())
+    }).await.unwrap();
+    assert_eq!(text, "## Usage\n\nRun `cargo run`.");
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn continuations_stop_at_the_limit_with_a_warning() {
+    let server = StubServer::always(completion("more ", "length")).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let generator = generator(&server, reporter.clone()).with_max_continuations(1);
+    assert_eq!(generator.call_main("fn main() {}", "prompt").await.unwrap(), "more more");
+    assert_eq!(server.requests().len(), 2);
+    assert!(reporter.events().iter().any(|event| matches!(
+        event,
+        Event::Warning { message } if message.contains("still cut off") && message.contains("after a continuation")
+    )));
+}
+
+#[tokio::test]
+async fn gemini_continues_after_max_tokens() {
+    let server = StubServer::start(|request| {
+        if request.path == "/token" {
+            return StubResponse::json(200, json!({ "access_token": "token", "expires_in": 3600, "token_type": "Bearer" }));
+        }
+        let (text, finish_reason) = match request.json()["contents"].as_array().unwrap().len() {
+            1 => ("# Demo\n\nA demo", "MAX_TOKENS"),
+            _ => (" project.", "STOP"),
+        };
+        StubResponse::json(200, json!({
+            "candidates": [{ "content": { "role": "model", "parts": [{ "text": text }] }, "finishReason": finish_reason }]
+        }))
+    }).await;
+    let key_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json");
+    let endpoint = VertexEndpoint::new(Some(key_file), None, Some("europe-west4".to_string()))
+        .with_base_url(&server.url)
+        .with_token_url(format!("{}/token", server.url));
+    let mut generator = SpeedOptimizedGenerator::vertex(endpoint, Arc::new(EventLog::default()));
+    generator.prepare().await.unwrap();
+
+    assert_eq!(generator.call_main("context", "prompt").await.unwrap(), "# Demo\n\nA demo project.");
+    let request = server.requests().into_iter().rfind(|request| request.path != "/token").unwrap();
+    let roles: Vec<_> = request.json()["contents"].as_array().unwrap().iter().map(|turn| turn["role"].clone()).collect();
+    assert_eq!(roles, ["user", "model", "user"]);
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::{generator, scratch, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ChatProvider, LlmGenerator, RetryPolicy, SpeedOptimizedGenerator, VertexEndpoint};
+use tldrs::util::manifest::ScanManifest;
+use tldrs::{scan, Error, LlmProvider, QuietReporter, ReadmeBuilder};
+
+async fn failure(response: StubResponse) -> Error {
+    let server = StubServer::always(response).await;
+    let generator = generator(&server, Arc::new(EventLog::default()))
+        .with_retry_policy(RetryPolicy::none());
+    generator.call_main("fn main() {}", "prompt").await.unwrap_err().into()
+}
+
+#[tokio::test]
+async fn provider_failures_are_sorted_into_categories() {
+    let err = failure(StubResponse::text(401, "invalid key")).await;
+    assert!(matches!(err, Error::AuthRejected { .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 4);
+    assert!(err.to_string().contains("Check that the API key is valid"), "{}", err);
+
+    let err = failure(StubResponse::text(429, "quota used up").header("retry-after", "30")).await;
+    assert!(matches!(err, Error::QuotaExceeded { retry_after: Some(_), .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 5);
+
+    let err = failure(StubResponse::text(503, "overloaded")).await;
+    assert!(matches!(err, Error::Network { .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 7);
+
+    let err = failure(StubResponse::text(404, "no such model")).await;
+    assert!(matches!(err, Error::Api { .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 8);
+
+    let err = failure(StubResponse::json(200, json!({ "choices": [] }))).await;
+    assert!(matches!(err, Error::InvalidResponse { .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 9);
+}
+
+#[tokio::test]
+async fn gemini_prompt_blocks_carry_the_reason_and_categories() {
+    let server = StubServer::start(|request| {
+        if request.path == "/token" {
+            return StubResponse::json(200, json!({ "access_token": "token", "expires_in": 3600, "token_type": "Bearer" }));
+        }
+        StubResponse::json(200, json!({
+            "promptFeedback": {
+                "blockReason": "SAFETY",
+                "safetyRatings": [
+                    { "category": "HARM_CATEGORY_HARASSMENT", "probability": "HIGH", "blocked": true },
+                    { "category": "HARM_CATEGORY_HATE_SPEECH", "probability": "NEGLIGIBLE" }
+                ]
+            }
+        }))
+    }).await;
+    let key_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json");
+    let endpoint = VertexEndpoint::new(Some(key_file), None, Some("europe-west4".to_string()))
+        .with_base_url(&server.url)
+        .with_token_url(format!("{}/token", server.url));
+    let mut generator = SpeedOptimizedGenerator::vertex(endpoint, Arc::new(EventLog::default()));
+    generator.prepare().await.unwrap();
+
+    let err = Error::from(generator.call_main("context", "prompt").await.unwrap_err());
+    match &err {
+        Error::Blocked { reason, .. } => assert_eq!(reason, "prompt blocked: SAFETY (HARM_CATEGORY_HARASSMENT)"),
+        other => panic!("expected a block, got {:?}", other),
+    }
+    assert_eq!(err.exit_code(), 6);
+}
+
+#[tokio::test]
+async fn failed_sections_keep_the_category_of_the_first_failure() {
+    let server = StubServer::always(StubResponse::text(403, "no access to this model")).await;
+    let generator = LlmGenerator::single(generator(&server, Arc::new(EventLog::default())));
+
+    let mut readme = Vec::new();
+    let err = Error::from(generator.generate_readme_streaming("context", &mut readme, "prompt", None, 2).await.unwrap_err());
+    assert!(matches!(&err, Error::SectionsFailed { sections, .. } if sections.len() == 5), "{:?}", err);
+    assert_eq!(err.exit_code(), 4);
+}
+
+#[test]
+fn the_cli_exits_with_the_code_for_the_failure() {
+    let dir = scratch("cli");
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tldrs"))
+        .args(["readme", ".", "--provider", "anthropic", "--quiet"])
+        .current_dir(&dir)
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No API key for anthropic. Set ANTHROPIC_API_KEY or use --api-key"), "{}", stderr);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn unreadable_files_keep_their_path_and_cause() {
+    let dir = scratch("files");
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+
+    let err = ReadmeBuilder::new(&dir)
+        .provider(LlmProvider::Mock)
+        .prompt_file(dir.join("missing.md"))
+        .generate()
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, Error::Config { source: Some(_), .. }), "{:?}", err);
+    assert_eq!(err.exit_code(), 2);
+    assert!(err.to_string().contains("missing.md"), "{}", err);
+
+    // The README cannot be written into a directory that does not exist
+    let out_path = dir.join("missing").join("README.md");
+    let err = ReadmeBuilder::new(&dir)
+        .provider(LlmProvider::Mock)
+        .output(&out_path)
+        .generate()
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, Error::Io { path: Some(path), .. } if *path == out_path), "{:?}", err);
+    assert_eq!(err.exit_code(), 11);
+    assert!(err.to_string().contains("README.md"), "{}", err);
+
+    let archive = dir.join("broken.tar.gz");
+    std::fs::write(&archive, "not gzip").unwrap();
+    let err = Error::from(scan(&archive, &ScanManifest::default(), &QuietReporter).unwrap_err());
+    assert!(matches!(&err, Error::Io { path: Some(path), .. } if *path == archive), "{:?}", err);
+    assert_eq!(err.exit_code(), 11);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
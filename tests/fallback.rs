@@ -0,0 +1,131 @@
+mod common;
+
+use common::{completion, EventLog, StubResponse, StubServer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tldrs::llm::streaming::{LlmApiClient, StreamingSectionGenerator};
+use tldrs::llm::{ChatProvider, FailoverOn, FallbackChain, LlmGenerator, OllamaGenerator, ProviderSpec, RetryPolicy};
+use tldrs::Event;
+
+fn event_stream(events: &[Value]) -> StubResponse {
+    let body = events.iter().map(|event| format!("data: {}\n\n", event)).collect::<String>();
+    StubResponse::with_content_type(200, "text/event-stream", body)
+}
+
+fn generator(server: &StubServer, reporter: Arc<EventLog>) -> LlmGenerator {
+    LlmGenerator::single(common::generator(server, reporter).with_retry_policy(RetryPolicy::none()))
+}
+
+fn failovers(reporter: &EventLog) -> Vec<(String, String)> {
+    reporter.events().into_iter()
+        .filter_map(|event| match event {
+            Event::ProviderFailedOver { from, to, .. } => Some((from, to)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn provider_specs_parse_with_and_without_models() {
+    let spec: ProviderSpec = "ollama:llama3.2:latest".parse().unwrap();
+    assert_eq!(spec, ProviderSpec::new("ollama", Some("llama3.2:latest".to_string())));
+    assert_eq!(spec.to_string(), "ollama:llama3.2:latest");
+
+    // Built-in aliases resolve to the registry name; other names are checked when the provider is built
+    let spec: ProviderSpec = "Open-AI".parse().unwrap();
+    assert_eq!(spec.to_string(), "openai");
+    assert_eq!("my-llm:v2".parse::<ProviderSpec>().unwrap(), ProviderSpec::new("my-llm", Some("v2".to_string())));
+    assert!(":model".parse::<ProviderSpec>().is_err());
+}
+
+// Several threads, so both stub servers can answer while sections run concurrently
+#[tokio::test(flavor = "multi_thread")]
+async fn sections_fail_over_and_name_their_provider() {
+    let primary = StubServer::start(|request| {
+        let task = request.json()["messages"][1]["content"].as_str().unwrap_or_default().to_string();
+        if task.contains("project title") {
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": "# Demo" }, "finish_reason": "stop" }] });
+            event_stream(&[chunk])
+        } else {
+            StubResponse::text(503, "overloaded")
+        }
+    }).await;
+    let backup = StubServer::always(event_stream(&[
+        json!({ "choices": [{ "index": 0, "delta": { "content": "Backup text" }, "finish_reason": "stop" }] }),
+    ])).await;
+    let reporter = Arc::new(EventLog::default());
+    let chain = FallbackChain::new("primary", generator(&primary, reporter.clone()))
+        .then("backup", generator(&backup, reporter.clone()));
+
+    let mut output = Vec::new();
+    StreamingSectionGenerator::new(reporter.clone())
+        .generate_sections_incrementally(&chain, "fn main() {}", &mut output, "prompt")
+        .await
+        .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("# Demo\n\n<!-- title generated by primary -->\n\nBackup text\n\n<!-- description generated by backup -->"), "{}", output);
+    assert!(output.ends_with("<!-- usage generated by backup -->\n\n"), "{}", output);
+    assert_eq!(failovers(&reporter).len(), 4);
+}
+
+#[tokio::test]
+async fn blocked_replies_fail_over_but_rejected_requests_do_not() {
+    let blocked = StubServer::always(completion("", "content_filter")).await;
+    let rejected = StubServer::always(StubResponse::text(400, "bad request")).await;
+    let backup = StubServer::always(completion("# Demo", "stop")).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let chain = FallbackChain::new("filtered", generator(&blocked, reporter.clone()))
+        .then("backup", generator(&backup, reporter.clone()));
+    let readme = LlmGenerator::Fallback(chain).generate_readme_fast("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(readme, "<!-- README generated by backup -->\n\n# Demo");
+    assert_eq!(failovers(&reporter), vec![("filtered".to_string(), "backup".to_string())]);
+
+    let chain = || FallbackChain::new("picky", generator(&rejected, reporter.clone()))
+        .then("backup", generator(&backup, reporter.clone()));
+    let err = LlmGenerator::Fallback(chain()).generate_readme_fast("fn main() {}", "prompt").await.unwrap_err();
+    assert!(err.to_string().contains("400"), "{}", err);
+
+    let chain = chain().failover_on(&[FailoverOn::Rejected]);
+    let readme = LlmGenerator::Fallback(chain).generate_readme_fast("fn main() {}", "prompt").await.unwrap();
+    assert!(readme.ends_with("# Demo"));
+}
+
+#[tokio::test]
+async fn an_unreachable_server_fails_over() {
+    // A port that was free a moment ago, so nothing answers on it
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let reporter = Arc::new(EventLog::default());
+    let ollama = OllamaGenerator::new(Some("llama3.2".to_string()), Some(format!("http://127.0.0.1:{}", port)), reporter.clone())
+        .with_retry_policy(RetryPolicy::none());
+    let backup = StubServer::always(completion("# Demo", "stop")).await;
+
+    let chain = FallbackChain::new("ollama", LlmGenerator::single(ollama))
+        .then("backup", generator(&backup, reporter.clone()));
+    let readme = LlmGenerator::Fallback(chain).generate_readme_fast("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(readme, "<!-- README generated by backup -->\n\n# Demo");
+    assert_eq!(failovers(&reporter), vec![("ollama".to_string(), "backup".to_string())]);
+}
+
+#[tokio::test]
+async fn a_stream_that_breaks_before_any_text_fails_over() {
+    // The stream ends without a finish reason or `[DONE]`
+    let broken = StubServer::always(event_stream(&[
+        json!({ "choices": [{ "index": 0, "delta": { "role": "assistant" }, "finish_reason": null }] }),
+    ])).await;
+    let backup = StubServer::always(event_stream(&[
+        json!({ "choices": [{ "index": 0, "delta": { "content": "Backup text" }, "finish_reason": "stop" }] }),
+    ])).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let chain = FallbackChain::new("broken", generator(&broken, reporter.clone()))
+        .then("backup", generator(&backup, reporter.clone()));
+    let mut text = String::new();
+    chain.stream_with_custom_context("section", "prompt", &mut |delta| {
+        text.push_str(delta);
+        Ok(())
+    }).await.unwrap();
+    assert_eq!(text, "Backup text");
+    assert_eq!(failovers(&reporter), vec![("broken".to_string(), "backup".to_string())]);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::scratch;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tldrs::util::manifest::{content_hash, manifest_key, ScanManifest};
+use tldrs::{collect_files, QuietReporter, ScannedFile};
+
+/// Write a file with a fixed modification time, so rewrites within the same second are still told apart
+fn write(dir: &Path, name: &str, content: &str, modified: u64) {
+    write_at(dir, name, content, Duration::from_secs(modified));
+}
+
+fn write_at(dir: &Path, name: &str, content: &str, modified: Duration) {
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::UNIX_EPOCH + modified).unwrap();
+}
+
+/// Scan the project and build the manifest the next run would load
+fn rescan(dir: &Path, previous: &ScanManifest) -> (Vec<ScannedFile>, ScanManifest) {
+    let files = collect_files(dir, previous, &QuietReporter).unwrap();
+    let manifest = previous.rescan(files.iter().map(|file| (manifest_key(&file.relative_path), file.manifest_entry())).collect());
+    (files, manifest)
+}
+
+#[test]
+fn a_second_scan_counts_what_changed() {
+    let dir = scratch("changes");
+    write(&dir, "main.rs", "fn main() {}\n", 1_000);
+    write(&dir, "lib.rs", "pub fn one() {}\n", 1_000);
+    write(&dir, "old.rs", "pub fn old() {}\n", 1_000);
+    let (_, first) = rescan(&dir, &ScanManifest::default());
+
+    write(&dir, "lib.rs", "pub fn two() {}\n", 2_000);
+    std::fs::remove_file(dir.join("old.rs")).unwrap();
+    write(&dir, "new.rs", "pub fn new() {}\n", 2_000);
+    let (_, second) = rescan(&dir, &first);
+
+    let changes = second.changes_since(&first);
+    assert_eq!((changes.added, changes.modified, changes.removed, changes.unchanged), (1, 1, 1, 1));
+    assert_eq!(changes.changed(), 3);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_unchanged_on_disk_are_not_read_during_the_scan() {
+    let dir = scratch("unchanged");
+    write(&dir, "main.rs", "fn main() {}\n", 1_000);
+    let (_, first) = rescan(&dir, &ScanManifest::default());
+
+    // Same size and modification time: the manifest is trusted and the file is not hashed again
+    write(&dir, "main.rs", "fn mian() {}\n", 1_000);
+    let (files, second) = rescan(&dir, &first);
+    assert_eq!(files[0].hash, content_hash("fn main() {}\n"));
+    assert_eq!(second.changes_since(&first).unchanged, 1);
+
+    // Its content is only read once something uses it
+    assert_eq!(&*files[0].content, "fn mian() {}\n");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn edits_within_the_same_second_are_noticed() {
+    let dir = scratch("subsecond");
+    write_at(&dir, "main.rs", "fn main() {}\n", Duration::new(1_000, 100_000_000));
+    let (_, first) = rescan(&dir, &ScanManifest::default());
+
+    // Same size and the same whole second, but a later modification time
+    write_at(&dir, "main.rs", "fn mian() {}\n", Duration::new(1_000, 900_000_000));
+    let (files, second) = rescan(&dir, &first);
+    assert_eq!(files[0].hash, content_hash("fn mian() {}\n"));
+    assert_eq!(second.changes_since(&first).modified, 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::{scratch, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{CacheMode, ChatProvider, Fixtures, MockReply, RetryPolicy, SpeedOptimizedGenerator, VertexEndpoint};
+use tldrs::{Error, Event, LlmProvider, ReadmeBuilder};
+
+/// A fresh project with a single source file
+fn project(name: &str) -> PathBuf {
+    let dir = scratch(name);
+    std::fs::write(dir.join("main.rs"), "fn main() {\n    println!(\"hello\");\n}\n").unwrap();
+    dir
+}
+
+fn script(dir: &Path, replies: &[MockReply]) -> PathBuf {
+    let path = dir.join("replies.json");
+    std::fs::write(&path, serde_json::to_string(replies).unwrap()).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn scripted_replies_run_the_whole_pipeline_offline() {
+    let dir = project("pipeline");
+    let replies = script(&dir, &[MockReply::Truncated("# Demo\n\nPrints a gree".to_string()), MockReply::Text("ting.".to_string())]);
+    let reporter = Arc::new(EventLog::default());
+
+    let readme = ReadmeBuilder::new(&dir)
+        .provider(LlmProvider::Mock)
+        .mock_replies(replies)
+        .cache(CacheMode::Off)
+        .output(dir.join("README.md"))
+        .reporter(reporter.clone())
+        .generate()
+        .await
+        .unwrap();
+
+    // The cut-off reply is continued, and the stitched README lands in the file
+    assert_eq!(readme.content, "# Demo\n\nPrints a greeting.");
+    assert_eq!(std::fs::read_to_string(dir.join("README.md")).unwrap(), readme.content);
+    assert_eq!(readme.files_scanned, 2);
+    assert!(readme.usage.input_tokens > 0 && readme.usage.output_tokens > 0, "{:?}", readme.usage);
+    assert!(reporter.events().iter().any(|event| matches!(event, Event::ReplyContinued { round: 1, .. })));
+
+    // Once the script runs out every section gets the canned reply
+    let streamed = ReadmeBuilder::new(&dir).provider(LlmProvider::Mock).cache(CacheMode::Off).streaming(true).generate().await.unwrap();
+    assert_eq!(streamed.content.matches("no model was called").count(), 5, "{}", streamed.content);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn scripted_failures_keep_their_category() {
+    let dir = project("failures");
+    let replies = script(&dir, &[MockReply::Error { status: 429, message: "slow down".to_string() }]);
+
+    let err = ReadmeBuilder::new(&dir)
+        .provider(LlmProvider::Mock)
+        .mock_replies(replies)
+        .cache(CacheMode::Off)
+        .streaming(true)
+        .concurrency(1)
+        .generate()
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, Error::SectionsFailed { sections, .. } if sections.len() == 1), "{:?}", err);
+    assert_eq!(err.exit_code(), 5);
+
+    std::fs::write(dir.join("replies.json"), "not json").unwrap();
+    let err = ReadmeBuilder::new(&dir).provider(LlmProvider::Mock).mock_replies(dir.join("replies.json")).generate().await.unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+    assert!(err.to_string().contains("Could not read the mock replies"), "{}", err);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_cli_runs_the_mock_provider() {
+    let dir = project("cli");
+    script(&dir, &[MockReply::Blocked("SAFETY".to_string())]);
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_tldrs"))
+            .args(["readme", ".", "--provider", "mock", "--no-cache", "--quiet"])
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--mock-replies", "replies.json"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Mock API blocked the request (SAFETY)"));
+
+    let output = run(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(std::fs::read_to_string(dir.join("README.md")).unwrap().contains("no model was called"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn recorded_exchanges_are_replayed_without_calling_the_provider() {
+    let server = StubServer::always(StubResponse::json(200, json!({
+        "choices": [{ "index": 0, "message": { "role": "assistant", "content": "# Recorded" }, "finish_reason": "stop" }]
+    }))).await;
+    let dir = project("replay");
+    // Fixtures inside the project would be scanned into the replayed prompt
+    let fixtures = scratch("replay-fixtures");
+    let run = |fixtures: Fixtures, api_key: Option<&str>, instructions: &str| {
+        let mut builder = ReadmeBuilder::new(&dir)
+            .provider(LlmProvider::OpenAICompatible)
+            .base_url(server.url.clone())
+            .model("m")
+            .instructions(instructions)
+            .fixtures(fixtures);
+        if let Some(api_key) = api_key {
+            builder = builder.api_key(api_key);
+        }
+        builder.generate()
+    };
+
+    let recorded = run(Fixtures::record(&fixtures), Some("secret"), "Be brief").await.unwrap();
+    assert_eq!(recorded.content, "# Recorded");
+    let saved: Vec<String> = std::fs::read_dir(&fixtures).unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect();
+    assert_eq!(saved.len(), 1);
+    assert!(saved[0].contains("# Recorded") && !saved[0].contains("secret"), "{}", saved[0]);
+
+    // Replaying needs neither the server nor a key
+    let replayed = run(Fixtures::replay(&fixtures), None, "Be brief").await.unwrap();
+    assert_eq!(replayed.content, "# Recorded");
+    assert_eq!(server.requests().len(), 1);
+
+    // A request that was never recorded fails instead of reaching the provider
+    let err = run(Fixtures::replay(&fixtures), None, "Be thorough").await.unwrap_err();
+    assert_eq!(err.exit_code(), 7);
+    assert!(err.to_string().contains("no recorded response"), "{}", err);
+    assert_eq!(server.requests().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&fixtures);
+}
+
+#[tokio::test]
+async fn replayed_vertex_runs_need_no_token_and_no_backoff() {
+    let server = StubServer::start(|request| {
+        if request.path == "/token" {
+            return StubResponse::json(200, json!({ "access_token": "token", "expires_in": 3600, "token_type": "Bearer" }));
+        }
+        StubResponse::text(503, "overloaded")
+    }).await;
+    let fixtures = scratch("vertex-fixtures");
+    let key_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json");
+    let vertex = |fixtures: Fixtures, reporter: Arc<EventLog>| {
+        let endpoint = VertexEndpoint::new(Some(key_file.clone()), None, Some("europe-west4".to_string()))
+            .with_base_url(&server.url)
+            .with_token_url(format!("{}/token", server.url));
+        SpeedOptimizedGenerator::vertex(endpoint, reporter).with_fixtures(Some(fixtures))
+    };
+
+    // Recording sends the token exchange as usual but only saves the model call
+    let mut recording = vertex(Fixtures::record(&fixtures), Arc::new(EventLog::default()))
+        .with_retry_policy(RetryPolicy::none());
+    recording.prepare().await.unwrap();
+    assert!(recording.call_main("context", "prompt").await.is_err());
+    let sent = server.requests().len();
+    assert_eq!(std::fs::read_dir(&fixtures).unwrap().count(), 1);
+
+    // Replaying skips the token endpoint and retries the saved 503 without waiting
+    let reporter = Arc::new(EventLog::default());
+    let mut replaying = vertex(Fixtures::replay(&fixtures), reporter.clone());
+    let started = Instant::now();
+    replaying.prepare().await.unwrap();
+    let err = Error::from(replaying.call_main("context", "prompt").await.unwrap_err());
+    assert!(matches!(err, Error::Network { .. }), "{:?}", err);
+    assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+    assert_eq!(server.requests().len(), sent);
+    let delays: Vec<u64> = reporter.events().iter()
+        .filter_map(|event| match event {
+            Event::RetryScheduled { delay_ms, .. } => Some(*delay_ms),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(delays, vec![0, 0, 0]);
+
+    let _ = std::fs::remove_dir_all(&fixtures);
+}
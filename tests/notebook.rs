@@ -0,0 +1,59 @@
+mod common;
+
+use common::scratch;
+use serde_json::json;
+use std::path::PathBuf;
+use tldrs::util::filters::SkipReason;
+use tldrs::util::manifest::ScanManifest;
+use tldrs::util::notebook::extract_notebook;
+use tldrs::{scan, QuietReporter};
+
+fn notebook() -> String {
+    json!({
+        "nbformat": 4,
+        "metadata": { "kernelspec": { "language": "python" } },
+        "cells": [
+            { "cell_type": "markdown", "source": ["# Training\n", "Fits the model."] },
+            {
+                "cell_type": "code",
+                "execution_count": 3,
+                "source": "model.fit(data)\n",
+                "outputs": [
+                    { "output_type": "stream", "text": ["epoch 1: loss 0.42\n"] },
+                    { "output_type": "display_data", "data": { "image/png": "iVBORw0KGgoAAAANSUhEUg" } }
+                ]
+            },
+            { "cell_type": "code", "source": [], "outputs": [] }
+        ]
+    })
+    .to_string()
+}
+
+#[test]
+fn notebooks_keep_their_cells_and_drop_outputs() {
+    let text = extract_notebook(&notebook()).unwrap();
+
+    assert_eq!(
+        text,
+        "Jupyter notebook (python), 3 cells\n\
+         \n# %% [markdown] cell 1\n# Training\nFits the model.\n\
+         \n# %% [code] cell 2\nmodel.fit(data)\n"
+    );
+    assert!(!text.contains("loss") && !text.contains("iVBOR"), "{}", text);
+    assert_eq!(extract_notebook("not json"), None);
+}
+
+#[test]
+fn scanned_notebooks_are_reduced_to_their_cells() {
+    let dir = scratch("scan");
+    std::fs::write(dir.join("train.ipynb"), notebook()).unwrap();
+    std::fs::write(dir.join("broken.ipynb"), "{ not a notebook").unwrap();
+
+    let result = scan(&dir, &ScanManifest::default(), &QuietReporter).unwrap();
+    assert_eq!(result.files.len(), 1);
+    assert!(result.files[0].content.starts_with("Jupyter notebook (python), 3 cells"), "{}", result.files[0].content);
+    let skipped: Vec<_> = result.skipped.iter().map(|file| (file.relative_path.clone(), file.reason)).collect();
+    assert_eq!(skipped, [(PathBuf::from("broken.ipynb"), SkipReason::InvalidNotebook)]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{completion, completion_with_usage, generator, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ChatProvider, GenerationConfig, GenerationSettings, OpenAiGenerator, ProviderOptions, RetryPolicy};
+use tldrs::{Event, LlmGenerator, QuietReporter};
+
+/// Serve `/models` with a single model and answer chat completions with `text`
+async fn server_with_model(model: &'static str, text: &'static str) -> StubServer {
+    StubServer::start(move |request| {
+        if request.path.ends_with("/models") {
+            StubResponse::json(200, json!({ "object": "list", "data": [{ "id": model, "object": "model" }] }))
+        } else {
+            completion_with_usage(text, 50, 10)
+        }
+    }).await
+}
+
+#[tokio::test]
+async fn sends_configured_model_headers_and_no_auth_without_key() {
+    let server = StubServer::always(completion("# Demo", "stop")).await;
+    let generator = OpenAiGenerator::compatible(
+        format!("{}/v1/", server.url),
+        Some("qwen2.5-coder-32b".to_string()),
+        String::new(),
+        Arc::new(QuietReporter),
+    )
+    .with_header("X-Team", "docs");
+
+    let readme = generator.call_main("context", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.path, "/v1/chat/completions");
+    assert_eq!(request.header("authorization"), None);
+    assert_eq!(request.header("x-team"), Some("docs"));
+    assert_eq!(request.json()["model"], "qwen2.5-coder-32b");
+    assert_eq!(request.json()["messages"][0]["role"], "system");
+    assert_eq!(request.json()["messages"][0]["content"], "prompt");
+}
+
+#[tokio::test]
+async fn sends_bearer_auth_when_key_is_set() {
+    let server = StubServer::always(completion("A summary.", "stop")).await;
+    let generator = OpenAiGenerator::compatible(
+        format!("{}/v1", server.url),
+        Some("local-model".to_string()),
+        "secret".to_string(),
+        Arc::new(QuietReporter),
+    );
+
+    generator.call_summary("summarize").await.unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.header("authorization"), Some("Bearer secret"));
+    // Compatible servers serve one model, so summaries use it too
+    assert_eq!(request.json()["model"], "local-model");
+}
+
+#[tokio::test]
+async fn discovers_the_served_model_when_none_is_set() {
+    let server = server_with_model("meta-llama/Llama-3.1-8B-Instruct", "# Demo").await;
+    let events = Arc::new(EventLog::default());
+    let options = ProviderOptions {
+        base_url: Some(format!("{}/v1", server.url)),
+        ..ProviderOptions::default()
+    };
+
+    let mut generator = LlmGenerator::new("openai-compatible", String::new(), options, events.clone()).unwrap();
+    generator.prepare().await.unwrap();
+    let readme = generator.generate_readme_fast("context", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo");
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/v1/models");
+    assert_eq!(requests[1].path, "/v1/chat/completions");
+    assert_eq!(requests[1].json()["model"], "meta-llama/Llama-3.1-8B-Instruct");
+
+    assert!(events.events().iter().any(|event| matches!(
+        event,
+        Event::TokensUsed { model, input_tokens: 50, output_tokens: 10, .. } if model == "meta-llama/Llama-3.1-8B-Instruct"
+    )));
+}
+
+#[tokio::test]
+async fn main_and_section_calls_use_their_own_settings() {
+    let server = StubServer::always(completion("# Demo", "stop")).await;
+    let options = ProviderOptions {
+        base_url: Some(format!("{}/v1", server.url)),
+        model: Some("base-model".to_string()),
+        generation: GenerationSettings {
+            main: "temperature=0.1,max_tokens=8000,seed=7".parse().unwrap(),
+            section: GenerationConfig {
+                model: Some("section-model".to_string()),
+                top_p: Some(0.9),
+                stop_sequences: vec!["<END>".to_string()],
+                ..GenerationConfig::default()
+            },
+        },
+        ..ProviderOptions::default()
+    };
+    let generator = LlmGenerator::new("openai-compatible", String::new(), options, Arc::new(QuietReporter)).unwrap();
+
+    generator.generate_readme_fast("context", "prompt").await.unwrap();
+    generator.call_with_custom_context("section", "prompt").await.unwrap();
+
+    let requests = server.requests();
+    let main = requests[0].json();
+    assert_eq!(main["model"], "base-model");
+    assert_eq!(main["temperature"], 0.1);
+    assert_eq!(main["max_tokens"], 8000);
+    assert_eq!(main["seed"], 7);
+    assert!(main.get("top_p").is_none());
+
+    let section = requests[1].json();
+    assert_eq!(section["model"], "section-model");
+    assert_eq!(section["temperature"], 0.7);
+    assert_eq!(section["max_tokens"], 1000);
+    assert_eq!(section["top_p"], 0.9);
+    assert_eq!(section["stop"], json!(["<END>"]));
+}
+
+#[tokio::test]
+async fn missing_base_url_is_reported_before_any_request() {
+    let mut generator = LlmGenerator::new("openai-compatible", String::new(), ProviderOptions::default(), Arc::new(QuietReporter)).unwrap();
+
+    let error = generator.prepare().await.unwrap_err();
+    assert!(error.to_string().contains("--base-url"));
+}
+
+#[tokio::test]
+async fn server_errors_include_the_response_body() {
+    let server = StubServer::always(StubResponse::text(503, "model is still loading")).await;
+    let generator = generator(&server, Arc::new(EventLog::default())).with_retry_policy(RetryPolicy::none());
+
+    let error = generator.call_main("context", "prompt").await.unwrap_err();
+    assert!(error.to_string().contains("model is still loading"));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{completion, completion_with_usage, model_generator, EventLog, StubResponse, StubServer};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tldrs::llm::rate_limit::acquire;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ChatProvider, RateLimits, RetryPolicy};
+use tldrs::Event;
+
+// Each test uses its own model name, since the limits are shared by the whole process
+
+#[tokio::test]
+async fn requests_beyond_the_allowance_wait_for_the_bucket_to_refill() {
+    let limits = RateLimits { requests_per_minute: Some(120), tokens_per_minute: None };
+    let mut waits = Vec::new();
+
+    // A full minute's allowance is available straight away
+    for _ in 0..120 {
+        acquire("test", "requests-model", &limits, 0, |wait| waits.push(wait)).await;
+    }
+    assert!(waits.is_empty());
+
+    let started = Instant::now();
+    acquire("test", "requests-model", &limits, 0, |wait| waits.push(wait)).await;
+    assert_eq!(waits.len(), 1);
+    assert!(waits[0] > Duration::from_millis(400) && waits[0] <= Duration::from_millis(500), "{:?}", waits);
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
+
+#[tokio::test]
+async fn token_budget_is_shared_across_generators() {
+    let server = StubServer::always(completion_with_usage("ok", 6000, 20)).await;
+    let limits = RateLimits { requests_per_minute: None, tokens_per_minute: Some(6000) };
+    let generator = |reporter: Arc<EventLog>| {
+        model_generator(&server, "tokens-model", reporter).with_rate_limits(limits)
+    };
+
+    // About 6000 tokens of prompt spends the whole minute's budget
+    let first = Arc::new(EventLog::default());
+    generator(first.clone()).call_summary(&"x".repeat(24000)).await.unwrap();
+
+    let second = Arc::new(EventLog::default());
+    let started = Instant::now();
+    generator(second.clone()).call_summary("short").await.unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert!(first.events().iter().all(|event| !matches!(event, Event::RateLimited { .. })));
+    assert!(second.events().iter().any(|event| matches!(
+        event,
+        Event::RateLimited { model, .. } if model == "tokens-model"
+    )));
+}
+
+#[tokio::test]
+async fn retries_wait_for_the_rate_limits_too() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let server = StubServer::start(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+        0 => StubResponse::text(503, "overloaded"),
+        _ => completion("# Demo", "stop"),
+    }).await;
+    let limits = RateLimits { requests_per_minute: Some(120), tokens_per_minute: None };
+    let reporter = Arc::new(EventLog::default());
+    let generator = model_generator(&server, "retry-model", reporter.clone())
+        .with_rate_limits(limits)
+        .with_retry_policy(RetryPolicy { max_retries: 1, base_delay: Duration::from_millis(1), max_delay: Duration::from_secs(1) });
+
+    // Leave room for the first attempt only
+    for _ in 0..119 {
+        acquire("openai", "retry-model", &limits, 0, |_| {}).await;
+    }
+    generator.call_main("context", "prompt").await.unwrap();
+
+    assert_eq!(server.requests().len(), 2);
+    let waits: Vec<u64> = reporter.events().into_iter()
+        .filter_map(|event| match event {
+            Event::RateLimited { delay_ms, .. } => Some(delay_ms),
+            _ => None,
+        })
+        .collect();
+    assert!(waits.len() == 1 && waits[0] > 400, "{:?}", waits);
+}
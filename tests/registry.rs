@@ -0,0 +1,150 @@
+mod common;
+
+use common::{scratch, EventLog, StubResponse, StubServer};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tldrs::llm::chat::ProviderFuture;
+use tldrs::llm::client::{LlmClient, Reply};
+use tldrs::llm::config::CallParams;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{
+    CallKind, ChatProvider, GenerationSettings, Message, ProviderConfig, ProviderEntry, ProviderOptions, ProviderRegistry,
+    SpeedOptimizedGenerator, VertexEndpoint,
+};
+use tldrs::llm::CacheMode;
+use tldrs::{Error, Event, LlmProvider, ReadmeBuilder};
+
+/// Replies with the roles of the conversation it was sent, cutting off the first reply
+#[derive(Debug)]
+struct Roles {
+    client: LlmClient,
+    generation: GenerationSettings,
+}
+
+impl ChatProvider for Roles {
+    fn id(&self) -> &'static str {
+        "roles"
+    }
+
+    fn client(&self) -> &LlmClient {
+        &self.client
+    }
+
+    fn client_mut(&mut self) -> &mut LlmClient {
+        &mut self.client
+    }
+
+    fn params(&self, kind: CallKind) -> Result<CallParams, Box<dyn std::error::Error>> {
+        Ok(self.generation.resolve(kind, "roles-1"))
+    }
+
+    fn chat<'a>(&'a self, _params: &'a CallParams, messages: &'a [Message]) -> ProviderFuture<'a, Reply> {
+        let roles: Vec<String> = messages.iter().map(|message| format!("{:?}", message.role).to_lowercase()).collect();
+        Box::pin(async move { Ok(Reply { text: format!("[{}]", roles.join(" ")), truncated: messages.len() == 2 }) })
+    }
+
+    fn list_models(&mut self) -> ProviderFuture<'_, Vec<String>> {
+        Box::pin(async { Ok(vec!["roles-1".to_string()]) })
+    }
+}
+
+fn roles(config: ProviderConfig) -> Box<dyn ChatProvider> {
+    Box::new(Roles { client: LlmClient::new(config.api_key, config.reporter), generation: config.options.generation })
+}
+
+fn registry() -> ProviderRegistry {
+    let mut registry = ProviderRegistry::default();
+    registry.register("roles", ProviderEntry { label: "Roles", api_key_env: Some("TLDRS_TEST_ROLES_KEY"), key_required: true, build: roles });
+    registry
+}
+
+#[tokio::test]
+async fn registered_providers_share_the_generation_code() {
+    let options = ProviderOptions { max_continuations: Some(1), ..ProviderOptions::default() };
+    let mut generator = registry().build("Roles", "key".to_string(), options, Arc::new(EventLog::default())).unwrap();
+    generator.prepare().await.unwrap();
+    assert_eq!(generator.list_models().await.unwrap(), ["roles-1"]);
+
+    // The cut-off reply is continued with the conversation so far, as for any built-in provider
+    let readme = generator.generate_readme_fast("fn main() {}", "prompt").await.unwrap();
+    assert_eq!(readme, "[system user][system user assistant user]");
+    assert_eq!(generator.call_summary("fn main() {}").await.unwrap(), "[system user][system user assistant user]");
+}
+
+#[tokio::test]
+async fn gemini_sends_system_messages_as_its_system_instruction() {
+    let server = StubServer::start(|request| {
+        if request.path == "/token" {
+            return StubResponse::json(200, json!({ "access_token": "token", "expires_in": 3600, "token_type": "Bearer" }));
+        }
+        StubResponse::json(200, json!({
+            "candidates": [{ "content": { "role": "model", "parts": [{ "text": "# Demo" }] }, "finishReason": "STOP" }]
+        }))
+    }).await;
+    let key_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json");
+    let endpoint = VertexEndpoint::new(Some(key_file), None, Some("europe-west4".to_string()))
+        .with_base_url(&server.url)
+        .with_token_url(format!("{}/token", server.url));
+    let mut generator = SpeedOptimizedGenerator::vertex(endpoint, Arc::new(EventLog::default()));
+    generator.prepare().await.unwrap();
+
+    generator.call_main("fn main() {}", "Be concise.").await.unwrap();
+    let body = server.requests().into_iter().rfind(|request| request.path != "/token").unwrap().json();
+    assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be concise.");
+    let contents = body["contents"].as_array().unwrap();
+    assert_eq!(contents.len(), 1);
+    assert_eq!(contents[0]["role"], "user");
+    let prompt = contents[0]["parts"][0]["text"].as_str().unwrap();
+    assert!(prompt.contains("fn main() {}") && !prompt.contains("Be concise."), "{}", prompt);
+}
+
+#[test]
+fn the_registry_names_its_providers_and_their_keys() {
+    let registry = registry();
+    let names: Vec<&str> = registry.names().collect();
+    assert_eq!(names, ["anthropic", "azure", "gemini", "mock", "ollama", "openai", "openai-compatible", "roles", "vertex"]);
+    assert_eq!(registry.get("openai-compatible").unwrap().label, "OpenAI-compatible endpoint");
+
+    let err = registry.resolve_api_key("roles", None).unwrap_err();
+    assert!(matches!(err, Error::MissingApiKey { env_var: "TLDRS_TEST_ROLES_KEY", .. }), "{:?}", err);
+    assert_eq!(registry.resolve_api_key("roles", Some("key".to_string())).unwrap(), "key");
+    assert_eq!(registry.resolve_api_key("ollama", None).unwrap(), "");
+
+    let err = registry.build("nope", String::new(), ProviderOptions::default(), Arc::new(EventLog::default())).unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+    assert!(err.to_string().contains("Unknown provider `nope`"), "{}", err);
+}
+
+#[tokio::test]
+async fn readme_runs_pick_providers_from_their_registry() {
+    let dir = scratch("readme");
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    let mut registry = registry();
+    registry.register("keyless", ProviderEntry { label: "Keyless", api_key_env: None, key_required: false, build: roles });
+    let builder = || ReadmeBuilder::new(&dir).registry(registry.clone()).cache(CacheMode::Off);
+
+    let reporter = Arc::new(EventLog::default());
+    let readme = builder().provider_name("roles").api_key("key").reporter(reporter.clone()).generate().await.unwrap();
+    assert_eq!(readme.content, "[system user][system user assistant user]");
+    assert_eq!(readme.provider, "roles");
+    assert!(reporter.events().iter().any(|event| matches!(event, Event::GenerationStarted { provider, .. } if provider == "Roles")));
+
+    // Registered providers can be fallbacks too; those without a key are left out, unknown names are mistakes
+    let reporter = Arc::new(EventLog::default());
+    builder().provider(LlmProvider::Mock).fallback("keyless".parse().unwrap()).fallback("roles".parse().unwrap())
+        .reporter(reporter.clone()).generate().await.unwrap();
+    let warnings: Vec<String> = reporter.events().into_iter()
+        .filter_map(|event| match event {
+            Event::Warning { message } => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert!(warnings.len() == 1 && warnings[0].starts_with("Leaving roles out of the fallback chain"), "{:?}", warnings);
+    let err = builder().provider(LlmProvider::Mock).fallback("nope".parse().unwrap()).generate().await.unwrap_err();
+    assert!(matches!(&err, Error::Config { .. }) && err.to_string().contains("Unknown provider `nope`"), "{:?}", err);
+    let err = ReadmeBuilder::new(&dir).provider_name("roles").generate().await.unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
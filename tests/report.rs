@@ -0,0 +1,67 @@
+mod common;
+
+use common::scratch;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A fresh project with one source file and one lock file
+fn project(name: &str) -> PathBuf {
+    let dir = scratch(name);
+    std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(dir.join("Cargo.lock"), "version = 3\n").unwrap();
+    dir
+}
+
+fn tldrs(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tldrs"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .unwrap()
+}
+
+fn parse_events(output: &Output) -> Vec<Value> {
+    String::from_utf8_lossy(&output.stderr).lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|err| panic!("{}: {}", err, line)))
+        .collect()
+}
+
+#[test]
+fn json_events_are_one_object_per_line() {
+    let dir = project("json");
+
+    let output = tldrs(&dir, &["scan", ".", "--events", "json"]);
+    assert!(output.status.success());
+    let events = parse_events(&output);
+    let names: Vec<&str> = events.iter().map(|event| event["event"].as_str().unwrap()).collect();
+    assert_eq!(names, ["scan_started", "file_skipped", "scan_finished", "file_read", "files_loaded"]);
+    assert_eq!(events[1]["reason"], "ignored_pattern");
+    assert_eq!((&events[2]["included"], &events[2]["discovered"]), (&Value::from(1), &Value::from(2)));
+
+    // Failures arrive as an event too, rather than as a status line
+    let output = tldrs(&dir, &["readme", ".", "--provider", "anthropic", "--events", "json"]);
+    assert_eq!(output.status.code(), Some(3));
+    let events = parse_events(&output);
+    let failed = events.last().unwrap();
+    assert_eq!((&failed["event"], &failed["action"], &failed["exit_code"]), (&Value::from("failed"), &Value::from("generating README"), &Value::from(3)));
+    assert!(failed["error"].as_str().unwrap().contains("No API key for anthropic"), "{}", failed);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failures_follow_the_plain_reporter() {
+    let dir = project("plain");
+
+    for args in [&["readme", ".", "--provider", "anthropic"][..], &["readme", ".", "--provider", "anthropic", "--quiet"]] {
+        let output = tldrs(&dir, args);
+        assert_eq!(output.status.code(), Some(3));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Error generating README: No API key for anthropic"), "{}", stderr);
+        assert!(!stderr.contains('❌'), "{}", stderr);
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
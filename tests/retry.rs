@@ -0,0 +1,86 @@
+mod common;
+
+use common::{completion, EventLog, StubResponse, StubServer};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ApiError, ChatProvider, OpenAiGenerator, RetryPolicy};
+use tldrs::Event;
+
+/// Answer the first `failures` requests with `failure`, then succeed
+async fn flaky_server(failures: usize, failure: StubResponse) -> StubServer {
+    let calls = Arc::new(AtomicUsize::new(0));
+    StubServer::start(move |_| {
+        if calls.fetch_add(1, Ordering::SeqCst) < failures {
+            failure.clone()
+        } else {
+            completion("# Demo", "stop")
+        }
+    }).await
+}
+
+fn generator(server: &StubServer, reporter: Arc<EventLog>) -> OpenAiGenerator {
+    common::generator(server, reporter)
+        .with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+        })
+}
+
+fn retries(reporter: &EventLog) -> Vec<u64> {
+    reporter.events().into_iter()
+        .filter_map(|event| match event {
+            Event::RetryScheduled { delay_ms, .. } => Some(delay_ms),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn retries_server_errors_until_the_call_succeeds() {
+    let server = flaky_server(2, StubResponse::text(503, "overloaded")).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let readme = generator(&server, reporter.clone()).call_main("context", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo");
+    assert_eq!(server.requests().len(), 3);
+    assert_eq!(retries(&reporter).len(), 2);
+}
+
+#[tokio::test]
+async fn honors_retry_after() {
+    let server = flaky_server(1, StubResponse::text(429, "slow down").header("retry-after-ms", "250")).await;
+    let reporter = Arc::new(EventLog::default());
+
+    generator(&server, reporter.clone()).call_main("context", "prompt").await.unwrap();
+    assert_eq!(retries(&reporter), vec![250]);
+}
+
+#[tokio::test]
+async fn gives_up_after_the_last_retry_with_the_status() {
+    let server = StubServer::always(StubResponse::text(502, "bad gateway")).await;
+    let reporter = Arc::new(EventLog::default());
+
+    let err = generator(&server, reporter.clone()).call_main("context", "prompt").await.unwrap_err();
+    let err = err.downcast_ref::<ApiError>().expect("not an API error");
+    assert_eq!(err.status().map(|status| status.as_u16()), Some(502));
+    assert!(err.to_string().contains("bad gateway"), "{}", err);
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn does_not_retry_client_errors_or_long_waits() {
+    let server = StubServer::always(StubResponse::text(401, "invalid api key")).await;
+    let reporter = Arc::new(EventLog::default());
+    let err = generator(&server, reporter.clone()).call_main("context", "prompt").await.unwrap_err();
+    assert!(!err.downcast_ref::<ApiError>().unwrap().is_retryable());
+    assert_eq!(server.requests().len(), 1);
+
+    // Waiting out an hour-long quota reset is left to the user
+    let server = StubServer::always(StubResponse::text(429, "slow down").header("Retry-After", "3600")).await;
+    generator(&server, reporter.clone()).call_main("context", "prompt").await.unwrap_err();
+    assert_eq!(server.requests().len(), 1);
+    assert!(retries(&reporter).is_empty());
+}
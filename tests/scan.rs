@@ -0,0 +1,70 @@
+mod common;
+
+use common::scratch;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A fresh project directory for one test
+fn project(name: &str) -> PathBuf {
+    let dir = scratch(name);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    dir
+}
+
+fn scan_json(dir: &Path) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_tldrs"))
+        .args(["scan", ".", "--compact"])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    serde_json::from_str(&stdout).unwrap()
+}
+
+#[test]
+fn every_skipped_file_says_why() {
+    let dir = project("reasons");
+    std::fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+    std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+    std::fs::create_dir_all(dir.join("node_modules/left-pad")).unwrap();
+    std::fs::write(dir.join("node_modules/left-pad/index.js"), "module.exports = 1;\n").unwrap();
+    std::fs::write(dir.join("Cargo.lock"), "version = 3\n").unwrap();
+    std::fs::write(dir.join("huge.rs"), "x".repeat(1_000_001)).unwrap();
+    std::fs::write(dir.join("program"), b"\x7fELF\x00\x00\x00\x00 not text").unwrap();
+    std::fs::write(dir.join("notes.xyz"), "hello\n").unwrap();
+    std::fs::write(dir.join("src/empty.rs"), "  \n").unwrap();
+    std::fs::write(dir.join("src/latin1.rs"), b"// caf\xe9\n").unwrap();
+    std::fs::write(dir.join("broken.ipynb"), "{ not a notebook").unwrap();
+
+    let analysis = scan_json(&dir);
+    let mut reasons: Vec<(String, Value)> = analysis["files"].as_array().unwrap().iter()
+        .map(|file| (file["path"].as_str().unwrap().to_string(), file["skip_reason"].clone()))
+        .collect();
+    reasons.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let expected = [
+        ("Cargo.lock", "ignored_pattern"),
+        ("Cargo.toml", ""),
+        ("broken.ipynb", "invalid_notebook"),
+        ("huge.rs", "too_large"),
+        ("node_modules/", "ignored_directory"),
+        ("notes.xyz", "unsupported_type"),
+        ("program", "binary"),
+        ("src/empty.rs", "empty"),
+        ("src/latin1.rs", "unreadable"),
+        ("src/main.rs", ""),
+    ];
+    let expected: Vec<(String, Value)> = expected.iter()
+        .map(|(path, reason)| (path.to_string(), if reason.is_empty() { Value::Null } else { Value::from(*reason) }))
+        .collect();
+    assert_eq!(reasons, expected);
+
+    assert_eq!((analysis["included"].as_u64(), analysis["skipped"].as_u64()), (Some(2), Some(8)));
+    let directory = analysis["files"].as_array().unwrap().iter().find(|file| file["path"] == "node_modules/").unwrap();
+    assert_eq!((&directory["included"], &directory["size"]), (&Value::Bool(false), &Value::Null));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
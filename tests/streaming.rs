@@ -0,0 +1,183 @@
+mod common;
+
+use common::{EventLog, StubResponse, StubServer};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tldrs::llm::sse::{SseEvent, SseParser};
+use tldrs::llm::streaming::{LlmApiClient, StreamingSectionGenerator};
+use tldrs::llm::{ChatProvider, OpenAiGenerator, SpeedOptimizedGenerator, VertexEndpoint};
+use tldrs::{Event, QuietReporter};
+
+fn event_stream(events: &[Value]) -> StubResponse {
+    let body = events.iter().map(|event| format!("data: {}\n\n", event)).collect::<String>();
+    StubResponse::with_content_type(200, "text/event-stream", body)
+}
+
+fn openai_chunk(content: &str) -> Value {
+    json!({ "choices": [{ "index": 0, "delta": { "content": content }, "finish_reason": null }] })
+}
+
+fn openai_generator(server: &StubServer, reporter: Arc<EventLog>) -> OpenAiGenerator {
+    OpenAiGenerator::compatible(format!("{}/v1", server.url), Some("local-model".to_string()), String::new(), reporter)
+}
+
+async fn collect(client: &impl LlmApiClient) -> (Vec<String>, Result<(), String>) {
+    let mut deltas = Vec::new();
+    let result = client
+        .stream_with_custom_context("section", "context", &mut |text| {
+            deltas.push(text.to_string());
+            Ok(())
+        })
+        .await
+        .map_err(|err| err.to_string());
+    (deltas, result)
+}
+
+#[test]
+fn parser_waits_for_characters_split_across_chunks() {
+    let body = "data: héllo\n\n".as_bytes();
+    // Split inside the two-byte `é`
+    let split = body.iter().position(|&byte| byte == 0xC3).unwrap() + 1;
+
+    let mut parser = SseParser::new();
+    assert!(parser.feed(&body[..split]).unwrap().is_empty());
+    let events = parser.feed(&body[split..]).unwrap();
+    assert_eq!(events, vec![SseEvent { event: None, data: "héllo".to_string() }]);
+}
+
+#[test]
+fn parser_handles_crlf_comments_and_multiline_data() {
+    let mut parser = SseParser::new();
+    let events = parser
+        .feed(b": keep-alive\r\nevent: delta\r\ndata: one\r\ndata: two\r\n\r\ndata: tail")
+        .unwrap();
+    assert_eq!(events, vec![SseEvent { event: Some("delta".to_string()), data: "one\ntwo".to_string() }]);
+
+    // A body that ends without the closing blank line still yields its last event
+    assert_eq!(parser.finish().unwrap(), Some(SseEvent { event: None, data: "tail".to_string() }));
+}
+
+#[tokio::test]
+async fn openai_streams_deltas_and_reports_usage() {
+    let server = StubServer::always(event_stream(&[
+        openai_chunk("# De"),
+        openai_chunk("mo"),
+        json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] }),
+        json!({ "choices": [], "usage": { "prompt_tokens": 12, "completion_tokens": 3 } }),
+    ])).await;
+    let reporter = Arc::new(EventLog::default());
+    let generator = OpenAiGenerator::new("test-key".to_string(), reporter.clone()).with_base_url(format!("{}/v1", server.url));
+
+    let (deltas, result) = collect(&generator).await;
+    result.unwrap();
+    assert_eq!(deltas, vec!["# De", "mo"]);
+
+    let body = server.requests()[0].json();
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["stream_options"]["include_usage"], true);
+    assert!(reporter.events().iter().any(|event| matches!(
+        event,
+        Event::TokensUsed { input_tokens: 12, output_tokens: 3, .. }
+    )));
+
+    // Compatible servers are not asked for usage, but usage they send anyway still counts
+    let reporter = Arc::new(EventLog::default());
+    let (_, result) = collect(&openai_generator(&server, reporter.clone())).await;
+    result.unwrap();
+    assert!(server.requests()[1].json().get("stream_options").is_none());
+    assert!(reporter.events().iter().any(|event| matches!(event, Event::TokensUsed { input_tokens: 12, .. })));
+}
+
+#[tokio::test]
+async fn openai_reports_errors_and_streams_cut_short() {
+    let server = StubServer::always(event_stream(&[
+        openai_chunk("Partial"),
+        json!({ "error": { "message": "overloaded" } }),
+    ])).await;
+    let generator = openai_generator(&server, Arc::new(EventLog::default()));
+    let (deltas, result) = collect(&generator).await;
+    assert_eq!(deltas, vec!["Partial"]);
+    let err = result.unwrap_err();
+    assert!(err.contains("mid-stream") && err.contains("overloaded"), "{}", err);
+
+    let server = StubServer::always(event_stream(&[openai_chunk("Partial")])).await;
+    let generator = openai_generator(&server, Arc::new(EventLog::default()));
+    let (_, result) = collect(&generator).await;
+    assert!(result.unwrap_err().contains("ended before the reply was complete"));
+}
+
+#[tokio::test]
+async fn vertex_streams_over_sse() {
+    let server = StubServer::start(|request| {
+        if request.path == "/token" {
+            StubResponse::json(200, json!({ "access_token": "token-1", "expires_in": 3600 }))
+        } else {
+            event_stream(&[
+                json!({ "candidates": [{ "content": { "parts": [{ "text": "# Demo" }] } }] }),
+                json!({
+                    "candidates": [{ "content": { "parts": [{ "text": "\n" }] }, "finishReason": "STOP" }],
+                    "usageMetadata": { "promptTokenCount": 20, "candidatesTokenCount": 4 }
+                }),
+            ])
+        }
+    }).await;
+    let key_file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json");
+    let endpoint = VertexEndpoint::new(Some(key_file), None, Some("europe-west4".to_string()))
+        .with_base_url(&server.url)
+        .with_token_url(format!("{}/token", server.url));
+    let mut generator = SpeedOptimizedGenerator::vertex(endpoint, Arc::new(QuietReporter));
+    generator.prepare().await.unwrap();
+
+    let (deltas, result) = collect(&generator).await;
+    result.unwrap();
+    assert_eq!(deltas, vec!["# Demo", "\n"]);
+
+    let request = server.requests().into_iter().find(|request| request.path != "/token").unwrap();
+    assert!(request.path.ends_with("gemini-2.5-pro:streamGenerateContent?alt=sse"), "{}", request.path);
+}
+
+// Several threads, so the stub server can hold one reply back while others are answered
+#[tokio::test(flavor = "multi_thread")]
+async fn sections_run_concurrently_and_are_written_in_order() {
+    let server = StubServer::start(|request| {
+        let task = request.json()["messages"][1]["content"].as_str().unwrap_or_default().to_string();
+        let done = json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] });
+        if task.contains("project title") {
+            // The first section finishes last
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            event_stream(&[openai_chunk("```mark"), openai_chunk("down\n# Demo\n"), openai_chunk("```"), done])
+        } else if task.contains("description") {
+            event_stream(&[openai_chunk("Half a descr")])
+        } else if task.contains("## Features") {
+            event_stream(&[openai_chunk("## Features"), done])
+        } else if task.contains("## Installation") {
+            event_stream(&[openai_chunk("## Installation"), done])
+        } else {
+            event_stream(&[openai_chunk("## Usage"), done])
+        }
+    }).await;
+    let reporter = Arc::new(EventLog::default());
+    let generator = openai_generator(&server, reporter.clone());
+
+    let mut output = Vec::new();
+    let err = StreamingSectionGenerator::new(reporter.clone())
+        .with_concurrency(3)
+        .generate_sections_incrementally(&generator, "fn main() {}", &mut output, "prompt")
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("description section") && err.contains("ended before the reply was complete"), "{}", err);
+
+    // The failed section keeps its partial text and does not stop the sections after it
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("# Demo\n\nHalf a descr\n\n<!-- description section failed:"), "{}", output);
+    assert!(output.ends_with("-->\n\n## Features\n\n## Installation\n\n## Usage\n\n"), "{}", output);
+
+    let events = reporter.events();
+    let position = |wanted: &dyn Fn(&Event) -> bool| events.iter().position(wanted).unwrap();
+    let features_started = position(&|event| matches!(event, Event::SectionStarted { section } if section == "features"));
+    let title_finished = position(&|event| matches!(event, Event::SectionFinished { section } if section == "title"));
+    assert!(features_started < title_finished);
+    assert!(events.iter().any(|event| matches!(event, Event::SectionFailed { section, .. } if section == "description")));
+}
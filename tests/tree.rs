@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use tldrs::util::readme::pack_context;
+use tldrs::util::tree::ProjectTree;
+use tldrs::ScannedFile;
+
+fn file(path: &str, content: &str) -> ScannedFile {
+    ScannedFile {
+        path: PathBuf::from(path),
+        relative_path: PathBuf::from(path),
+        content: content.to_string().into(),
+        priority: 50,
+        size: content.len() as u64,
+        modified: None,
+        hash: String::new(),
+        language: "Rust".to_string(),
+        outline: Vec::new(),
+    }
+}
+
+fn tree(paths: impl IntoIterator<Item = String>) -> ProjectTree {
+    let files: Vec<ScannedFile> = paths.into_iter().map(|path| file(&path, "fn main() {}\n")).collect();
+    ProjectTree::build("demo", &files)
+}
+
+#[test]
+fn small_trees_are_rendered_in_full() {
+    let mut paths = vec!["Cargo.toml".to_string(), "src/main.rs".to_string()];
+    paths.extend((0..9).map(|index| format!("src/gen/file{}.rs", index)));
+
+    assert_eq!(
+        tree(paths).render(),
+        "demo/ (11 files, Rust)\n\
+         ├── src/ (10 files, Rust)\n\
+         │   ├── gen/ (9 files, Rust)\n\
+         │   └── main.rs\n\
+         └── Cargo.toml\n"
+    );
+}
+
+#[test]
+fn long_trees_drop_nested_files_then_deep_directories() {
+    // Too many files: only top-level files are listed
+    let mut paths: Vec<String> = (0..40).flat_map(|dir| (0..4).map(move |index| format!("src/m{:02}/f{}.rs", dir, index))).collect();
+    paths.push("main.rs".to_string());
+    let rendered = tree(paths).render();
+    assert!(rendered.lines().count() <= 150, "{}", rendered);
+    assert!(rendered.contains("├── m00/ (4 files, Rust)") && rendered.contains("└── main.rs"), "{}", rendered);
+    assert!(!rendered.contains("f0.rs"), "{}", rendered);
+
+    // Too many directories: fewer levels are expanded, and only the directories shown are listed
+    let monorepo = tree((0..200).map(|index| format!("packages/p{:03}/src/lib.rs", index)));
+    let rendered = monorepo.render();
+    assert_eq!(rendered, "demo/ (200 files, Rust)\n└── packages/ (200 files, Rust)\n");
+    assert_eq!(monorepo.directories(), ["packages/"]);
+}
+
+#[test]
+fn trees_that_never_fit_are_cut_off() {
+    let wide = tree((0..400).map(|index| format!("d{:03}/lib.rs", index)));
+
+    let rendered = wide.render();
+    assert_eq!(rendered.lines().count(), 150);
+    assert!(rendered.ends_with("└── … 252 more lines\n"), "{}", rendered);
+
+    let short = wide.render_within(500);
+    assert!(short.len() <= 500 && short.contains("more lines"), "{}", short);
+}
+
+#[test]
+fn the_tree_counts_against_the_context_budget() {
+    let files: Vec<ScannedFile> = (0..400).map(|index| file(&format!("d{:03}/main.rs", index), &"x".repeat(200))).collect();
+    let tree = ProjectTree::build("demo", &files);
+
+    let context = pack_context(&tree, &files, 4000);
+    assert!(context.len() <= 4000 + "...[truncated]".len(), "{} characters", context.len());
+    assert!(context.starts_with("Project structure:\n```\ndemo/ (400 files, Rust)\n"), "{}", context);
+    assert!(context.contains("d000/main.rs:\nxxx"), "{}", context);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{completion_with_usage, model_generator, EventLog, StubServer};
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{Budget, BudgetExceeded, ChatProvider, ModelPrice, OpenAiGenerator, PriceTable, UsageTracker};
+
+fn generator(server: &StubServer, model: &str, usage: &Arc<UsageTracker>) -> OpenAiGenerator {
+    model_generator(server, model, Arc::new(EventLog::default()))
+        .with_usage_tracker(Some(usage.clone()))
+}
+
+#[test]
+fn prices_match_the_longest_model_prefix() {
+    let mut prices = PriceTable::default();
+    assert_eq!(prices.price("openai", "gpt-4o-mini-2024-07-18"), Some(ModelPrice { input: 0.15, output: 0.60 }));
+    assert_eq!(prices.price("ollama", "llama3.2"), Some(ModelPrice { input: 0.0, output: 0.0 }));
+    assert_eq!(prices.price("openai", "my-finetune"), None);
+
+    prices.set("my-finetune", ModelPrice { input: 1.0, output: 2.0 });
+    assert_eq!(prices.price("openai", "my-finetune-v2").map(|price| price.cost(1_000_000, 500_000)), Some(2.0));
+}
+
+#[tokio::test]
+async fn usage_is_added_up_per_model_with_its_cost() {
+    let server = StubServer::always(completion_with_usage("# Demo", 1000, 500)).await;
+    let usage = Arc::new(UsageTracker::default());
+    let generator = generator(&server, "gpt-4o", &usage);
+
+    generator.call_main("fn main() {}", "prompt").await.unwrap();
+    generator.call_main("fn main() {}", "prompt").await.unwrap();
+
+    let summary = usage.summary();
+    assert_eq!((summary.calls, summary.input_tokens, summary.output_tokens), (2, 2000, 1000));
+    let cost = summary.cost_usd.unwrap();
+    assert!((cost - 0.015).abs() < 1e-9, "{}", cost);
+    assert_eq!(summary.models.len(), 1);
+    assert_eq!(summary.models[0].model, "gpt-4o");
+}
+
+#[tokio::test]
+async fn calls_that_could_break_the_budget_are_not_made() {
+    let server = StubServer::always(completion_with_usage("# Demo", 1000, 500)).await;
+
+    // The call's output limit alone is over the token budget
+    let usage = Arc::new(UsageTracker::new(PriceTable::default(), Budget { max_tokens: Some(1000), max_cost: None }));
+    let err = generator(&server, "gpt-4o", &usage).call_main("fn main() {}", "prompt").await.unwrap_err();
+    assert!(err.is::<BudgetExceeded>(), "{}", err);
+    assert!(server.requests().is_empty());
+
+    // A cost budget needs a price for every model
+    let usage = Arc::new(UsageTracker::new(PriceTable::default(), Budget { max_tokens: None, max_cost: Some(1.0) }));
+    let err = generator(&server, "my-finetune", &usage).call_main("fn main() {}", "prompt").await.unwrap_err();
+    assert!(err.to_string().contains("No price is known for my-finetune"), "{}", err);
+
+    // A call could cost about 4 cents, which fits once, but not on top of the first call's 0.75 cents
+    let usage = Arc::new(UsageTracker::new(PriceTable::default(), Budget { max_tokens: None, max_cost: Some(0.045) }));
+    let generator = generator(&server, "gpt-4o", &usage);
+    generator.call_main("fn main() {}", "prompt").await.unwrap();
+    let err = generator.call_main("fn main() {}", "prompt").await.unwrap_err();
+    assert!(err.to_string().contains("over the budget of $0.0450"), "{}", err);
+    assert_eq!(server.requests().len(), 1);
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use common::{RecordedRequest, StubResponse, StubServer};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tldrs::llm::streaming::LlmApiClient;
+use tldrs::llm::{ChatProvider, SpeedOptimizedGenerator, VertexEndpoint};
+use tldrs::QuietReporter;
+
+const GENERATE_PATH: &str =
+    "/v1/projects/tldrs-test-project/locations/europe-west4/publishers/google/models/gemini-2.0-flash:generateContent";
+
+fn key_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vertex_service_account.json")
+}
+
+fn generation(text: &str) -> StubResponse {
+    StubResponse::json(200, json!({
+        "candidates": [{
+            "content": { "role": "model", "parts": [{ "text": text }] },
+            "finishReason": "STOP"
+        }],
+        "usageMetadata": { "promptTokenCount": 40, "candidatesTokenCount": 8 }
+    }))
+}
+
+/// Serve the token endpoint with tokens that live `expires_in` seconds, numbered per exchange
+async fn vertex_server(expires_in: u64) -> StubServer {
+    let exchanges = Arc::new(AtomicUsize::new(0));
+    StubServer::start(move |request| {
+        if request.path == "/token" {
+            let n = exchanges.fetch_add(1, Ordering::SeqCst) + 1;
+            StubResponse::json(200, json!({
+                "access_token": format!("token-{}", n),
+                "expires_in": expires_in,
+                "token_type": "Bearer"
+            }))
+        } else {
+            generation("# Demo")
+        }
+    }).await
+}
+
+fn generator(server: &StubServer) -> SpeedOptimizedGenerator {
+    let endpoint = VertexEndpoint::new(Some(key_file()), None, Some("europe-west4".to_string()))
+        .with_base_url(&server.url)
+        .with_token_url(format!("{}/token", server.url));
+    SpeedOptimizedGenerator::vertex(endpoint, Arc::new(QuietReporter))
+}
+
+fn token_requests(server: &StubServer) -> Vec<RecordedRequest> {
+    server.requests().into_iter().filter(|request| request.path == "/token").collect()
+}
+
+fn form_value<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    body.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+#[tokio::test]
+async fn exchanges_a_signed_assertion_and_calls_the_regional_path() {
+    let server = vertex_server(3600).await;
+    let mut generator = generator(&server);
+    generator.prepare().await.unwrap();
+
+    let readme = generator.call_main("context", "prompt").await.unwrap();
+    assert_eq!(readme, "# Demo");
+
+    let tokens = token_requests(&server);
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(
+        form_value(&tokens[0].body, "grant_type"),
+        Some("urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer")
+    );
+
+    // The signature is not checked here, only what the assertion claims
+    let assertion = form_value(&tokens[0].body, "assertion").unwrap();
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.insecure_disable_signature_validation();
+    validation.set_audience(&[format!("{}/token", server.url)]);
+    let claims = decode::<Value>(assertion, &DecodingKey::from_secret(&[]), &validation).unwrap();
+    assert_eq!(claims.header.kid.as_deref(), Some("test-key-1"));
+    assert_eq!(claims.claims["iss"], "tldrs-test@tldrs-test-project.iam.gserviceaccount.com");
+    assert_eq!(claims.claims["scope"], "https://www.googleapis.com/auth/cloud-platform");
+
+    let request = server.requests().into_iter().find(|request| request.path != "/token").unwrap();
+    assert_eq!(request.path, GENERATE_PATH);
+    assert_eq!(request.header("authorization"), Some("Bearer token-1"));
+    assert_eq!(request.json()["contents"][0]["role"], "user");
+    assert_eq!(request.json()["generationConfig"]["maxOutputTokens"], 4000);
+}
+
+#[tokio::test]
+async fn reuses_the_cached_token() {
+    let server = vertex_server(3600).await;
+    let mut generator = generator(&server);
+    generator.prepare().await.unwrap();
+
+    generator.call_main("context", "prompt").await.unwrap();
+    generator.call_main("context", "prompt").await.unwrap();
+
+    assert_eq!(token_requests(&server).len(), 1);
+}
+
+#[tokio::test]
+async fn refreshes_a_token_that_is_about_to_expire() {
+    let server = vertex_server(30).await;
+    let mut generator = generator(&server);
+    generator.prepare().await.unwrap();
+
+    generator.call_main("context", "prompt").await.unwrap();
+
+    assert_eq!(token_requests(&server).len(), 2);
+    let request = server.requests().into_iter().find(|request| request.path != "/token").unwrap();
+    assert_eq!(request.header("authorization"), Some("Bearer token-2"));
+}
+
+#[tokio::test]
+async fn reports_a_rejected_token_exchange() {
+    let server = StubServer::always(StubResponse::json(400, json!({
+        "error": "invalid_grant",
+        "error_description": "Invalid JWT Signature."
+    }))).await;
+    let mut generator = generator(&server);
+
+    let err = generator.prepare().await.unwrap_err().to_string();
+    assert!(err.contains("Token exchange"), "{}", err);
+    assert!(err.contains("invalid_grant"), "{}", err);
+}
+
+#[tokio::test]
+async fn reports_a_missing_key_file() {
+    let endpoint = VertexEndpoint::new(Some(PathBuf::from("does/not/exist.json")), Some("project".to_string()), None);
+    let mut generator = SpeedOptimizedGenerator::vertex(endpoint, Arc::new(QuietReporter));
+
+    let err = generator.prepare().await.unwrap_err().to_string();
+    assert!(err.contains("does/not/exist.json"), "{}", err);
+}